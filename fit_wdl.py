@@ -0,0 +1,148 @@
+import collections
+import math
+import sys
+
+# Fits the win rate model of win_rate_model() in src/uci.rs to the text
+# output of gensfen, lines of "<fen> | <score> | <result>" with the score in
+# centipawns and the result 1.0, 0.5 or 0.0, both for white:
+#
+#   ./target/release/rustfish gensfen fit.txt positions 150000 depth 6 seed 7 format text threads 1
+#   python3 fit_wdl.py fit.txt
+#
+# The model gives the win rate of a score x at ply p as
+#
+#   1 / (1 + exp((a - x) / b))
+#
+# with a and b third order polynomials in m = min(p, 240) / 64. For every
+# 8 plies, a and b are fitted by maximum likelihood of the win, draw and
+# loss outcomes, where the loss rate is the win rate of -x. The polynomials
+# are then fitted to them by least squares, weighted by the number of
+# positions.
+
+PLY_STEP = 8
+MAX_PLY = 240
+
+
+def sigmoid(t):
+    if t < -50:
+        return 0.0
+    if t > 50:
+        return 1.0
+    return 1.0 / (1.0 + math.exp(-t))
+
+
+def probability(a, b, x, result):
+    win = sigmoid((x - a) / b)
+    loss = sigmoid((-x - a) / b)
+    p = [loss, 1.0 - win - loss, win][result]
+    return max(p, 1e-12)
+
+
+def log_loss(a, b, counts):
+    if b <= 1.0:
+        return float("inf")
+    return -sum(n * math.log(probability(a, b, x, r)) for (x, r), n in counts.items())
+
+
+def nelder_mead(f, x0, step, iterations=400):
+    n = len(x0)
+    points = [list(x0)]
+    for i in range(n):
+        points.append([x0[j] + (step[j] if j == i else 0.0) for j in range(n)])
+    values = [f(p) for p in points]
+
+    for _ in range(iterations):
+        order = sorted(range(n + 1), key=lambda i: values[i])
+        points = [points[i] for i in order]
+        values = [values[i] for i in order]
+        centroid = [sum(p[j] for p in points[:-1]) / n for j in range(n)]
+
+        def towards(t):
+            return [centroid[j] + t * (points[-1][j] - centroid[j]) for j in range(n)]
+
+        reflected = towards(-1.0)
+        fr = f(reflected)
+        if fr < values[0]:
+            expanded = towards(-2.0)
+            fe = f(expanded)
+            points[-1], values[-1] = (expanded, fe) if fe < fr else (reflected, fr)
+        elif fr < values[-2]:
+            points[-1], values[-1] = reflected, fr
+        else:
+            contracted = towards(0.5)
+            fc = f(contracted)
+            if fc < values[-1]:
+                points[-1], values[-1] = contracted, fc
+            else:
+                for i in range(1, n + 1):
+                    points[i] = [points[0][j] + 0.5 * (points[i][j] - points[0][j]) for j in range(n)]
+                    values[i] = f(points[i])
+
+    return points[min(range(n + 1), key=lambda i: values[i])]
+
+
+# polyfit() returns the coefficients of the weighted least squares cubic,
+# highest power first as in src/uci.rs
+
+def polyfit(xs, ys, weights, degree=3):
+    k = degree + 1
+    a = [[sum(w * x ** (i + j) for x, w in zip(xs, weights)) for j in range(k)] for i in range(k)]
+    b = [sum(w * y * x ** i for x, y, w in zip(xs, ys, weights)) for i in range(k)]
+
+    # Gauss-Jordan elimination with partial pivoting
+    for i in range(k):
+        p = max(range(i, k), key=lambda r: abs(a[r][i]))
+        a[i], a[p], b[i], b[p] = a[p], a[i], b[p], b[i]
+        for r in range(k):
+            if r != i:
+                f = a[r][i] / a[i][i]
+                a[r] = [a[r][j] - f * a[i][j] for j in range(k)]
+                b[r] -= f * b[i]
+
+    return [b[i] / a[i][i] for i in range(k)][::-1]
+
+
+def polynomial(c, m):
+    return ((c[0] * m + c[1]) * m + c[2]) * m + c[3]
+
+
+def read(file_name):
+    results = {"0.0": 0, "0.5": 1, "1.0": 2}
+    samples = collections.Counter()
+    for line in open(file_name):
+        fen, score, result = line.split(" | ")
+        fields = fen.split()
+        ply = 2 * (int(fields[5]) - 1) + (fields[1] == "b")
+        x = max(-1000, min(1000, int(score)))
+        samples[(min(ply, MAX_PLY), x, results[result.strip()])] += 1
+    return samples
+
+
+if __name__ == "__main__":
+    samples = read(sys.argv[1])
+
+    buckets = collections.defaultdict(collections.Counter)
+    for (ply, x, r), n in samples.items():
+        buckets[ply // PLY_STEP * PLY_STEP][(x, r)] += n
+
+    ms, As, Bs, weights = [], [], [], []
+    for ply in sorted(buckets):
+        counts = buckets[ply]
+        a, b = nelder_mead(lambda v: log_loss(v[0], v[1], counts), [200.0, 100.0], [50.0, 30.0])
+        ms.append((ply + PLY_STEP / 2) / 64.0)
+        As.append(a)
+        Bs.append(b)
+        weights.append(sum(counts.values()))
+        print("ply %3d-%3d: %6d positions, a %6.1f, b %6.1f" % (ply, ply + PLY_STEP - 1, weights[-1], a, b))
+
+    AS = polyfit(ms, As, weights)
+    BS = polyfit(ms, Bs, weights)
+
+    total = sum(samples.values())
+    loss = sum(
+        n * -math.log(probability(polynomial(AS, p / 64.0), polynomial(BS, p / 64.0), x, r))
+        for (p, x, r), n in samples.items()
+    )
+    print("log loss per position: %.4f" % (loss / total))
+    print("const AS: [f64; 4] = [%s];" % ", ".join("%.8f" % c for c in AS))
+    print("const BS: [f64; 4] = [%s];" % ", ".join("%.8f" % c for c in BS))
@@ -116,7 +116,7 @@ pub fn setup_bench(pos: &Position, args: &str) -> Vec<String> {
         };
         let reader = std::io::BufReader::new(file);
         for fen in reader.lines() {
-            if fen.is_ok() {
+            if fen.is_err() {
                 break;
             }
             let fen = fen.unwrap();
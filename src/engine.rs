@@ -0,0 +1,199 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use bitbases;
+use bitboard;
+use endgame;
+use listener::SearchListener;
+use pawns;
+use nnue::Network;
+use pgn::Game;
+use position;
+use position::{FenError, Position};
+use psqt;
+use search;
+use tb;
+#[cfg(feature = "tune")]
+use tune;
+use threads::{PosData, SearchHandle, ThreadPool};
+use types::*;
+use uci;
+use ucioption;
+use ucioption::OptionsMap;
+
+use std::sync::{Arc, Once, RwLock, RwLockReadGuard};
+
+static INIT: Once = Once::new();
+
+// init() fills the lookup tables. They only depend on constants and are
+// shared by all engines, so this is done once per process.
+
+fn init() {
+    INIT.call_once(|| {
+        psqt::init();
+        bitboard::init();
+        position::zobrist::init();
+        bitbases::init();
+        search::init();
+        pawns::init();
+        endgame::init();
+        #[cfg(feature = "tune")]
+        tune::init();
+    });
+}
+
+/// Engine is a complete, independent chess engine: it owns its options,
+/// transposition table, search threads, search limits and the current
+/// position. Several engines can be used side by side in one process.
+/// Syzygy tablebase files are read-only and shared by all engines.
+pub struct Engine {
+    pool: ThreadPool,
+    pos: Box<Position>,
+    pos_data: Arc<RwLock<PosData>>,
+}
+
+impl Engine {
+    pub fn new() -> Engine {
+        init();
+
+        let options = OptionsMap::new();
+        let hash = options.get_i32("Hash") as usize;
+        let threads = options.get_i32("Threads") as usize;
+        let tb_path = options.get_string("SyzygyPath");
+
+        let mut pool = ThreadPool::new(options);
+        pool.shared.tt.resize(hash);
+        pool.set(threads);
+        *pool.shared.tablebases.write().unwrap() = tb::open(&tb_path);
+        search::clear(&pool);
+
+        let pos = Box::new(Position::from_fen(uci::START_FEN, false).unwrap());
+
+        Engine {
+            pool,
+            pos,
+            pos_data: Arc::new(RwLock::new(PosData {
+                fen: String::from(uci::START_FEN),
+                moves: Vec::new(),
+            })),
+        }
+    }
+
+    pub fn options(&self) -> RwLockReadGuard<'_, OptionsMap> {
+        self.pool.shared.options.read().unwrap()
+    }
+
+    pub fn set_option(&mut self, name: &str, value: &str) -> Result<(), String> {
+        ucioption::set(&mut self.pool, name, value)
+    }
+
+    pub fn print_options(&self) {
+        self.options().print();
+    }
+
+    // set_position() sets up the position described by the FEN string and
+    // then plays the given moves in coordinate notation. Parsing of the
+    // move list stops at the first illegal move. If the FEN string is
+    // invalid, the current position is kept.
+
+    pub fn set_position(&mut self, fen: &str, moves: &[&str]) -> Result<(), FenError> {
+        let chess960 = self.options().get_bool("UCI_Chess960");
+        let pos = Position::from_fen(fen, chess960)?;
+        let mut pos_data = self.pos_data.write().unwrap();
+
+        *self.pos = pos;
+        pos_data.fen = String::from(fen);
+        pos_data.moves = Vec::new();
+
+        for token in moves {
+            let m = uci::to_move(&self.pos, token);
+            if m == Move::NONE {
+                break;
+            }
+            let gives_check = self.pos.gives_check(m);
+            self.pos.do_move(m, gives_check);
+            pos_data.moves.push(m);
+        }
+
+        Ok(())
+    }
+
+    // set_game() sets up the final position of the main line of a game
+
+    pub fn set_game(&mut self, game: &Game) -> Result<(), FenError> {
+        let chess960 = self.options().get_bool("UCI_Chess960");
+        let moves = game.uci_moves(chess960);
+        let moves: Vec<&str> = moves.iter().map(|m| m.as_str()).collect();
+        self.set_position(&game.start_fen(), &moves)
+    }
+
+    pub fn position(&self) -> &Position {
+        &self.pos
+    }
+
+    // network() returns the NNUE network used by the search, if any
+
+    pub fn network(&self) -> Option<Arc<Network>> {
+        self.pool.shared.network.read().unwrap().clone()
+    }
+
+    // set_listener() sets the receiver of the search output, in place of
+    // the UCI output to stdout. It takes effect with the next search.
+
+    pub fn set_listener(&mut self, listener: Arc<dyn SearchListener>) {
+        self.pool.wait_for_main();
+        *self.pool.shared.listener.write().unwrap() = listener;
+    }
+
+    pub fn listener(&self) -> Arc<dyn SearchListener> {
+        self.pool.shared.listener()
+    }
+
+    pub fn print_position(&mut self) {
+        self.pos.tb_config.tables = self.pool.shared.tablebases.read().unwrap().clone();
+        self.pos.print();
+    }
+
+    // go() starts searching the current position and returns immediately.
+    // The result is passed to the listener by the main search thread and
+    // can be waited for with the returned handle.
+
+    pub fn go(
+        &mut self,
+        limits: &search::LimitsType,
+        searchmoves: Vec<Move>,
+        ponder: bool,
+    ) -> SearchHandle {
+        self.pool
+            .start_thinking(&mut self.pos, &self.pos_data, limits, searchmoves, ponder)
+    }
+
+    pub fn stop(&self) {
+        self.pool.shared.set_stop(true);
+    }
+
+    // ponderhit() tells the engine that the expected move was played
+
+    pub fn ponderhit(&self) {
+        self.pool.shared.ponderhit();
+    }
+
+    // wait() blocks until the current search, if any, has finished
+
+    pub fn wait(&self) {
+        self.pool.wait_for_main();
+    }
+
+    pub fn new_game(&self) {
+        search::clear(&self.pool);
+    }
+
+    pub fn nodes_searched(&self) -> u64 {
+        self.pool.nodes_searched()
+    }
+}
+
+impl Default for Engine {
+    fn default() -> Engine {
+        Engine::new()
+    }
+}
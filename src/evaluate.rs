@@ -4,13 +4,16 @@ use std;
 
 use bitboard::*;
 use material;
+use nnue;
 use pawns;
 use position::Position;
 use types::*;
 
-pub const TEMPO: Value = Value(20);
+use std::sync::Arc;
 
-pub static mut CONTEMPT: Score = Score::ZERO;
+tunable! {
+    pub TEMPO: Value = Value(20);
+}
 
 //const CENTER: Bitboard = (FILED_BB | FILEE_BB) & (RANK4_BB | RANK5_BB);
 //const QUEEN_SIDE: Bitboard = FILEA_BB | FILEB_BB | FILEC_BB | FILED_BB;
@@ -102,244 +105,250 @@ macro_rules! s {
 
 const S0: Score = Score::ZERO;
 
-/// MOBILITY_BONUS[PieceType-2][attacked] contains bonuses for middle and end
-/// game, indexed by piece type and number of attacked squares in the mobility
-/// area.
-const MOBILITY_BONUS: [[Score; 32]; 4] = [
-    // Knights
-    [
-        s!(-75, -76),
-        s!(-57, -54),
-        s!(-9, -28),
-        s!(-2, -10),
-        s!(6, 5),
-        s!(14, 12),
-        s!(22, 26),
-        s!(29, 29),
-        s!(36, 29),
-        S0,
-        S0,
-        S0,
-        S0,
-        S0,
-        S0,
-        S0,
-        S0,
-        S0,
-        S0,
-        S0,
-        S0,
-        S0,
-        S0,
-        S0,
-        S0,
-        S0,
-        S0,
-        S0,
-        S0,
-        S0,
-        S0,
-        S0,
-    ],
-    // Bishops
-    [
-        s!(-48, -59),
-        s!(-20, -23),
-        s!(16, -3),
-        s!(26, 13),
-        s!(38, 24),
-        s!(51, 42),
-        s!(55, 54),
-        s!(63, 57),
-        s!(63, 65),
-        s!(68, 73),
-        s!(81, 78),
-        s!(81, 86),
-        s!(91, 88),
-        s!(98, 97),
-        S0,
-        S0,
-        S0,
-        S0,
-        S0,
-        S0,
-        S0,
-        S0,
-        S0,
-        S0,
-        S0,
-        S0,
-        S0,
-        S0,
-        S0,
-        S0,
-        S0,
-        S0,
-    ],
-    // Rooks
-    [
-        s!(-58, -76),
-        s!(-27, -18),
-        s!(-15, 28),
-        s!(-10, 55),
-        s!(-5, 69),
-        s!(-2, 82),
-        s!(9, 112),
-        s!(16, 118),
-        s!(30, 132),
-        s!(29, 142),
-        s!(32, 155),
-        s!(38, 165),
-        s!(46, 166),
-        s!(48, 169),
-        s!(58, 171),
-        S0,
-        S0,
-        S0,
-        S0,
-        S0,
-        S0,
-        S0,
-        S0,
-        S0,
-        S0,
-        S0,
-        S0,
-        S0,
-        S0,
-        S0,
-        S0,
-        S0,
-    ],
-    // Queens
-    [
-        s!(-39, -36),
-        s!(-21, -15),
-        s!(3, 8),
-        s!(3, 18),
-        s!(14, 34),
-        s!(22, 54),
-        s!(28, 61),
-        s!(41, 73),
-        s!(43, 79),
-        s!(48, 92),
-        s!(56, 94),
-        s!(60, 104),
-        s!(60, 113),
-        s!(66, 120),
-        s!(67, 123),
-        s!(70, 126),
-        s!(71, 133),
-        s!(73, 136),
-        s!(79, 140),
-        s!(88, 143),
-        s!(88, 148),
-        s!(99, 166),
-        s!(102, 170),
-        s!(102, 175),
-        s!(106, 184),
-        s!(109, 191),
-        s!(113, 206),
-        s!(116, 212),
+tunable! {
+    /// MOBILITY_BONUS[PieceType-2][attacked] contains bonuses for middle and end
+    /// game, indexed by piece type and number of attacked squares in the mobility
+    /// area.
+    pub MOBILITY_BONUS: [[Score; 32]; 4] = [
+        // Knights
+        [
+            s!(-75, -76),
+            s!(-57, -54),
+            s!(-9, -28),
+            s!(-2, -10),
+            s!(6, 5),
+            s!(14, 12),
+            s!(22, 26),
+            s!(29, 29),
+            s!(36, 29),
+            S0,
+            S0,
+            S0,
+            S0,
+            S0,
+            S0,
+            S0,
+            S0,
+            S0,
+            S0,
+            S0,
+            S0,
+            S0,
+            S0,
+            S0,
+            S0,
+            S0,
+            S0,
+            S0,
+            S0,
+            S0,
+            S0,
+            S0,
+        ],
+        // Bishops
+        [
+            s!(-48, -59),
+            s!(-20, -23),
+            s!(16, -3),
+            s!(26, 13),
+            s!(38, 24),
+            s!(51, 42),
+            s!(55, 54),
+            s!(63, 57),
+            s!(63, 65),
+            s!(68, 73),
+            s!(81, 78),
+            s!(81, 86),
+            s!(91, 88),
+            s!(98, 97),
+            S0,
+            S0,
+            S0,
+            S0,
+            S0,
+            S0,
+            S0,
+            S0,
+            S0,
+            S0,
+            S0,
+            S0,
+            S0,
+            S0,
+            S0,
+            S0,
+            S0,
+            S0,
+        ],
+        // Rooks
+        [
+            s!(-58, -76),
+            s!(-27, -18),
+            s!(-15, 28),
+            s!(-10, 55),
+            s!(-5, 69),
+            s!(-2, 82),
+            s!(9, 112),
+            s!(16, 118),
+            s!(30, 132),
+            s!(29, 142),
+            s!(32, 155),
+            s!(38, 165),
+            s!(46, 166),
+            s!(48, 169),
+            s!(58, 171),
+            S0,
+            S0,
+            S0,
+            S0,
+            S0,
+            S0,
+            S0,
+            S0,
+            S0,
+            S0,
+            S0,
+            S0,
+            S0,
+            S0,
+            S0,
+            S0,
+            S0,
+        ],
+        // Queens
+        [
+            s!(-39, -36),
+            s!(-21, -15),
+            s!(3, 8),
+            s!(3, 18),
+            s!(14, 34),
+            s!(22, 54),
+            s!(28, 61),
+            s!(41, 73),
+            s!(43, 79),
+            s!(48, 92),
+            s!(56, 94),
+            s!(60, 104),
+            s!(60, 113),
+            s!(66, 120),
+            s!(67, 123),
+            s!(70, 126),
+            s!(71, 133),
+            s!(73, 136),
+            s!(79, 140),
+            s!(88, 143),
+            s!(88, 148),
+            s!(99, 166),
+            s!(102, 170),
+            s!(102, 175),
+            s!(106, 184),
+            s!(109, 191),
+            s!(113, 206),
+            s!(116, 212),
+            S0,
+            S0,
+            S0,
+            S0,
+        ],
+    ];
+}
+
+tunable! {
+    /// OUTPOST[KNIGHT/BISHOP][supported by pawn contains bonuses for minor
+    /// pieces if they can reach an outpost square, bigger if that square is
+    /// supported by a pawn. If the minor piece occupied an output square, the
+    /// outscore is doubled.
+    pub OUTPOST: [[Score; 2]; 2] = [
+        [s!(22, 6), s!(36, 12)], // Knight
+        [s!(9, 2), s!(15, 5)],   // Bishop
+    ];
+
+    /// ROOK_ON_FILE[semiopen/open] contains bonuses for each rook value when
+    /// there is no friendly pawns on the rook file.
+    pub ROOK_ON_FILE: [Score; 2] = [s!(20, 7), s!(45, 20)];
+
+    /// THREAT_BY_MINOR/BY_ROOK[attacked PieceType] contains bonuses according to
+    /// which piece type attacks which one. Attacks on lesser pieces which are
+    /// pawn-defended are not considered.
+    pub THREAT_BY_MINOR: [Score; 8] = [
+        s!(0, 0),
+        s!(0, 31),
+        s!(39, 42),
+        s!(57, 44),
+        s!(68, 112),
+        s!(47, 120),
         S0,
         S0,
+    ];
+
+    pub THREAT_BY_ROOK: [Score; 8] = [
+        s!(0, 0),
+        s!(0, 24),
+        s!(38, 71),
+        s!(38, 61),
+        s!(0, 38),
+        s!(36, 38),
         S0,
         S0,
-    ],
-];
-
-/// OUTPOST[KNIGHT/BISHOP][supported by pawn contains bonuses for minor
-/// pieces if they can reach an outpost square, bigger if that square is
-/// supported by a pawn. If the minor piece occupied an output square, the
-/// outscore is doubled.
-const OUTPOST: [[Score; 2]; 2] = [
-    [s!(22, 6), s!(36, 12)], // Knight
-    [s!(9, 2), s!(15, 5)],   // Bishop
-];
-
-/// ROOK_ON_FILE[semiopen/open] contains bonuses for each rook value when
-/// there is no friendly pawns on the rook file.
-const ROOK_ON_FILE: [Score; 2] = [s!(20, 7), s!(45, 20)];
-
-/// THREAT_BY_MINOR/BY_ROOK[attacked PieceType] contains bonuses according to
-/// which piece type attacks which one. Attacks on lesser pieces which are
-/// pawn-defended are not considered.
-const THREAT_BY_MINOR: [Score; 8] = [
-    s!(0, 0),
-    s!(0, 31),
-    s!(39, 42),
-    s!(57, 44),
-    s!(68, 112),
-    s!(47, 120),
-    S0,
-    S0,
-];
-
-const THREAT_BY_ROOK: [Score; 8] = [
-    s!(0, 0),
-    s!(0, 24),
-    s!(38, 71),
-    s!(38, 61),
-    s!(0, 38),
-    s!(36, 38),
-    S0,
-    S0,
-];
-
-/// THREAT_BY_KING[on one/on many] contains bonuses for king attacks on pawns
-/// or pieces which are not pawn-defended.
-const THREAT_BY_KING: [Score; 2] = [s!(3, 65), s!(9, 145)];
-
-/// PASSED[mg/eg][Rank] contains midgame and endgame bonuses for passed pawns.
-/// We don't use a Score because we process the two components independently.
-const PASSED: [[i32; 8]; 2] = [
-    [0, 5, 5, 18, 74, 164, 268, 0],
-    [0, 7, 13, 23, 58, 166, 243, 0],
-];
-
-/// PASSED_FILE[File] contains a bonus according to the file of a passed pawn
-const PASSED_FILE: [Score; 8] = [
-    s!(15, 7),
-    s!(-5, 14),
-    s!(1, -5),
-    s!(-22, -11),
-    s!(-22, -11),
-    s!(1, -5),
-    s!(-5, 14),
-    s!(15, 7),
-];
+    ];
+
+    /// THREAT_BY_KING[on one/on many] contains bonuses for king attacks on pawns
+    /// or pieces which are not pawn-defended.
+    pub THREAT_BY_KING: [Score; 2] = [s!(3, 65), s!(9, 145)];
+
+    /// PASSED[mg/eg][Rank] contains midgame and endgame bonuses for passed pawns.
+    /// We don't use a Score because we process the two components independently.
+    pub PASSED: [[i32; 8]; 2] = [
+        [0, 5, 5, 18, 74, 164, 268, 0],
+        [0, 7, 13, 23, 58, 166, 243, 0],
+    ];
+
+    /// PASSED_FILE[File] contains a bonus according to the file of a passed pawn
+    pub PASSED_FILE: [Score; 8] = [
+        s!(15, 7),
+        s!(-5, 14),
+        s!(1, -5),
+        s!(-22, -11),
+        s!(-22, -11),
+        s!(1, -5),
+        s!(-5, 14),
+        s!(15, 7),
+    ];
+
+    /// KING_PROTECTOR[PieceType-2] contains a bonus according to distance from
+    /// king
+    pub KING_PROTECTOR: [Score; 4] = [s!(-3, -5), s!(-4, -3), s!(-3, 0), s!(-1, 1)];
+
+    /// Assorted bonuses and penalties used by evaluation
+    pub BISHOP_PAWNS: Score = s!(3, 5);
+    pub CLOSE_ENEMIES: Score = s!(7, 0);
+    pub CONNECTIVITY: Score = s!(3, 1);
+    pub CORNERED_BISHOP: Score = s!(50, 50);
+    pub HANGING: Score = s!(52, 30);
+    pub HINDER_PASSED_PAWN: Score = s!(8, 1);
+    pub KNIGHT_ON_QUEEN: Score = s!(21, 11);
+    pub LONG_DIAGONAL_BISHOP: Score = s!(22, 0);
+    pub MINOR_BEHIND_PAWN: Score = s!(16, 0);
+    pub OVERLOAD: Score = s!(10, 5);
+    pub PAWNLESS_FLANK: Score = s!(20, 80);
+    pub ROOK_ON_PAWN: Score = s!(8, 24);
+    pub THREAT_BY_PAWN_PUSH: Score = s!(47, 26);
+    pub THREAT_BY_RANK: Score = s!(16, 3);
+    pub THREAT_BY_SAFE_PAWN: Score = s!(175, 168);
+    pub THREAT_BY_SLIDER_ON_QUEEN: Score = s!(42, 21);
+    pub TRAPPED_ROOK: Score = s!(92, 0);
+    pub WEAK_QUEEN: Score = s!(50, 10);
+    pub WEAK_UNOPPOSED_PAWN: Score = s!(5, 25);
+}
 
 /// PASSED_DANGER contains terms to weight the passed score
 const PASSED_DANGER: [i32; 8] = [0, 0, 0, 3, 6, 12, 21, 0];
 
-/// KING_PROTECTOR[PieceType-2] contains a bonus according to distance from
-/// king
-const KING_PROTECTOR: [Score; 4] = [s!(-3, -5), s!(-4, -3), s!(-3, 0), s!(-1, 1)];
-
-/// Assorted bonuses and penalties used by evaluation
-const BISHOP_PAWNS: Score = s!(3, 5);
-const CLOSE_ENEMIES: Score = s!(7, 0);
-const CONNECTIVITY: Score = s!(3, 1);
-const CORNERED_BISHOP: Score = s!(50, 50);
-const HANGING: Score = s!(52, 30);
-const HINDER_PASSED_PAWN: Score = s!(8, 1);
-const KNIGHT_ON_QUEEN: Score = s!(21, 11);
-const LONG_DIAGONAL_BISHOP: Score = s!(22, 0);
-const MINOR_BEHIND_PAWN: Score = s!(16, 0);
-const OVERLOAD: Score = s!(10, 5);
-const PAWNLESS_FLANK: Score = s!(20, 80);
-const ROOK_ON_PAWN: Score = s!(8, 24);
-const THREAT_BY_PAWN_PUSH: Score = s!(47, 26);
-const THREAT_BY_RANK: Score = s!(16, 3);
-const THREAT_BY_SAFE_PAWN: Score = s!(175, 168);
-const THREAT_BY_SLIDER_ON_QUEEN: Score = s!(42, 21);
-const TRAPPED_ROOK: Score = s!(92, 0);
-const WEAK_QUEEN: Score = s!(50, 10);
-const WEAK_UNOPPOSED_PAWN: Score = s!(5, 25);
-
-/// king_attack_weights[PieceType] contains king attack weights by piece
-/// type
-const KING_ATTACK_WEIGHTS: [i32; 8] = [0, 0, 78, 56, 45, 11, 0, 0];
+tunable! {
+    /// king_attack_weights[PieceType] contains king attack weights by piece
+    /// type
+    pub KING_ATTACK_WEIGHTS: [i32; 8] = [0, 0, 78, 56, 45, 11, 0, 0];
+}
 
 /// Penalties for enemy's safe checks
 const QUEEN_SAFE_CHECK: i32 = 780;
@@ -347,8 +356,10 @@ const ROOK_SAFE_CHECK: i32 = 880;
 const BISHOP_SAFE_CHECK: i32 = 435;
 const KNIGHT_SAFE_CHECK: i32 = 790;
 
-/// Threshold for lazy and space evaluation
-const LAZY_THRESHOLD: Value = Value(1500);
+// Threshold for lazy and space evaluation
+tunable! {
+    pub LAZY_THRESHOLD: Value = Value(1500);
+}
 const SPACE_THRESHOLD: Value = Value(12222);
 
 /// initialize() computes king and pawn attacks and the king ring bitboard
@@ -445,37 +456,37 @@ fn evaluate_pieces<Us: ColorTrait, Pt: PieceTypeTrait>(pos: &Position, ei: &mut
 
         if b & ei.king_ring[them.0 as usize] != 0 {
             ei.king_attackers_count[us.0 as usize] += 1;
-            ei.king_attackers_weight[us.0 as usize] += KING_ATTACK_WEIGHTS[pt.0 as usize];
+            ei.king_attackers_weight[us.0 as usize] += param!(KING_ATTACK_WEIGHTS[pt.0 as usize]);
             ei.king_attacks_count[us.0 as usize] +=
                 popcount(b & ei.attacked_by[them.0 as usize][KING.0 as usize]) as i32;
         }
 
         let mob = popcount(b & ei.mobility_area[us.0 as usize]);
 
-        ei.mobility[us.0 as usize] += MOBILITY_BONUS[(pt.0 - 2) as usize][mob as usize];
+        ei.mobility[us.0 as usize] += param!(MOBILITY_BONUS[(pt.0 - 2) as usize][mob as usize]);
 
         // Bonus for this piece as king protector
-        score +=
-            KING_PROTECTOR[(pt.0 - 2) as usize] * Square::distance(s, pos.square(us, KING)) as i32;
+        score += param!(KING_PROTECTOR[(pt.0 - 2) as usize])
+            * Square::distance(s, pos.square(us, KING)) as i32;
 
         if pt == BISHOP || pt == KNIGHT {
             // Bonus for outpost squares
             let mut bb = outpost_ranks & !ei.pe.pawn_attacks_span(them);
             if bb & s != 0 {
-                score += OUTPOST[(pt == BISHOP) as usize]
+                score += param!(OUTPOST[(pt == BISHOP) as usize])
                     [(ei.attacked_by[us.0 as usize][PAWN.0 as usize] & s != 0) as usize]
                     * 2;
             } else {
                 bb &= b & !pos.pieces_c(us);
                 if bb != 0 {
-                    score += OUTPOST[(pt == BISHOP) as usize]
+                    score += param!(OUTPOST[(pt == BISHOP) as usize])
                         [((ei.attacked_by[us.0 as usize][PAWN.0 as usize] & bb) != 0) as usize];
                 }
             }
 
             // Bonus when behind a pawn
             if s.relative_rank(us) < RANK_5 && pos.pieces_p(PAWN) & (s + pawn_push(us)) != 0 {
-                score += MINOR_BEHIND_PAWN;
+                score += param!(MINOR_BEHIND_PAWN);
             }
 
             if pt == BISHOP {
@@ -483,14 +494,14 @@ fn evaluate_pieces<Us: ColorTrait, Pt: PieceTypeTrait>(pos: &Position, ei: &mut
                 // bishop, bigger when the center files are blocked with pawns.
                 let blocked = pos.pieces_cp(us, PAWN) & pos.pieces().shift(down);
 
-                score -= BISHOP_PAWNS
+                score -= param!(BISHOP_PAWNS)
                     * ei.pe.pawns_on_same_color_squares(us, s)
                     * (1 + popcount(blocked & CENTER_FILES)) as i32;
 
                 // Bonus for bishop on a long diagonal with can "see" both
                 // center squares
                 if more_than_one(CENTER & (attacks_bb(BISHOP, s, pos.pieces_p(PAWN)) | s)) {
-                    score += LONG_DIAGONAL_BISHOP;
+                    score += param!(LONG_DIAGONAL_BISHOP);
                 }
             }
 
@@ -504,11 +515,11 @@ fn evaluate_pieces<Us: ColorTrait, Pt: PieceTypeTrait>(pos: &Position, ei: &mut
                 let d = pawn_push(us) + (if s.file() == FILE_A { EAST } else { WEST });
                 if pos.piece_on(s + d) == Piece::make(us, PAWN) {
                     score -= if !pos.empty(s + d + pawn_push(us)) {
-                        CORNERED_BISHOP * 4
+                        param!(CORNERED_BISHOP) * 4
                     } else if pos.piece_on(s + 2 * d) == Piece::make(us, PAWN) {
-                        CORNERED_BISHOP * 2
+                        param!(CORNERED_BISHOP) * 2
                     } else {
-                        CORNERED_BISHOP
+                        param!(CORNERED_BISHOP)
                     }
                 }
             }
@@ -517,13 +528,13 @@ fn evaluate_pieces<Us: ColorTrait, Pt: PieceTypeTrait>(pos: &Position, ei: &mut
         if pt == ROOK {
             // Bonus for aligning with enemy pawns on the same rank/file
             if s.relative_rank(us) >= RANK_5 {
-                score += ROOK_ON_PAWN
+                score += param!(ROOK_ON_PAWN)
                     * (popcount(pos.pieces_cp(them, PAWN) & pseudo_attacks(ROOK, s)) as i32);
             }
 
             // Bonus when on an open or semi-open file
             if ei.pe.semiopen_file(us, s.file()) != 0 {
-                score += ROOK_ON_FILE[(ei.pe.semiopen_file(them, s.file()) != 0) as usize];
+                score += param!(ROOK_ON_FILE[(ei.pe.semiopen_file(them, s.file()) != 0) as usize]);
             }
             // Penalty when trapped by the king, even more if the king cannot
             // castle
@@ -531,7 +542,7 @@ fn evaluate_pieces<Us: ColorTrait, Pt: PieceTypeTrait>(pos: &Position, ei: &mut
                 let kf = pos.square(us, KING).file();
 
                 if (kf < FILE_E) == (s.file() < kf) {
-                    score -= (TRAPPED_ROOK - Score::make((mob as i32) * 22, 0))
+                    score -= (param!(TRAPPED_ROOK) - Score::make((mob as i32) * 22, 0))
                         * (1 + ((!pos.can_castle(us)) as i32));
                 }
             }
@@ -542,7 +553,7 @@ fn evaluate_pieces<Us: ColorTrait, Pt: PieceTypeTrait>(pos: &Position, ei: &mut
             // queen
             let mut pinners = Bitboard(0);
             if pos.slider_blockers(pos.pieces_cpp(them, ROOK, BISHOP), s, &mut pinners) != 0 {
-                score -= WEAK_QUEEN;
+                score -= param!(WEAK_QUEEN);
             }
         }
     }
@@ -660,11 +671,11 @@ fn evaluate_king<Us: ColorTrait>(pos: &Position, ei: &mut EvalInfo) -> Score {
     b = (if us == WHITE { b << 4 } else { b >> 4 })
         | (b & ei.attacked_by2[them.0 as usize] & !ei.attacked_by[us.0 as usize][PAWN.0 as usize]);
 
-    score -= CLOSE_ENEMIES * (popcount(b) as i32);
+    score -= param!(CLOSE_ENEMIES) * (popcount(b) as i32);
 
     // Penalty when our king is on a pawnless flank
     if pos.pieces_p(PAWN) & KING_FLANK[kf as usize] == 0 {
-        score -= PAWNLESS_FLANK;
+        score -= param!(PAWNLESS_FLANK);
     }
 
     score
@@ -695,7 +706,7 @@ fn evaluate_threats<Us: ColorTrait>(pos: &Position, ei: &EvalInfo) -> Score {
 
         let safe_threats = (b.shift(right) | b.shift(left)) & weak;
 
-        score += THREAT_BY_SAFE_PAWN * (popcount(safe_threats) as i32);
+        score += param!(THREAT_BY_SAFE_PAWN) * (popcount(safe_threats) as i32);
     }
      */
 
@@ -719,27 +730,27 @@ fn evaluate_threats<Us: ColorTrait>(pos: &Position, ei: &EvalInfo) -> Score {
             & (ei.attacked_by[us.0 as usize][KNIGHT.0 as usize]
                 | ei.attacked_by[us.0 as usize][BISHOP.0 as usize]);
         for s in b {
-            score += THREAT_BY_MINOR[pos.piece_on(s).piece_type().0 as usize];
+            score += param!(THREAT_BY_MINOR[pos.piece_on(s).piece_type().0 as usize]);
             if pos.piece_on(s).piece_type() != PAWN {
-                score += THREAT_BY_RANK * (s.relative_rank(them) as i32);
+                score += param!(THREAT_BY_RANK) * (s.relative_rank(them) as i32);
             }
         }
 
         let b =
             (pos.pieces_cp(them, QUEEN) | weak) & ei.attacked_by[us.0 as usize][ROOK.0 as usize];
         for s in b {
-            score += THREAT_BY_ROOK[pos.piece_on(s).piece_type().0 as usize];
+            score += param!(THREAT_BY_ROOK[pos.piece_on(s).piece_type().0 as usize]);
             if pos.piece_on(s).piece_type() != PAWN {
-                score += THREAT_BY_RANK * (s.relative_rank(them) as i32);
+                score += param!(THREAT_BY_RANK) * (s.relative_rank(them) as i32);
             }
         }
 
         let mut b = weak & ei.attacked_by[us.0 as usize][KING.0 as usize];
         if b != 0 {
-            score += THREAT_BY_KING[more_than_one(b) as usize];
+            score += param!(THREAT_BY_KING[more_than_one(b) as usize]);
         }
 
-        score += HANGING
+        score += param!(HANGING)
             * (popcount(weak & !ei.attacked_by[them.0 as usize][ALL_PIECES.0 as usize]) as i32);
 
         // Bonus for overload (non-pawn enemies attacked and defended exactly once)
@@ -748,12 +759,12 @@ fn evaluate_threats<Us: ColorTrait>(pos: &Position, ei: &EvalInfo) -> Score {
             & !ei.attacked_by2[us.0 as usize]
             & ei.attacked_by[them.0 as usize][ALL_PIECES.0 as usize]
             & !ei.attacked_by2[them.0 as usize];
-        score += OVERLOAD * popcount(b) as i32;
+        score += param!(OVERLOAD) * popcount(b) as i32;
     }
 
     // Bonus for unopposed weak opponent pawns
     if pos.pieces_cpp(us, ROOK, QUEEN) != 0 {
-        score += WEAK_UNOPPOSED_PAWN * ei.pe.weak_unopposed(them);
+        score += param!(WEAK_UNOPPOSED_PAWN) * ei.pe.weak_unopposed(them);
     }
 
     // Our safe or protected pawns
@@ -763,7 +774,7 @@ fn evaluate_threats<Us: ColorTrait>(pos: &Position, ei: &EvalInfo) -> Score {
 
     let safe_threats = (b.shift(right) | b.shift(left)) & non_pawn_enemies;
 
-    score += THREAT_BY_SAFE_PAWN * (popcount(safe_threats) as i32);
+    score += param!(THREAT_BY_SAFE_PAWN) * (popcount(safe_threats) as i32);
 
     // Find squares where our pawns can push on the next move
     let mut b = pos.pieces_cp(us, PAWN).shift(up) & !pos.pieces();
@@ -779,7 +790,7 @@ fn evaluate_threats<Us: ColorTrait>(pos: &Position, ei: &EvalInfo) -> Score {
         & pos.pieces_c(them)
         & !ei.attacked_by[us.0 as usize][PAWN.0 as usize];
 
-    score += THREAT_BY_PAWN_PUSH * (popcount(b) as i32);
+    score += param!(THREAT_BY_PAWN_PUSH) * (popcount(b) as i32);
 
     // Bonus for threats on the next moves against enemy queen
     if pos.count(them, QUEEN) == 1 {
@@ -788,19 +799,19 @@ fn evaluate_threats<Us: ColorTrait>(pos: &Position, ei: &EvalInfo) -> Score {
 
         b = ei.attacked_by[us.0 as usize][KNIGHT.0 as usize] & pos.attacks_from(KNIGHT, s);
 
-        score += KNIGHT_ON_QUEEN * popcount(b & safe_threats) as i32;
+        score += param!(KNIGHT_ON_QUEEN) * popcount(b & safe_threats) as i32;
 
         b = (ei.attacked_by[us.0 as usize][BISHOP.0 as usize] & pos.attacks_from(BISHOP, s))
             | (ei.attacked_by[us.0 as usize][ROOK.0 as usize] & pos.attacks_from(ROOK, s));
 
-        score += THREAT_BY_SLIDER_ON_QUEEN
+        score += param!(THREAT_BY_SLIDER_ON_QUEEN)
             * popcount(b & safe_threats & ei.attacked_by2[us.0 as usize]) as i32;
     }
 
     // Connectivity: ensure that knights, bishops, rooks, and queens are protected
     b = (pos.pieces_c(us) ^ pos.pieces_cpp(us, PAWN, KING))
         & ei.attacked_by[us.0 as usize][ALL_PIECES.0 as usize];
-    score += CONNECTIVITY * popcount(b) as i32;
+    score += param!(CONNECTIVITY) * popcount(b) as i32;
 
     score
 }
@@ -825,13 +836,13 @@ fn evaluate_passed_pawns<Us: ColorTrait>(pos: &Position, ei: &EvalInfo) -> Score
 
         let bb = forward_file_bb(us, s)
             & (ei.attacked_by[them.0 as usize][ALL_PIECES.0 as usize] | pos.pieces_c(them));
-        score -= HINDER_PASSED_PAWN * popcount(bb) as i32;
+        score -= param!(HINDER_PASSED_PAWN) * popcount(bb) as i32;
 
         let r = s.relative_rank(us);
         let rr = PASSED_DANGER[r as usize];
 
-        let mut mbonus = PASSED[MG][r as usize];
-        let mut ebonus = PASSED[EG][r as usize];
+        let mut mbonus = param!(PASSED[MG][r as usize]);
+        let mut ebonus = param!(PASSED[EG][r as usize]);
 
         if rr != 0 {
             let block_sq = s + up;
@@ -904,7 +915,7 @@ fn evaluate_passed_pawns<Us: ColorTrait>(pos: &Position, ei: &EvalInfo) -> Score
             ebonus /= 2;
         }
 
-        score += Score::make(mbonus, ebonus) + PASSED_FILE[s.file() as usize];
+        score += Score::make(mbonus, ebonus) + param!(PASSED_FILE[s.file() as usize]);
     }
 
     score
@@ -1011,11 +1022,101 @@ fn evaluate_scale_factor(pos: &Position, ei: &EvalInfo, eg: Value) -> ScaleFacto
     sf
 }
 
+// Terms of the evaluation that are reported separately by trace()
+
+const MATERIAL: usize = 0;
+const IMBALANCE: usize = 1;
+const PAWNS: usize = 2;
+const KNIGHTS: usize = 3;
+const BISHOPS: usize = 4;
+const ROOKS: usize = 5;
+const QUEENS: usize = 6;
+const MOBILITY: usize = 7;
+const KING_SAFETY: usize = 8;
+const THREATS: usize = 9;
+const PASSED_PAWNS: usize = 10;
+const SPACE: usize = 11;
+const INITIATIVE: usize = 12;
+const TOTAL: usize = 13;
+const TERM_NB: usize = 14;
+
+const TERM_NAMES: [&str; TERM_NB] = [
+    "Material",
+    "Imbalance",
+    "Pawns",
+    "Knights",
+    "Bishops",
+    "Rooks",
+    "Queens",
+    "Mobility",
+    "King safety",
+    "Threats",
+    "Passed pawns",
+    "Space",
+    "Initiative",
+    "Total",
+];
+
+/// Trace collects the contribution of each evaluation term for both colors.
+/// Terms that are only computed as a difference between white and black
+/// are stored as white's score.
+struct Trace {
+    scores: [[Score; 2]; TERM_NB],
+    scale_factor: ScaleFactor,
+    game_phase: Phase,
+    lazy: bool,
+}
+
+impl Trace {
+    fn new() -> Trace {
+        Trace {
+            scores: [[Score::ZERO; 2]; TERM_NB],
+            scale_factor: ScaleFactor::NORMAL,
+            game_phase: 0,
+            lazy: false,
+        }
+    }
+
+    fn add(&mut self, term: usize, w: Score, b: Score) {
+        self.scores[term][WHITE.0 as usize] = w;
+        self.scores[term][BLACK.0 as usize] = b;
+    }
+}
+
 // evaluate() is the main evaluation function. It computes the various parts
 // of the evaluation and returns the value of the position from the point of
-// view of the side to move.
+// view of the side to move. If the position has an NNUE network, the
+// network is used instead of the classical evaluation.
+
+pub fn evaluate(pos: &mut Position) -> Value {
+    if let Some(net) = pos.network.take() {
+        let v = evaluate_nnue(pos, &net);
+        pos.network = Some(net);
+        return v;
+    }
+
+    evaluate_generic::<False>(pos, &mut Trace::new())
+}
+
+// evaluate_nnue() scales the output of the network to the classical
+// evaluation and adds the tempo bonus
+
+fn evaluate_nnue(pos: &mut Position, net: &nnue::Network) -> Value {
+    let v = nnue::evaluate(pos, net) * 5 / 4 + param!(TEMPO);
+
+    // Damp down the evaluation linearly when shuffling
+    let v = v * (100 - pos.rule50_count()) / 100;
+
+    // Keep the evaluation out of the mate range
+    let limit = Value::MATE_IN_MAX_PLY - 1;
+    v.clamp(-limit, limit)
+}
 
-pub fn evaluate(pos: &Position) -> Value {
+// evaluate_generic() does the work for evaluate() and trace(). When tracing,
+// the terms are stored in the trace and the lazy exit is not taken, so the
+// full table is always available.
+
+fn evaluate_generic<T: Bool>(pos: &Position, trace: &mut Trace) -> Value {
     debug_assert!(pos.checkers() == 0);
 
     // Probe the material hash table
@@ -1023,7 +1124,7 @@ pub fn evaluate(pos: &Position) -> Value {
 
     // If we have a specialized evluation function for the current material
     // configuration, call it and return.
-    if me.specialized_eval_exists() {
+    if me.specialized_eval_exists() && !T::BOOL {
         return me.evaluate(pos);
     }
 
@@ -1036,17 +1137,26 @@ pub fn evaluate(pos: &Position) -> Value {
         + if let Some(th) = &pos.thread_ctrl {
             th.contempt.get()
         } else {
-            unsafe { CONTEMPT }
+            Score::ZERO
         };
 
     // Probe the pawn hash table
     let pe = pawns::probe(pos);
     score += pe.pawns_score();
 
+    if T::BOOL {
+        trace.add(MATERIAL, pos.psq_score(), Score::ZERO);
+        trace.add(IMBALANCE, me.imbalance(), Score::ZERO);
+        trace.add(PAWNS, pe.pawn_score(WHITE), pe.pawn_score(BLACK));
+    }
+
     // Early exit if score is high
     let v = (score.mg() + score.eg()) / 2;
-    if v.abs() > LAZY_THRESHOLD {
-        return if pos.side_to_move() == WHITE { v } else { -v };
+    if v.abs() > param!(LAZY_THRESHOLD) {
+        if !T::BOOL {
+            return if pos.side_to_move() == WHITE { v } else { -v };
+        }
+        trace.lazy = true;
     }
 
     // Main evaluation begins here
@@ -1056,28 +1166,71 @@ pub fn evaluate(pos: &Position) -> Value {
     initialize::<White>(pos, &mut ei);
     initialize::<Black>(pos, &mut ei);
 
-    score += evaluate_pieces::<White, Knight>(pos, &mut ei)
-        - evaluate_pieces::<Black, Knight>(pos, &mut ei);
-    score += evaluate_pieces::<White, Bishop>(pos, &mut ei)
-        - evaluate_pieces::<Black, Bishop>(pos, &mut ei);
-    score +=
-        evaluate_pieces::<White, Rook>(pos, &mut ei) - evaluate_pieces::<Black, Rook>(pos, &mut ei);
-    score += evaluate_pieces::<White, Queen>(pos, &mut ei)
-        - evaluate_pieces::<Black, Queen>(pos, &mut ei);
+    let w = evaluate_pieces::<White, Knight>(pos, &mut ei);
+    let b = evaluate_pieces::<Black, Knight>(pos, &mut ei);
+    score += w - b;
+    if T::BOOL {
+        trace.add(KNIGHTS, w, b);
+    }
+
+    let w = evaluate_pieces::<White, Bishop>(pos, &mut ei);
+    let b = evaluate_pieces::<Black, Bishop>(pos, &mut ei);
+    score += w - b;
+    if T::BOOL {
+        trace.add(BISHOPS, w, b);
+    }
+
+    let w = evaluate_pieces::<White, Rook>(pos, &mut ei);
+    let b = evaluate_pieces::<Black, Rook>(pos, &mut ei);
+    score += w - b;
+    if T::BOOL {
+        trace.add(ROOKS, w, b);
+    }
+
+    let w = evaluate_pieces::<White, Queen>(pos, &mut ei);
+    let b = evaluate_pieces::<Black, Queen>(pos, &mut ei);
+    score += w - b;
+    if T::BOOL {
+        trace.add(QUEENS, w, b);
+    }
 
     score += ei.mobility[WHITE.0 as usize] - ei.mobility[BLACK.0 as usize];
+    if T::BOOL {
+        trace.add(MOBILITY, ei.mobility[WHITE.0 as usize], ei.mobility[BLACK.0 as usize]);
+    }
 
-    score += evaluate_king::<White>(pos, &mut ei) - evaluate_king::<Black>(pos, &mut ei);
+    let w = evaluate_king::<White>(pos, &mut ei);
+    let b = evaluate_king::<Black>(pos, &mut ei);
+    score += w - b;
+    if T::BOOL {
+        trace.add(KING_SAFETY, w, b);
+    }
 
-    score += evaluate_threats::<White>(pos, &ei) - evaluate_threats::<Black>(pos, &ei);
+    let w = evaluate_threats::<White>(pos, &ei);
+    let b = evaluate_threats::<Black>(pos, &ei);
+    score += w - b;
+    if T::BOOL {
+        trace.add(THREATS, w, b);
+    }
 
-    score += evaluate_passed_pawns::<White>(pos, &ei) - evaluate_passed_pawns::<Black>(pos, &ei);
+    let w = evaluate_passed_pawns::<White>(pos, &ei);
+    let b = evaluate_passed_pawns::<Black>(pos, &ei);
+    score += w - b;
+    if T::BOOL {
+        trace.add(PASSED_PAWNS, w, b);
+    }
 
     if pos.non_pawn_material() >= SPACE_THRESHOLD {
-        score += evaluate_space::<White>(pos, &ei) - evaluate_space::<Black>(pos, &ei);
+        let w = evaluate_space::<White>(pos, &ei);
+        let b = evaluate_space::<Black>(pos, &ei);
+        score += w - b;
+        if T::BOOL {
+            trace.add(SPACE, w, b);
+        }
     }
 
-    score += evaluate_initiative(pos, &ei, score.eg());
+    let initiative = evaluate_initiative(pos, &ei, score.eg());
+    score += initiative;
 
     // Interpolate between a middlegame and a (scaled by 'sf') endgame score
     let sf = evaluate_scale_factor(pos, &ei, score.eg());
@@ -1086,5 +1239,104 @@ pub fn evaluate(pos: &Position) -> Value {
 
     v /= PHASE_MIDGAME;
 
-    TEMPO + if pos.side_to_move() == WHITE { v } else { -v }
+    if T::BOOL {
+        trace.add(INITIATIVE, initiative, Score::ZERO);
+        trace.add(TOTAL, score, Score::ZERO);
+        trace.scale_factor = sf;
+        trace.game_phase = ei.me.game_phase();
+    }
+
+    param!(TEMPO) + if pos.side_to_move() == WHITE { v } else { -v }
+}
+
+// to_cp() converts a Value to pawns, as printed by trace()
+
+fn to_cp(v: Value) -> f64 {
+    v.0 as f64 / PawnValueEg.0 as f64
+}
+
+// trace_term() formats one row of the trace table. Terms that are not
+// computed per color only show the total.
+
+fn trace_term(trace: &Trace, term: usize) -> String {
+    let w = trace.scores[term][WHITE.0 as usize];
+    let b = trace.scores[term][BLACK.0 as usize];
+
+    let per_color = !matches!(term, MATERIAL | IMBALANCE | INITIATIVE | TOTAL);
+
+    let side = |s: Score| {
+        if per_color {
+            format!("{:5.2} {:5.2}", to_cp(s.mg()), to_cp(s.eg()))
+        } else {
+            String::from(" ----  ----")
+        }
+    };
+
+    format!(
+        "{:>12} | {} | {} | {:5.2} {:5.2}",
+        TERM_NAMES[term],
+        side(w),
+        side(b),
+        to_cp((w - b).mg()),
+        to_cp((w - b).eg())
+    )
+}
+
+/// trace() is like evaluate(), but instead of returning a value, it returns
+/// a string (suitable for outputting to stdout) that contains the detailed
+/// descriptions and values of each evaluation term. All values are in pawns
+/// from white's point of view. If a network is given, the NNUE evaluation
+/// is shown as well.
+pub fn trace(pos: &Position, network: Option<Arc<nnue::Network>>) -> String {
+    let mut s = String::new();
+
+    if pos.checkers() != 0 {
+        s.push_str("Total evaluation: none (in check)\n");
+        return s;
+    }
+
+    // The pawn and material tables of a search thread are not shared, so
+    // evaluate a private copy of the position.
+    let mut p = Position::from_fen(&pos.fen(), pos.is_chess960()).unwrap();
+    p.init_tables();
+
+    let mut trace = Trace::new();
+    evaluate_generic::<True>(&p, &mut trace);
+
+    s.push_str("        Term |    White    |    Black    |    Total\n");
+    s.push_str("             |   MG    EG  |   MG    EG  |   MG    EG\n");
+    s.push_str(" ------------+-------------+-------------+------------\n");
+    for term in 0..TOTAL {
+        s.push_str(&trace_term(&trace, term));
+        s.push('\n');
+    }
+    s.push_str(" ------------+-------------+-------------+------------\n");
+    s.push_str(&trace_term(&trace, TOTAL));
+    s.push_str("\n\n");
+
+    s.push_str(&format!(
+        "Scale factor: {} / {}\nGame phase: {} / {}\n",
+        trace.scale_factor.0,
+        ScaleFactor::NORMAL.0,
+        trace.game_phase,
+        PHASE_MIDGAME
+    ));
+    if material::probe(&p).specialized_eval_exists() {
+        s.push_str("A specialized endgame evaluation overrides the terms above\n");
+    } else if trace.lazy {
+        s.push_str("Lazy evaluation: only material, imbalance and pawns are used\n");
+    }
+
+    let white = if p.side_to_move() == WHITE { 1 } else { -1 };
+    let v = evaluate(&mut p) * white;
+    if network.is_none() {
+        s.push_str(&format!("Total evaluation: {:.2} (white side)\n", to_cp(v)));
+    } else {
+        s.push_str(&format!("Classical evaluation: {:.2} (white side)\n", to_cp(v)));
+        p.network = network;
+        let v = evaluate(&mut p) * white;
+        s.push_str(&format!("Total evaluation: {:.2} (white side, NNUE)\n", to_cp(v)));
+    }
+
+    s
 }
@@ -0,0 +1,465 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+// Generation of training data from self-play. Each thread runs its own
+// engine and plays games at a fixed depth or number of nodes. Every game
+// starts with a number of random moves from the initial position. After
+// that, the quiet positions, i.e. those not in check and where the best
+// move is not a capture or a promotion, are recorded with the score of the
+// search. When the game ends, they are written out labeled with its result.
+//
+// Games end by the rules, or are adjudicated: as a win once the score of a
+// side reaches eval_limit, as a draw once the scores have stayed within
+// draw_score centipawns of zero for draw_count plies from ply draw_ply on,
+// or when max_ply is reached.
+//
+// Two output formats are supported. The binary format is marlinformat, the
+// 32-byte records used by marlinflow and the bullet trainer:
+//
+//   occupancy        u64  occupied squares, bit 0 = a1
+//   pieces         [u8; 16]  one nibble per occupied square, in the order
+//                         of the occupancy bits, low nibble first: the piece
+//                         type (pawn 0 to king 5, or 6 for a rook that can
+//                         still castle) plus 8 for black
+//   stm_ep           u8   en passant square (64 if none), plus 128 if
+//                         black is to move
+//   halfmove_clock   u8
+//   fullmove_number  u16
+//   eval             i16  score in centipawns from white's point of view
+//   wdl              u8   0 if black won, 1 for a draw, 2 if white won
+//   extra            u8   unused, 0
+//
+// with all numbers little-endian. The text format has one line per
+// position, as read by bullet:
+//
+//   <fen> | <score> | <result>
+//
+// with the score from white's point of view and the result 1.0, 0.5 or
+// 0.0 for white.
+
+use engine::Engine;
+use listener::SearchListener;
+use misc;
+use misc::Prng;
+use movegen::*;
+use position::Position;
+use search::LimitsType;
+use tournament;
+use types::*;
+use uci;
+
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Write};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SfenFormat {
+    Binary,
+    Text,
+}
+
+/// GensfenConfig holds the settings of a data generation run. A depth or
+/// node count of 0 means no limit; one of them must be set. Scores are in
+/// centipawns.
+#[derive(Clone, Debug)]
+pub struct GensfenConfig {
+    pub output: String,
+    pub format: SfenFormat,
+    pub positions: u64,
+    pub depth: u32,
+    pub nodes: u64,
+    pub threads: usize,
+    // Hash size of the engine of each thread, in MB
+    pub hash: usize,
+    pub random_plies: i32,
+    pub eval_limit: i32,
+    pub max_ply: i32,
+    pub draw_ply: i32,
+    pub draw_count: i32,
+    pub draw_score: i32,
+    pub seed: Option<u64>,
+}
+
+impl Default for GensfenConfig {
+    fn default() -> GensfenConfig {
+        GensfenConfig {
+            output: String::from("sfens.bin"),
+            format: SfenFormat::Binary,
+            positions: 1_000_000,
+            depth: 8,
+            nodes: 0,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+            hash: 16,
+            random_plies: 8,
+            eval_limit: 3000,
+            max_ply: 400,
+            draw_ply: 80,
+            draw_count: 8,
+            draw_score: 0,
+            seed: None,
+        }
+    }
+}
+
+// Quiet discards the output of the engines
+
+struct Quiet;
+
+impl SearchListener for Quiet {}
+
+// Sample is a recorded position, kept as a marlinformat record without
+// score and result or as a FEN string, and its score from white's point of
+// view
+
+struct Sample {
+    packed: [u8; 32],
+    fen: String,
+    score: i32,
+}
+
+impl Sample {
+    // record() completes the marlinformat record with the score and the
+    // result, white's score in half points
+
+    fn record(&self, result: u8) -> [u8; 32] {
+        let mut record = self.packed;
+        let score = self.score.clamp(-32000, 32000) as i16;
+        record[28..30].copy_from_slice(&score.to_le_bytes());
+        record[30] = result;
+        record
+    }
+}
+
+// pack() encodes a position as a marlinformat record, with the score and
+// the result left at 0
+
+fn pack(pos: &Position) -> [u8; 32] {
+    let mut record = [0u8; 32];
+    let occupied = pos.pieces();
+    record[0..8].copy_from_slice(&occupied.0.to_le_bytes());
+
+    // Rooks that can still castle are marked as such
+    let mut castling_rooks = 0u64;
+    for &cr in [WHITE_OO, WHITE_OOO, BLACK_OO, BLACK_OOO].iter() {
+        if pos.has_castling_right(cr) {
+            castling_rooks |= 1u64 << pos.castling_rook_square(cr).0;
+        }
+    }
+
+    for (i, s) in occupied.enumerate() {
+        let pc = pos.piece_on(s);
+        let pt = if castling_rooks & (1u64 << s.0) != 0 {
+            6
+        } else {
+            pc.piece_type().0 as u8 - 1
+        };
+        let nibble = pt | (pc.color().0 as u8) << 3;
+        record[8 + i / 2] |= nibble << (4 * (i % 2));
+    }
+
+    let ep = if pos.ep_square() == Square::NONE {
+        64
+    } else {
+        pos.ep_square().0 as u8
+    };
+    record[24] = ep | (pos.side_to_move().0 as u8) << 7;
+    record[25] = pos.rule50_count().min(255) as u8;
+    let fullmove = (pos.game_ply() / 2 + 1) as u16;
+    record[26..28].copy_from_slice(&fullmove.to_le_bytes());
+
+    record
+}
+
+// Output collects the samples of all threads
+
+struct Output {
+    writer: BufWriter<File>,
+    format: SfenFormat,
+    written: u64,
+    games: u64,
+    // Number of positions at the last progress report
+    reported: u64,
+}
+
+impl Output {
+    // write() writes the samples of a game. result is white's score in
+    // half points.
+
+    fn write(&mut self, samples: &[Sample], result: u8) -> io::Result<()> {
+        for sample in samples {
+            match self.format {
+                SfenFormat::Binary => {
+                    self.writer.write_all(&sample.record(result))?;
+                }
+                SfenFormat::Text => {
+                    let result = ["0.0", "0.5", "1.0"][result as usize];
+                    writeln!(self.writer, "{} | {} | {}", sample.fen, sample.score, result)?;
+                }
+            }
+        }
+
+        self.written += samples.len() as u64;
+        self.games += 1;
+        Ok(())
+    }
+}
+
+// play_game() plays one game and returns its recorded positions together
+// with white's score in half points. No positions are returned if the
+// game ended during the random moves.
+
+fn play_game(engine: &mut Engine, config: &GensfenConfig, prng: &mut Prng) -> (Vec<Sample>, u8) {
+    let mut pos = Box::new(Position::from_fen(uci::START_FEN, false).unwrap());
+    let mut moves: Vec<String> = Vec::new();
+    let mut samples = Vec::new();
+
+    engine.new_game();
+
+    for _ in 0..config.random_plies {
+        let mut list = MoveList::new::<Legal>(&pos);
+        if list.len() == 0 {
+            return (samples, 1);
+        }
+        let k = prng.rand64() as usize % list.len();
+        let m = list.nth(k).unwrap();
+        moves.push(uci::move_str(m, false));
+        let gives_check = pos.gives_check(m);
+        pos.do_move(m, gives_check);
+    }
+
+    let mut limits = LimitsType::new();
+    limits.depth = config.depth;
+    limits.nodes = config.nodes;
+    let mut draw_plies = 0;
+
+    let result = loop {
+        if let Some((result, _)) = tournament::game_end(&pos) {
+            break result;
+        }
+        if pos.game_ply() >= config.max_ply {
+            break "1/2-1/2";
+        }
+
+        let refs: Vec<&str> = moves.iter().map(|m| m.as_str()).collect();
+        engine.set_position(uci::START_FEN, &refs).unwrap();
+        let outcome = engine.go(&limits, Vec::new(), false).wait();
+        let us = pos.side_to_move();
+        let score = tournament::centipawns(outcome.score);
+
+        if score.abs() >= config.eval_limit {
+            break if (score > 0) == (us == WHITE) {
+                "1-0"
+            } else {
+                "0-1"
+            };
+        }
+
+        if pos.game_ply() >= config.draw_ply && score.abs() <= config.draw_score {
+            draw_plies += 1;
+        } else {
+            draw_plies = 0;
+        }
+        if config.draw_count > 0 && draw_plies >= config.draw_count {
+            break "1/2-1/2";
+        }
+
+        let m = outcome.best;
+        if pos.checkers() == 0 && !pos.capture_or_promotion(m) {
+            let (packed, fen) = match config.format {
+                SfenFormat::Binary => (pack(&pos), String::new()),
+                SfenFormat::Text => ([0; 32], pos.fen()),
+            };
+            let score = if us == WHITE { score } else { -score };
+            samples.push(Sample { packed, fen, score });
+        }
+
+        moves.push(uci::move_str(m, false));
+        let gives_check = pos.gives_check(m);
+        pos.do_move(m, gives_check);
+    };
+
+    let result = match result {
+        "1-0" => 2,
+        "0-1" => 0,
+        _ => 1,
+    };
+    (samples, result)
+}
+
+// worker() plays games until enough positions have been written
+
+fn worker(config: &GensfenConfig, seed: u64, output: &Mutex<Output>, start: Instant) {
+    let mut engine = Engine::new();
+    engine.set_listener(Arc::new(Quiet));
+    engine.set_option("Hash", &config.hash.to_string()).unwrap();
+    let mut prng = Prng::new(seed);
+
+    loop {
+        if output.lock().unwrap().written >= config.positions {
+            break;
+        }
+
+        let (samples, result) = play_game(&mut engine, config, &mut prng);
+        if samples.is_empty() {
+            continue;
+        }
+
+        let mut output = output.lock().unwrap();
+        if output.written >= config.positions {
+            break;
+        }
+        let left = (config.positions - output.written) as usize;
+        let samples = &samples[..samples.len().min(left)];
+        if let Err(e) = output.write(samples, result) {
+            println!("info string Unable to write {}: {}", config.output, e);
+            output.written = config.positions;
+            break;
+        }
+
+        if output.written - output.reported >= 10000 || output.written >= config.positions {
+            let elapsed = start.elapsed().as_millis() as u64 + 1;
+            println!(
+                "info string {} positions from {} games, {} positions/s",
+                output.written,
+                output.games,
+                output.written * 1000 / elapsed
+            );
+            output.reported = output.written;
+        }
+    }
+}
+
+// run() generates the training data. It returns the number of positions
+// written.
+
+pub fn run(config: &GensfenConfig) -> u64 {
+    if config.depth == 0 && config.nodes == 0 {
+        println!("info string A depth or a number of nodes is needed");
+        return 0;
+    }
+
+    let file = match File::create(&config.output) {
+        Ok(file) => file,
+        Err(e) => {
+            println!("info string Unable to create {}: {}", config.output, e);
+            return 0;
+        }
+    };
+
+    let output = Arc::new(Mutex::new(Output {
+        writer: BufWriter::new(file),
+        format: config.format,
+        written: 0,
+        games: 0,
+        reported: 0,
+    }));
+    let config = Arc::new(config.clone());
+    let mut prng = Prng::new(config.seed.unwrap_or_else(misc::time_seed));
+    let start = Instant::now();
+
+    let mut handles = Vec::new();
+    for _ in 0..config.threads.max(1) {
+        let (config, output) = (config.clone(), output.clone());
+        let seed = prng.rand64() | 1;
+        // The engines need a large stack, as in main()
+        let handle = thread::Builder::new()
+            .stack_size(16 * 1024 * 1024)
+            .spawn(move || worker(&config, seed, &output, start))
+            .unwrap();
+        handles.push(handle);
+    }
+    for handle in handles {
+        let _ = handle.join();
+    }
+
+    let mut output = output.lock().unwrap();
+    if let Err(e) = output.writer.flush() {
+        println!("info string Unable to write {}: {}", config.output, e);
+    }
+    output.written
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitboard::Bitboard;
+
+    // unpack() decodes a marlinformat record into a FEN, the score and the
+    // result
+
+    fn unpack(record: &[u8; 32], is_chess960: bool) -> (String, i16, u8) {
+        let mut occupied = [0u8; 8];
+        occupied.copy_from_slice(&record[0..8]);
+        let occupied = Bitboard(u64::from_le_bytes(occupied));
+
+        let mut pieces = Vec::new();
+        let mut castling_rooks = Vec::new();
+        for (i, s) in occupied.enumerate() {
+            let nibble = (record[8 + i / 2] >> (4 * (i % 2))) as u32 & 15;
+            let c = Color(nibble >> 3);
+            let pt = if nibble & 7 == 6 {
+                castling_rooks.push(s);
+                ROOK
+            } else {
+                PieceType((nibble & 7) + 1)
+            };
+            pieces.push((s, Piece::make(c, pt)));
+        }
+
+        let stm = Color(record[24] as u32 >> 7);
+        let mut pos = Position::from_fen(uci::START_FEN, false).unwrap();
+        pos.set_pieces(&pieces, stm, &castling_rooks, is_chess960);
+
+        // set_pieces() leaves out the en passant square and the counters
+        let ep = match record[24] & 127 {
+            64 => String::from("-"),
+            s => uci::square(Square(s as u32)),
+        };
+        let fullmove = u16::from_le_bytes([record[26], record[27]]);
+        let fen = pos.fen();
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        let fen = format!(
+            "{} {} {} {} {} {}",
+            fields[0], fields[1], fields[2], ep, record[25], fullmove
+        );
+
+        (fen, i16::from_le_bytes([record[28], record[29]]), record[30])
+    }
+
+    #[test]
+    fn pack_round_trip() {
+        Engine::new();
+
+        let positions = [
+            (uci::START_FEN, false),
+            ("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3", false),
+            ("r3k2r/8/8/8/8/8/8/R3K2R b Kq - 7 40", false),
+            ("8/8/3k4/8/2pP4/8/6K1/8 b - d3 0 57", false),
+            // Chess960, with the king between two rooks that can castle
+            ("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9", true),
+            ("1r2k1r1/8/8/8/8/8/8/1R2K1R1 b Gb - 0 1", true),
+        ];
+
+        for &(fen, is_chess960) in positions.iter() {
+            let pos = Position::from_fen(fen, is_chess960).unwrap();
+            assert_eq!(pos.fen(), fen);
+
+            let sample = Sample {
+                packed: pack(&pos),
+                fen: String::new(),
+                score: -1234,
+            };
+            assert_eq!(unpack(&sample.record(2), is_chess960), (String::from(fen), -1234, 2));
+        }
+
+        // Scores are clamped to the range of the format
+        let sample = Sample {
+            packed: [0; 32],
+            fen: String::new(),
+            score: 40000,
+        };
+        assert_eq!(i16::from_le_bytes([sample.record(0)[28], sample.record(0)[29]]), 32000);
+    }
+}
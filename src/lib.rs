@@ -1,23 +1,43 @@
+// tune comes first, as it defines the macros for tunable parameters
+#[macro_use]
+pub mod tune;
 pub mod benchmark;
 pub mod bitbases;
 #[macro_use]
 pub mod bitboard;
+pub mod book;
 pub mod endgame;
+pub mod engine;
 pub mod evaluate;
+pub mod gensfen;
+pub mod listener;
 pub mod material;
 pub mod misc;
 pub mod movegen;
 pub mod movepick;
+pub mod nnue;
+pub mod notation;
 pub mod pawns;
+pub mod perft;
+pub mod pgn;
 pub mod position;
 pub mod psqt;
 pub mod search;
+pub mod simd;
+pub mod stats;
 pub mod tb;
+pub mod testsuite;
+#[cfg(feature = "tune")]
+pub mod texel;
 pub mod threads;
 pub mod timeman;
+pub mod tournament;
 pub mod tt;
 pub mod types;
 pub mod uci;
 pub mod ucioption;
+pub mod xboard;
 
 extern crate memmap;
+
+pub use engine::Engine;
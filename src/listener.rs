@@ -0,0 +1,78 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+// The search does not print anything itself: it reports its progress and
+// its result to a SearchListener. The UCI front end is one implementation,
+// uci::UciListener, which prints the usual 'info' and 'bestmove' lines.
+// Programs using the crate as a library install their own listener with
+// Engine::set_listener().
+//
+// The callbacks are made from the search threads while they search, so a
+// listener must be Send and Sync and should return quickly. All callbacks
+// do nothing by default.
+
+use position::Position;
+use types::*;
+
+/// ScoreBound tells whether the score of a PV line is exact, or only a
+/// lower or upper bound after a fail high or fail low at the root
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ScoreBound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+/// PvLine is one of the MultiPV lines reported after an iteration. Lines
+/// not searched yet at the current depth keep their depth and score from
+/// the previous iteration.
+#[derive(Clone)]
+pub struct PvLine {
+    // 1 for the best line
+    pub multipv: usize,
+    pub depth: i32,
+    pub sel_depth: i32,
+    pub score: Value,
+    pub bound: ScoreBound,
+    // Win, draw and loss probabilities in per mille, if UCI_ShowWDL is set
+    pub wdl: Option<(i32, i32, i32)>,
+    pub pv: Vec<Move>,
+}
+
+/// SearchStats holds the counters of the search so far. The time is in
+/// milliseconds and hashfull in per mille.
+#[derive(Clone, Copy)]
+pub struct SearchStats {
+    pub nodes: u64,
+    pub nps: u64,
+    pub hashfull: i32,
+    pub tb_hits: u64,
+    pub time: i64,
+}
+
+pub trait SearchListener: Send + Sync {
+    // on_iteration() is called by the main thread with the PV lines of the
+    // root moves whenever an iteration completes or the best line fails
+    // high or low. If there is no legal move, it is called once with depth
+    // 0 and a single line with an empty PV and the mate or draw score.
+    fn on_iteration(&self, _pos: &Position, _depth: i32, _lines: &[PvLine], _stats: &SearchStats) {}
+
+    // on_currmove() is called when the main thread starts searching a root
+    // move, number being its 1-based position in the move list
+    fn on_currmove(&self, _pos: &Position, _depth: i32, _m: Move, _number: usize) {}
+
+    // on_progress() is called about every second during the search
+    fn on_progress(&self, _stats: &SearchStats) {}
+
+    // on_bestmove() is called once at the end of every search, including
+    // searches answered from the opening book
+    fn on_bestmove(&self, _pos: &Position, _best: Move, _ponder: Option<Move>) {}
+
+    // on_perft() is called with the leaf count of each root move during a
+    // perft run, and on_perft_done() with the total at the end
+    fn on_perft(&self, _pos: &Position, _m: Move, _nodes: u64) {}
+
+    fn on_perft_done(&self, _nodes: u64) {}
+
+    // on_message() passes on a warning or informational message
+    fn on_message(&self, _msg: &str) {}
+}
@@ -64,27 +64,29 @@ impl Entry {
 
 // Polynomial material imbalance parameters
 
-const QUADRATIC_OURS: [[i32; 8]; 6] = [
-    //             OUR PIECES
-    // pair pawn knight bishop rook queen
-    [1667, 0, 0, 0, 0, 0, 0, 0],           // Bishop pair
-    [40, 0, 0, 0, 0, 0, 0, 0],             // Pawn
-    [32, 255, -3, 0, 0, 0, 0, 0],          // Knight     OUR PIECES
-    [0, 104, 4, 0, 0, 0, 0, 0],            // Bishop
-    [-26, -2, 47, 105, -149, 0, 0, 0],     // Rook
-    [-189, 24, 117, 133, -134, -10, 0, 0], // Queen
-];
-
-const QUADRATIC_THEIRS: [[i32; 8]; 6] = [
-    //           THEIR PIECES
-    // pair pawn knight bishop rook queen
-    [0, 0, 0, 0, 0, 0, 0, 0],          // Bishop pair
-    [36, 0, 0, 0, 0, 0, 0, 0],         // Pawn
-    [9, 63, 0, 0, 0, 0, 0, 0],         // Knight    THEIR PIECES
-    [59, 65, 42, 0, 0, 0, 0, 0],       // Bishop
-    [46, 39, 24, -24, 0, 0, 0, 0],     // Rook
-    [97, 100, -42, 137, 268, 0, 0, 0], // Queen
-];
+tunable! {
+    pub QUADRATIC_OURS: [[i32; 8]; 6] = [
+        //             OUR PIECES
+        // pair pawn knight bishop rook queen
+        [1667, 0, 0, 0, 0, 0, 0, 0],           // Bishop pair
+        [40, 0, 0, 0, 0, 0, 0, 0],             // Pawn
+        [32, 255, -3, 0, 0, 0, 0, 0],          // Knight     OUR PIECES
+        [0, 104, 4, 0, 0, 0, 0, 0],            // Bishop
+        [-26, -2, 47, 105, -149, 0, 0, 0],     // Rook
+        [-189, 24, 117, 133, -134, -10, 0, 0], // Queen
+    ];
+
+    pub QUADRATIC_THEIRS: [[i32; 8]; 6] = [
+        //           THEIR PIECES
+        // pair pawn knight bishop rook queen
+        [0, 0, 0, 0, 0, 0, 0, 0],          // Bishop pair
+        [36, 0, 0, 0, 0, 0, 0, 0],         // Pawn
+        [9, 63, 0, 0, 0, 0, 0, 0],         // Knight    THEIR PIECES
+        [59, 65, 42, 0, 0, 0, 0, 0],       // Bishop
+        [46, 39, 24, -24, 0, 0, 0, 0],     // Rook
+        [97, 100, -42, 137, 268, 0, 0, 0], // Queen
+    ];
+}
 
 // Helper used to detect a given material distribution
 fn is_kxk(pos: &Position, us: Color) -> bool {
@@ -121,8 +123,8 @@ fn imbalance(pc: &[[i32; 6]; 2], us: Color) -> i32 {
         let mut v = 0;
 
         for pt2 in 0..(pt1 + 1) {
-            v += QUADRATIC_OURS[pt1][pt2] * pc[us.0 as usize][pt2]
-                + QUADRATIC_THEIRS[pt1][pt2] * pc[them.0 as usize][pt2];
+            v += param!(QUADRATIC_OURS[pt1][pt2]) * pc[us.0 as usize][pt2]
+                + param!(QUADRATIC_THEIRS[pt1][pt2]) * pc[them.0 as usize][pt2];
         }
 
         bonus += pc[us.0 as usize][pt1] * v;
@@ -1,5 +1,7 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+use std::time::{SystemTime, UNIX_EPOCH};
+
 #[derive(Clone, Copy)]
 pub struct Prng(u64);
 
@@ -31,6 +33,17 @@ impl Prng {
     }
 }
 
+// time_seed() returns a non-zero seed for a Prng that differs from run to
+// run, for choices that should not be repeatable
+
+pub fn time_seed() -> u64 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0);
+    nanos | 1
+}
+
 pub fn engine_info(to_uci: bool) -> String {
     //    let months = &"Jan Feb Mar Apr May Jun Jul Aug Sep Oct Nov Dec";
 
@@ -1,5 +1,7 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+use std;
+use std::alloc::{alloc_zeroed, handle_alloc_error, Layout};
 use std::cell::Cell;
 
 use movegen::*;
@@ -77,6 +79,24 @@ pub struct ContinuationHistory {
 }
 
 impl ContinuationHistory {
+    // new() allocates a zeroed table directly on the heap. The table is
+    // too large to be built on the stack first.
+
+    pub fn new() -> Box<ContinuationHistory> {
+        let layout = Layout::new::<ContinuationHistory>();
+        unsafe {
+            let ptr = alloc_zeroed(layout) as *mut ContinuationHistory;
+            if ptr.is_null() {
+                handle_alloc_error(layout);
+            }
+            Box::from_raw(ptr)
+        }
+    }
+
+    pub fn clear(&mut self) {
+        unsafe { std::ptr::write_bytes(self as *mut ContinuationHistory, 0, 1) };
+    }
+
     pub fn get(&self, pc: Piece, s: Square) -> &'static PieceToHistory {
         let p: *const PieceToHistory = &self.v[pc.0 as usize][s.0 as usize];
         unsafe { &*p }
@@ -0,0 +1,345 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+// NNUE evaluation with a HalfKP[41024->256x2]-32-32-1 network, the format
+// used by the .nnue files of Stockfish 12 and 13.
+//
+// The input features are the (own king square, piece, piece square) triples
+// of all pieces except the kings, seen from each side's perspective. The
+// feature transformer maps them to 256 values per perspective, which are
+// kept in an Accumulator for every StateInfo. Since a move only changes a
+// few features, the accumulator of a position is computed from that of the
+// previous position by adding and subtracting a few weight columns. It is
+// only computed from scratch when the king of the perspective has moved.
+//
+// The two halves of the transformed features, side to move first, go
+// through two hidden layers of 32 neurons with clipped ReLU activations and
+// an output layer with a single neuron. The heavy lifting is done by the
+// kernels in simd.rs, using the best instruction set of the CPU.
+
+use position::Position;
+use simd;
+use simd::Simd;
+use types::*;
+
+use std::fs::File;
+use std::io;
+use std::io::Read;
+
+pub const HALF_DIMENSIONS: usize = 256;
+const INPUT_DIMENSIONS: usize = 64 * PS_END;
+const HIDDEN_DIMENSIONS: usize = 32;
+
+// For a given king square, the features are ordered by kind of piece (own
+// pawns, enemy pawns, own knights, ..., enemy queens) and then by square,
+// starting at index PS_OWN_PAWN
+const PS_OWN_PAWN: usize = 1;
+const PS_END: usize = 641;
+
+const VERSION: u32 = 0x7AF32F16;
+const FT_HASH: u32 = 0x5D69D7B8;
+const NETWORK_HASH: u32 = 0x63337156;
+
+// Scaling factor of the output of the network
+const FV_SCALE: i32 = 16;
+
+// Number of bits the outputs of the hidden layers are shifted right by
+const WEIGHT_SCALE_BITS: u32 = 6;
+
+/// Accumulator holds the output of the feature transformer for both
+/// perspectives. The accumulators are kept on a stack of their own, next to
+/// the StateInfos, so that making a move does not touch them; the
+/// accumulator_computed field of a StateInfo tells which values are up to
+/// date. See Position::accumulators().
+#[derive(Clone, Copy)]
+pub struct Accumulator {
+    pub values: [[i16; HALF_DIMENSIONS]; 2],
+}
+
+impl Accumulator {
+    pub fn new() -> Accumulator {
+        Accumulator {
+            values: [[0; HALF_DIMENSIONS]; 2],
+        }
+    }
+}
+
+impl Default for Accumulator {
+    fn default() -> Accumulator {
+        Accumulator::new()
+    }
+}
+
+/// DirtyPiece records the pieces changed by a move: piece[i] moved from
+/// from[i] to to[i], where Square::NONE stands for a piece removed from or
+/// added to the board. The king, if it moved, is always the first piece.
+#[derive(Clone, Copy)]
+pub struct DirtyPiece {
+    pub dirty_num: usize,
+    pub piece: [Piece; 3],
+    pub from: [Square; 3],
+    pub to: [Square; 3],
+}
+
+impl DirtyPiece {
+    pub fn new() -> DirtyPiece {
+        DirtyPiece {
+            dirty_num: 0,
+            piece: [NO_PIECE; 3],
+            from: [Square::NONE; 3],
+            to: [Square::NONE; 3],
+        }
+    }
+
+    pub fn add(&mut self, pc: Piece, from: Square, to: Square) {
+        self.piece[self.dirty_num] = pc;
+        self.from[self.dirty_num] = from;
+        self.to[self.dirty_num] = to;
+        self.dirty_num += 1;
+    }
+}
+
+impl Default for DirtyPiece {
+    fn default() -> DirtyPiece {
+        DirtyPiece::new()
+    }
+}
+
+struct Layer {
+    inputs: usize,
+    biases: Vec<i32>,
+    weights: Vec<i8>,
+}
+
+/// Network holds the weights of a network loaded from a .nnue file
+pub struct Network {
+    pub file_name: String,
+    pub description: String,
+    // Instruction set used to run the network
+    pub simd: Simd,
+    ft_biases: Vec<i16>,
+    ft_weights: Vec<i16>,
+    hidden1: Layer,
+    hidden2: Layer,
+    output: Layer,
+}
+
+// Reader reads the little-endian numbers of a network file
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, n: usize) -> io::Result<&'a [u8]> {
+        if self.data.len() < n {
+            return Err(invalid("unexpected end of file"));
+        }
+        let (head, tail) = self.data.split_at(n);
+        self.data = tail;
+        Ok(head)
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn i16s(&mut self, n: usize) -> io::Result<Vec<i16>> {
+        let b = self.bytes(2 * n)?;
+        Ok(b.chunks(2).map(|c| i16::from_le_bytes([c[0], c[1]])).collect())
+    }
+
+    fn i32s(&mut self, n: usize) -> io::Result<Vec<i32>> {
+        let b = self.bytes(4 * n)?;
+        Ok(b
+            .chunks(4)
+            .map(|c| i32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+            .collect())
+    }
+
+    fn layer(&mut self, inputs: usize, outputs: usize) -> io::Result<Layer> {
+        let biases = self.i32s(outputs)?;
+        let weights = self.bytes(inputs * outputs)?.iter().map(|&b| b as i8).collect();
+        Ok(Layer {
+            inputs,
+            biases,
+            weights,
+        })
+    }
+}
+
+impl Network {
+    // load() reads a network from a file. Files with another architecture
+    // are rejected.
+
+    pub fn load(file_name: &str) -> io::Result<Network> {
+        let mut data = Vec::new();
+        File::open(file_name)?.read_to_end(&mut data)?;
+
+        let mut r = Reader { data: &data };
+        if r.u32()? != VERSION {
+            return Err(invalid("unsupported file version"));
+        }
+        if r.u32()? != FT_HASH ^ NETWORK_HASH {
+            return Err(invalid("network architecture is not HalfKP 256x2-32-32"));
+        }
+        let size = r.u32()? as usize;
+        let description = String::from_utf8_lossy(r.bytes(size)?).into_owned();
+
+        if r.u32()? != FT_HASH {
+            return Err(invalid("bad feature transformer header"));
+        }
+        let ft_biases = r.i16s(HALF_DIMENSIONS)?;
+        let ft_weights = r.i16s(HALF_DIMENSIONS * INPUT_DIMENSIONS)?;
+
+        if r.u32()? != NETWORK_HASH {
+            return Err(invalid("bad network header"));
+        }
+        let hidden1 = r.layer(2 * HALF_DIMENSIONS, HIDDEN_DIMENSIONS)?;
+        let hidden2 = r.layer(HIDDEN_DIMENSIONS, HIDDEN_DIMENSIONS)?;
+        let output = r.layer(HIDDEN_DIMENSIONS, 1)?;
+
+        if !r.data.is_empty() {
+            return Err(invalid("trailing data after the network"));
+        }
+
+        Ok(Network {
+            file_name: String::from(file_name),
+            description,
+            simd: simd::detect(),
+            ft_biases,
+            ft_weights,
+            hidden1,
+            hidden2,
+            output,
+        })
+    }
+
+    fn add_feature(&self, values: &mut [i16; HALF_DIMENSIONS], idx: usize) {
+        let column = &self.ft_weights[idx * HALF_DIMENSIONS..(idx + 1) * HALF_DIMENSIONS];
+        simd::add_i16(self.simd, values, column);
+    }
+
+    fn sub_feature(&self, values: &mut [i16; HALF_DIMENSIONS], idx: usize) {
+        let column = &self.ft_weights[idx * HALF_DIMENSIONS..(idx + 1) * HALF_DIMENSIONS];
+        simd::sub_i16(self.simd, values, column);
+    }
+}
+
+// feature_index() returns the index of the feature for a piece on a square,
+// seen from the given perspective with its king on ksq. For black the board
+// is rotated, so that each side sees its own pieces from below.
+
+fn feature_index(perspective: Color, ksq: Square, pc: Piece, s: Square) -> usize {
+    let orient = if perspective == WHITE { 0 } else { 63 };
+    let kind = 2 * (pc.piece_type().0 as usize - 1) + (pc.color() != perspective) as usize;
+    (s.0 as usize ^ orient) + PS_OWN_PAWN + 64 * kind + PS_END * (ksq.0 as usize ^ orient)
+}
+
+// refresh() computes the accumulator of the current position for one
+// perspective from scratch
+
+fn refresh(pos: &mut Position, net: &Network, perspective: Color) {
+    let ksq = pos.square(perspective, KING);
+    let features: Vec<usize> = (pos.pieces() & !pos.pieces_p(KING))
+        .map(|s| feature_index(perspective, ksq, pos.piece_on(s), s))
+        .collect();
+
+    let (states, accumulators) = pos.accumulators();
+    let last = states.len() - 1;
+    let values = &mut accumulators[last].values[perspective.0 as usize];
+    values.copy_from_slice(&net.ft_biases);
+    for &idx in features.iter() {
+        net.add_feature(values, idx);
+    }
+    states[last].accumulator_computed[perspective.0 as usize] = true;
+}
+
+// update_accumulator() makes sure the accumulator of the current position
+// is computed for one perspective. It looks back for the last position
+// with a computed accumulator and applies the changes of the moves made
+// since. If the king has moved in between, or the history does not reach
+// far enough, the accumulator is computed from scratch.
+
+fn update_accumulator(pos: &mut Position, net: &Network, perspective: Color) {
+    let p = perspective.0 as usize;
+    let king = Piece::make(perspective, KING);
+    let ksq = pos.square(perspective, KING);
+
+    let (states, accumulators) = pos.accumulators();
+    let last = states.len() - 1;
+    let mut i = last;
+    while !states[i].accumulator_computed[p] {
+        match states[i].dirty_piece {
+            Some(ref dp) if i > 0 && dp.piece[0] != king => i -= 1,
+            _ => {
+                refresh(pos, net, perspective);
+                return;
+            }
+        }
+    }
+
+    for j in i + 1..=last {
+        let dp = states[j].dirty_piece.unwrap();
+        let (prev, next) = accumulators.split_at_mut(j);
+        let values = &mut next[0].values[p];
+        *values = prev[j - 1].values[p];
+
+        for k in 0..dp.dirty_num {
+            // Kings are not features
+            if dp.piece[k].piece_type() == KING {
+                continue;
+            }
+            if dp.from[k] != Square::NONE {
+                net.sub_feature(values, feature_index(perspective, ksq, dp.piece[k], dp.from[k]));
+            }
+            if dp.to[k] != Square::NONE {
+                net.add_feature(values, feature_index(perspective, ksq, dp.piece[k], dp.to[k]));
+            }
+        }
+        states[j].accumulator_computed[p] = true;
+    }
+}
+
+// propagate() computes the outputs of a fully connected layer followed by
+// a clipped ReLU
+
+fn propagate(simd: Simd, layer: &Layer, input: &[u8], output: &mut [u8]) {
+    for (o, out) in output.iter_mut().enumerate() {
+        let row = &layer.weights[o * layer.inputs..(o + 1) * layer.inputs];
+        let sum = layer.biases[o] + simd::dot_u8_i8(simd, input, row);
+        *out = (sum >> WEIGHT_SCALE_BITS).clamp(0, 127) as u8;
+    }
+}
+
+// evaluate() returns the output of the network for the current position,
+// from the point of view of the side to move
+
+pub fn evaluate(pos: &mut Position, net: &Network) -> Value {
+    update_accumulator(pos, net, WHITE);
+    update_accumulator(pos, net, BLACK);
+
+    let us = pos.side_to_move();
+    let (states, accumulators) = pos.accumulators();
+    let acc = &accumulators[states.len() - 1];
+
+    let mut transformed = [0u8; 2 * HALF_DIMENSIONS];
+    for (half, &c) in [us, !us].iter().enumerate() {
+        let values = &acc.values[c.0 as usize];
+        let out = &mut transformed[half * HALF_DIMENSIONS..(half + 1) * HALF_DIMENSIONS];
+        simd::clamp_u8(net.simd, values, out);
+    }
+
+    let mut hidden1 = [0u8; HIDDEN_DIMENSIONS];
+    let mut hidden2 = [0u8; HIDDEN_DIMENSIONS];
+    propagate(net.simd, &net.hidden1, &transformed, &mut hidden1);
+    propagate(net.simd, &net.hidden2, &hidden1, &mut hidden2);
+
+    let output = net.output.biases[0] + simd::dot_u8_i8(net.simd, &hidden2, &net.output.weights);
+
+    Value(output / FV_SCALE)
+}
@@ -0,0 +1,437 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use movegen::*;
+use position::Position;
+use types::*;
+use uci;
+
+// Move notations supported for input and output:
+//
+// Uci: coordinate notation as used by the UCI protocol (g1f3, e7e8q)
+// San: Standard Algebraic Notation (Nf3, exd5, O-O, e8=Q+)
+// Lan: long algebraic notation (Ng1-f3, e4xd5, O-O, e7-e8=Q+)
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Notation {
+    Uci,
+    San,
+    Lan,
+}
+
+fn piece_char(pt: PieceType) -> &'static str {
+    ["", "", "N", "B", "R", "Q", "K"][pt.0 as usize]
+}
+
+// check_suffix() returns "+" or "#" if the move gives check or mate. The
+// move must be legal.
+
+fn check_suffix(pos: &mut Position, m: Move) -> &'static str {
+    if !pos.gives_check(m) {
+        return "";
+    }
+
+    pos.do_move(m, true);
+    let mate = MoveList::new::<Legal>(pos).len() == 0;
+    pos.undo_move(m);
+
+    if mate {
+        "#"
+    } else {
+        "+"
+    }
+}
+
+// disambiguation() returns the file, rank or square of the origin square
+// that is needed to tell a piece move apart from the moves of other pieces
+// of the same type to the same square.
+
+fn disambiguation(pos: &Position, m: Move) -> String {
+    let from = m.from();
+    let pc = pos.moved_piece(m);
+    let mut same_file = false;
+    let mut same_rank = false;
+    let mut ambiguous = false;
+
+    for other in MoveList::new::<Legal>(pos) {
+        if other.to() == m.to() && other.from() != from && pos.moved_piece(other) == pc {
+            ambiguous = true;
+            same_file |= other.from().file() == from.file();
+            same_rank |= other.from().rank() == from.rank();
+        }
+    }
+
+    let square = uci::square(from);
+    if !ambiguous {
+        String::new()
+    } else if !same_file {
+        String::from(&square[0..1])
+    } else if !same_rank {
+        String::from(&square[1..2])
+    } else {
+        square
+    }
+}
+
+// to_san() converts a legal move to Standard Algebraic Notation. Castling
+// is written as O-O or O-O-O in both normal chess and Chess960.
+
+pub fn to_san(pos: &mut Position, m: Move) -> String {
+    if m == Move::NONE {
+        return String::from("(none)");
+    }
+
+    if m == Move::NULL {
+        return String::from("--");
+    }
+
+    let mut san = String::new();
+
+    if m.move_type() == CASTLING {
+        san.push_str(if m.to() > m.from() { "O-O" } else { "O-O-O" });
+    } else {
+        let pt = pos.moved_piece(m).piece_type();
+        let capture = pos.capture(m);
+
+        if pt == PAWN {
+            if capture {
+                san.push_str(&uci::square(m.from())[0..1]);
+            }
+        } else {
+            san.push_str(piece_char(pt));
+            san.push_str(&disambiguation(pos, m));
+        }
+
+        if capture {
+            san.push('x');
+        }
+
+        san.push_str(&uci::square(m.to()));
+
+        if m.move_type() == PROMOTION {
+            san.push('=');
+            san.push_str(piece_char(m.promotion_type()));
+        }
+    }
+
+    san.push_str(check_suffix(pos, m));
+    san
+}
+
+// to_lan() converts a legal move to long algebraic notation, where the
+// origin square is always given.
+
+pub fn to_lan(pos: &mut Position, m: Move) -> String {
+    if m == Move::NONE {
+        return String::from("(none)");
+    }
+
+    if m == Move::NULL {
+        return String::from("--");
+    }
+
+    let mut lan = String::new();
+
+    if m.move_type() == CASTLING {
+        lan.push_str(if m.to() > m.from() { "O-O" } else { "O-O-O" });
+    } else {
+        lan.push_str(piece_char(pos.moved_piece(m).piece_type()));
+        lan.push_str(&uci::square(m.from()));
+        lan.push(if pos.capture(m) { 'x' } else { '-' });
+        lan.push_str(&uci::square(m.to()));
+
+        if m.move_type() == PROMOTION {
+            lan.push('=');
+            lan.push_str(piece_char(m.promotion_type()));
+        }
+    }
+
+    lan.push_str(check_suffix(pos, m));
+    lan
+}
+
+// move_str() converts a legal move to a string in the given notation
+
+pub fn move_str(pos: &mut Position, m: Move, notation: Notation) -> String {
+    match notation {
+        Notation::Uci => uci::move_str(m, pos.is_chess960()),
+        Notation::San => to_san(pos, m),
+        Notation::Lan => to_lan(pos, m),
+    }
+}
+
+// line_str() converts a sequence of moves, starting from the current
+// position, to a space-separated string in the given notation. The moves
+// are played on the position and taken back afterwards.
+
+pub fn line_str(pos: &mut Position, moves: &[Move], notation: Notation) -> String {
+    let mut strs = Vec::new();
+
+    for &m in moves.iter() {
+        // Stop at moves that are not legal in the current position, e.g.
+        // a stale ponder move.
+        if !MoveList::new::<Legal>(pos).contains(m) {
+            break;
+        }
+        strs.push(move_str(pos, m, notation));
+        let gives_check = pos.gives_check(m);
+        pos.do_move(m, gives_check);
+    }
+
+    for &m in moves[..strs.len()].iter().rev() {
+        pos.undo_move(m);
+    }
+
+    strs.join(" ")
+}
+
+// notation() returns the notation for the given name, which is the value
+// of the "PV Notation" option.
+
+pub fn notation(name: &str) -> Notation {
+    match name {
+        "san" => Notation::San,
+        "lan" => Notation::Lan,
+        _ => Notation::Uci,
+    }
+}
+
+// SanMove holds the parts of a move in algebraic notation. The origin
+// file and rank are only given when needed to tell moves apart, or always
+// in long algebraic notation.
+
+struct SanMove {
+    piece: PieceType,
+    file: Option<File>,
+    rank: Option<Rank>,
+    to: Square,
+    promotion: Option<PieceType>,
+}
+
+fn piece_type(c: char) -> Option<PieceType> {
+    match c {
+        'N' => Some(KNIGHT),
+        'B' => Some(BISHOP),
+        'R' => Some(ROOK),
+        'Q' => Some(QUEEN),
+        'K' => Some(KING),
+        _ => None,
+    }
+}
+
+fn file_of(c: char) -> Option<File> {
+    if ('a'..='h').contains(&c) {
+        Some(c as u32 - 'a' as u32)
+    } else {
+        None
+    }
+}
+
+fn rank_of(c: char) -> Option<Rank> {
+    if ('1'..='8').contains(&c) {
+        Some(c as u32 - '1' as u32)
+    } else {
+        None
+    }
+}
+
+// parse_san() splits a move in SAN or long algebraic notation, without
+// castling, into its parts. The characters that are optional or written
+// in different ways (check and annotation suffixes, capture and promotion
+// marks, the dash of long algebraic notation, "e.p.") must be removed
+// already. The destination is the last square in the string: anything
+// before it is the piece and the origin, anything after it the promotion
+// piece, which may be written in lowercase.
+
+fn parse_san(s: &[char]) -> Option<SanMove> {
+    let (piece, s) = match s.first().and_then(|&c| piece_type(c)) {
+        Some(pt) => (pt, &s[1..]),
+        None => (PAWN, s),
+    };
+
+    let idx = (0..s.len().saturating_sub(1))
+        .rev()
+        .find(|&i| file_of(s[i]).is_some() && rank_of(s[i + 1]).is_some())?;
+    let to = Square::make(file_of(s[idx])?, rank_of(s[idx + 1])?);
+
+    let mut file = None;
+    let mut rank = None;
+    for &c in s[..idx].iter() {
+        match (file_of(c), rank_of(c)) {
+            (Some(f), _) if file.is_none() && rank.is_none() => file = Some(f),
+            (_, Some(r)) if rank.is_none() => rank = Some(r),
+            _ => return None,
+        }
+    }
+
+    let promotion = match s[idx + 2..] {
+        [] => None,
+        [c] if piece == PAWN => match piece_type(c.to_ascii_uppercase()) {
+            Some(KING) | None => return None,
+            pt => pt,
+        },
+        _ => return None,
+    };
+
+    Some(SanMove {
+        piece,
+        file,
+        rank,
+        to,
+        promotion,
+    })
+}
+
+// from_san() converts a string in SAN, long algebraic or coordinate
+// notation to the corresponding legal Move, if any. The parts of the move
+// are matched against the legal moves, so redundant disambiguation (Ngf3)
+// is accepted, while an ambiguous move gives none. Castling may be written
+// with zeros.
+
+pub fn from_san(pos: &mut Position, s: &str) -> Move {
+    let s = s.trim_end_matches("e.p.");
+    let chars: Vec<char> = s
+        .chars()
+        .filter(|&c| !"+#!?x=-:".contains(c))
+        .map(|c| if c == '0' { 'O' } else { c })
+        .collect();
+
+    let castling = match chars.iter().collect::<String>().as_str() {
+        "OO" => Some(true),
+        "OOO" => Some(false),
+        _ => None,
+    };
+    if let Some(king_side) = castling {
+        return MoveList::new::<Legal>(pos)
+            .find(|m| m.move_type() == CASTLING && (m.to() > m.from()) == king_side)
+            .unwrap_or(Move::NONE);
+    }
+
+    let san = match parse_san(&chars) {
+        Some(san) => san,
+        // Piece moves in coordinate notation and Chess960 castling as
+        // king captures rook
+        None => return uci::to_move(pos, s),
+    };
+
+    let mut found = Move::NONE;
+    for m in MoveList::new::<Legal>(pos) {
+        let promotion = if m.move_type() == PROMOTION {
+            Some(m.promotion_type())
+        } else {
+            None
+        };
+        if m.to() != san.to
+            || m.move_type() == CASTLING
+            || pos.moved_piece(m).piece_type() != san.piece
+            || san.file.is_some_and(|f| m.from().file() != f)
+            || san.rank.is_some_and(|r| m.from().rank() != r)
+            || promotion != san.promotion
+        {
+            continue;
+        }
+        if found != Move::NONE {
+            return Move::NONE;
+        }
+        found = m;
+    }
+
+    if found == Move::NONE {
+        uci::to_move(pos, s)
+    } else {
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use engine::Engine;
+
+    fn position(fen: &str, chess960: bool) -> Position {
+        let _ = Engine::new();
+        Position::from_fen(fen, chess960).unwrap()
+    }
+
+    fn check(pos: &mut Position, s: &str, expected: &str) {
+        let m = from_san(pos, s);
+        assert_eq!(uci::move_str(m, pos.is_chess960()), expected, "{}", s);
+    }
+
+    // Every legal move converted to SAN, long algebraic and coordinate
+    // notation must give back the same move
+
+    #[test]
+    fn round_trip() {
+        let fens = [
+            (uci::START_FEN, false),
+            (
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                false,
+            ),
+            (
+                "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+                false,
+            ),
+            ("8/8/1k6/2b5/2pP4/8/5K2/8 b - d3 0 1", false),
+            ("1N2k3/8/8/8/8/8/8/1N2KN2 w - - 0 1", false),
+            (
+                "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+                true,
+            ),
+            ("4k3/8/8/8/8/8/8/1R3KR1 w GB - 0 1", true),
+        ];
+
+        for &(fen, chess960) in fens.iter() {
+            let mut pos = position(fen, chess960);
+            for m in MoveList::new::<Legal>(&pos) {
+                for &notation in [Notation::San, Notation::Lan, Notation::Uci].iter() {
+                    let s = move_str(&mut pos, m, notation);
+                    assert!(from_san(&mut pos, &s) == m, "{} in {}", s, fen);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn variants() {
+        let mut pos = position(uci::START_FEN, false);
+        check(&mut pos, "Nf3", "g1f3");
+        check(&mut pos, "Ngf3", "g1f3");
+        check(&mut pos, "Ng1f3", "g1f3");
+        check(&mut pos, "Ng1-f3!?", "g1f3");
+        check(&mut pos, "e4", "e2e4");
+        check(&mut pos, "e2-e4", "e2e4");
+        check(&mut pos, "Nf6", "(none)");
+        check(&mut pos, "Nbf3", "(none)");
+        check(&mut pos, "nf3", "(none)");
+
+        let mut pos = position("8/1P1P4/6k1/8/8/8/8/R3K2R w KQ - 0 1", false);
+        check(&mut pos, "d8=Q+", "d7d8q");
+        check(&mut pos, "d8=q", "d7d8q");
+        check(&mut pos, "d8N", "d7d8n");
+        check(&mut pos, "d7d8r", "d7d8r");
+        check(&mut pos, "d8", "(none)");
+        check(&mut pos, "d8=K", "(none)");
+        check(&mut pos, "O-O", "e1g1");
+        check(&mut pos, "0-0-0", "e1c1");
+
+        // Two knights can move to d2
+        let mut pos = position("4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1", false);
+        check(&mut pos, "Nd2", "(none)");
+        check(&mut pos, "N1d2", "(none)");
+        check(&mut pos, "Nbd2", "b1d2");
+        check(&mut pos, "Nf1d2", "f1d2");
+        check(&mut pos, "Ne3", "f1e3");
+
+        let mut pos = position("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2", false);
+        check(&mut pos, "exd6", "e5d6");
+        check(&mut pos, "exd6e.p.", "e5d6");
+        check(&mut pos, "ed6", "e5d6");
+
+        let mut pos = position("4k3/8/8/8/8/8/8/1R3KR1 w GB - 0 1", true);
+        check(&mut pos, "O-O", "f1g1");
+        check(&mut pos, "O-O-O", "f1b1");
+        check(&mut pos, "f1b1", "f1b1");
+        check(&mut pos, "Kg2", "f1g2");
+    }
+}
@@ -19,18 +19,20 @@ macro_rules! s {
 
 const V0: Value = Value::ZERO;
 
-// Isolated pawn penalty
-const ISOLATED: Score = s!(13, 16);
+tunable! {
+    // Isolated pawn penalty
+    pub ISOLATED: Score = s!(13, 16);
 
-// Backward pawn penalty
-const BACKWARD: Score = s!(17, 11);
+    // Backward pawn penalty
+    pub BACKWARD: Score = s!(17, 11);
+
+    // Doubled pawn penalty
+    pub DOUBLED: Score = s!(13, 40);
+}
 
 // Connected pawn bonus by opposed, phalanx, #support and rank
 static mut CONNECTED: [[[[Score; 8]; 3]; 2]; 2] = [[[[Score::ZERO; 8]; 3]; 2]; 2];
 
-// Doubled pawn penalty
-const DOUBLED: Score = s!(13, 40);
-
 // Strength of pawn shelter for our king by [distance from edge][rank].
 // RANK_1 = 0 is used for files where we have no pawn, or pawn is behind our king.
 const SHELTER_STRENGTH: [[Value; 8]; 4] = [
@@ -83,7 +85,7 @@ const STORM_DANGER: [[[Value; 8]; 4]; 3] = [
 
 pub struct Entry {
     key: Key,
-    score: Score,
+    scores: [Score; 2],
     passed_pawns: [Bitboard; 2],
     pawn_attacks: [Bitboard; 2],
     pawn_attacks_span: [Bitboard; 2],
@@ -101,7 +103,7 @@ impl Entry {
     pub fn new() -> Entry {
         Entry {
             key: Key(0),
-            score: Score::ZERO,
+            scores: [Score::ZERO; 2],
             passed_pawns: [Bitboard(0); 2],
             pawn_attacks: [Bitboard(0); 2],
             pawn_attacks_span: [Bitboard(0); 2],
@@ -117,7 +119,11 @@ impl Entry {
     }
 
     pub fn pawns_score(&self) -> Score {
-        self.score
+        self.scores[WHITE.0 as usize] - self.scores[BLACK.0 as usize]
+    }
+
+    pub fn pawn_score(&self, c: Color) -> Score {
+        self.scores[c.0 as usize]
     }
 
     pub fn pawn_attacks(&self, c: Color) -> Bitboard {
@@ -301,7 +307,8 @@ pub fn probe(pos: &Position) -> &mut Entry {
     }
 
     e.key = key;
-    e.score = evaluate::<White>(pos, e) - evaluate::<Black>(pos, e);
+    e.scores[WHITE.0 as usize] = evaluate::<White>(pos, e);
+    e.scores[BLACK.0 as usize] = evaluate::<Black>(pos, e);
     e.open_files = (e.semiopen_files[WHITE.0 as usize] & e.semiopen_files[BLACK.0 as usize])
         .count_ones() as i32;
     e.asymmetry = (e.passed_pawns[WHITE.0 as usize].0
@@ -384,15 +391,15 @@ fn evaluate<Us: ColorTrait>(pos: &Position, e: &mut Entry) -> Score {
                     [popcount(supported) as usize][s.relative_rank(us) as usize]
             };
         } else if neighbours == 0 {
-            score -= ISOLATED;
+            score -= param!(ISOLATED);
             e.weak_unopposed[us.0 as usize] += (opposed == 0) as i32;
         } else if backward {
-            score -= BACKWARD;
+            score -= param!(BACKWARD);
             e.weak_unopposed[us.0 as usize] += (opposed == 0) as i32;
         }
 
         if doubled != 0 && supported == 0 {
-            score -= DOUBLED;
+            score -= param!(DOUBLED);
         }
     }
 
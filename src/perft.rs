@@ -0,0 +1,145 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+// Perft counts the leaf nodes of the tree of legal moves up to a given
+// depth, which verifies move generation against known results. The moves
+// of the last ply are not made: their number is the length of the move
+// list ("bulk counting"). Optionally, the counts of subtrees are kept in a
+// hash table keyed on the position and the remaining depth, so that
+// subtrees reached by different move orders are only counted once.
+
+use movegen::*;
+use position::Position;
+use types::*;
+
+#[derive(Clone, Copy)]
+struct PerftEntry {
+    key: u64,
+    depth: i32,
+    nodes: u64,
+}
+
+/// PerftTable holds the leaf counts of subtrees. Entries are always
+/// replaced. Only subtrees of depth 2 and more are stored, so an empty
+/// entry (depth 0) never matches.
+pub struct PerftTable {
+    entries: Vec<PerftEntry>,
+}
+
+impl PerftTable {
+    // new() creates a table of at most the given size in MB. The number of
+    // entries is a power of two.
+
+    pub fn new(mb: usize) -> PerftTable {
+        let size = std::mem::size_of::<PerftEntry>();
+        let count = (mb * 1024 * 1024 / size).max(1);
+        let count = if count.is_power_of_two() {
+            count
+        } else {
+            count.next_power_of_two() / 2
+        };
+
+        PerftTable {
+            entries: vec![
+                PerftEntry {
+                    key: 0,
+                    depth: 0,
+                    nodes: 0,
+                };
+                count
+            ],
+        }
+    }
+
+    fn index(&self, key: Key, depth: i32) -> usize {
+        let k = key.0 ^ (depth as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        k as usize & (self.entries.len() - 1)
+    }
+
+    fn probe(&self, key: Key, depth: i32) -> Option<u64> {
+        let e = &self.entries[self.index(key, depth)];
+        if e.key == key.0 && e.depth == depth {
+            Some(e.nodes)
+        } else {
+            None
+        }
+    }
+
+    fn store(&mut self, key: Key, depth: i32, nodes: u64) {
+        let i = self.index(key, depth);
+        self.entries[i] = PerftEntry {
+            key: key.0,
+            depth,
+            nodes,
+        };
+    }
+}
+
+// count() returns the number of leaf nodes at the given depth in plies
+// below the current position
+
+fn count(pos: &mut Position, depth: i32, mut table: Option<&mut PerftTable>) -> u64 {
+    if depth <= 0 {
+        return 1;
+    }
+
+    if depth == 1 {
+        return MoveList::new::<Legal>(pos).len() as u64;
+    }
+
+    if let Some(nodes) = table.as_ref().and_then(|t| t.probe(pos.key(), depth)) {
+        return nodes;
+    }
+
+    let mut nodes = 0;
+    for m in MoveList::new::<Legal>(pos) {
+        let gives_check = pos.gives_check(m);
+        pos.do_move(m, gives_check);
+        nodes += count(pos, depth - 1, table.as_deref_mut());
+        pos.undo_move(m);
+    }
+
+    if let Some(t) = table {
+        t.store(pos.key(), depth, nodes);
+    }
+
+    nodes
+}
+
+// perft() returns the number of leaf nodes at the given depth in plies
+
+pub fn perft(pos: &mut Position, depth: i32) -> u64 {
+    count(pos, depth, None)
+}
+
+// perft_hashed() is perft() using a hash table, which may already hold
+// counts from earlier runs
+
+pub fn perft_hashed(pos: &mut Position, depth: i32, table: &mut PerftTable) -> u64 {
+    count(pos, depth, Some(table))
+}
+
+// divide() counts the leaf nodes below each root move, passing the counts
+// to f as they become available, and returns the total
+
+pub fn divide<F>(pos: &mut Position, depth: i32, mut table: Option<&mut PerftTable>, mut f: F) -> u64
+where
+    F: FnMut(&Position, Move, u64),
+{
+    let mut nodes = 0;
+
+    for m in MoveList::new::<Legal>(pos) {
+        let cnt = if depth <= 1 {
+            1
+        } else {
+            let gives_check = pos.gives_check(m);
+            pos.do_move(m, gives_check);
+            let cnt = count(pos, depth - 1, table.as_deref_mut());
+            pos.undo_move(m);
+            cnt
+        };
+        f(pos, m, cnt);
+        nodes += cnt;
+    }
+
+    nodes
+}
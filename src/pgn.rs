@@ -0,0 +1,765 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use notation;
+use position::{FenError, Position};
+use types::*;
+use uci;
+
+use std;
+use std::fmt;
+use std::io;
+use std::io::{BufRead, Write};
+
+#[derive(Debug)]
+pub enum PgnError {
+    Io(io::Error),
+    Fen(FenError),
+    IllegalMove(String),
+    NullMove(String),
+    Syntax(String),
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PgnError::Io(ref e) => write!(f, "{}", e),
+            PgnError::Fen(ref e) => write!(f, "invalid FEN tag: {}", e),
+            PgnError::IllegalMove(ref s) => write!(f, "illegal move {}", s),
+            PgnError::NullMove(ref s) => write!(f, "null move -- in {} is not supported", s),
+            PgnError::Syntax(ref s) => write!(f, "{}", s),
+        }
+    }
+}
+
+impl std::error::Error for PgnError {}
+
+impl From<io::Error> for PgnError {
+    fn from(e: io::Error) -> PgnError {
+        PgnError::Io(e)
+    }
+}
+
+impl From<FenError> for PgnError {
+    fn from(e: FenError) -> PgnError {
+        PgnError::Fen(e)
+    }
+}
+
+// A GameMove is a move of the game together with its annotations. The
+// variations are alternatives to this move, starting from the position
+// before it.
+
+#[derive(Clone, Debug)]
+pub struct GameMove {
+    pub m: Move,
+    pub san: String,
+    pub nags: Vec<u8>,
+    pub comment_before: Option<String>,
+    pub comment: Option<String>,
+    pub variations: Vec<Vec<GameMove>>,
+}
+
+impl GameMove {
+    pub fn new(m: Move, san: String) -> GameMove {
+        GameMove {
+            m,
+            san,
+            nags: Vec::new(),
+            comment_before: None,
+            comment: None,
+            variations: Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Game {
+    pub tags: Vec<(String, String)>,
+    pub comment: Option<String>,
+    pub moves: Vec<GameMove>,
+    pub result: String,
+}
+
+const SEVEN_TAG_ROSTER: [&str; 7] = ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+
+impl Game {
+    // new() creates an empty game with the Seven Tag Roster set to unknown
+    // values
+
+    pub fn new() -> Game {
+        Game {
+            tags: SEVEN_TAG_ROSTER
+                .iter()
+                .map(|&t| (String::from(t), String::from(if t == "Result" { "*" } else { "?" })))
+                .collect(),
+            comment: None,
+            moves: Vec::new(),
+            result: String::from("*"),
+        }
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|t| t.0 == name)
+            .map(|t| t.1.as_str())
+    }
+
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|t| t.0 == name) {
+            Some(t) => t.1 = String::from(value),
+            None => self.tags.push((String::from(name), String::from(value))),
+        }
+    }
+
+    pub fn set_result(&mut self, result: &str) {
+        self.result = String::from(result);
+        self.set_tag("Result", result);
+    }
+
+    pub fn is_chess960(&self) -> bool {
+        match self.tag("Variant") {
+            Some(v) => {
+                let v = v.to_lowercase();
+                v == "chess960" || v == "fischerandom" || v == "chess 960"
+            }
+            None => false,
+        }
+    }
+
+    pub fn start_fen(&self) -> String {
+        String::from(self.tag("FEN").unwrap_or(uci::START_FEN))
+    }
+
+    pub fn start_position(&self) -> Result<Position, FenError> {
+        Position::from_fen(&self.start_fen(), self.is_chess960())
+    }
+
+    // push() appends a move played in the given position, which must be
+    // the current final position of the game, to the main line. The move
+    // itself is not played.
+
+    pub fn push(&mut self, pos: &mut Position, m: Move) {
+        let san = notation::to_san(pos, m);
+        self.moves.push(GameMove::new(m, san));
+    }
+
+    // replay() plays the main line from the starting position, calling f
+    // with the position before each move, and returns the final position.
+
+    pub fn replay<F: FnMut(&Position, &GameMove)>(&self, mut f: F) -> Result<Position, FenError> {
+        let mut pos = self.start_position()?;
+        for mv in self.moves.iter() {
+            f(&pos, mv);
+            let gives_check = pos.gives_check(mv.m);
+            pos.do_move(mv.m, gives_check);
+        }
+        Ok(pos)
+    }
+
+    pub fn final_position(&self) -> Result<Position, FenError> {
+        self.replay(|_, _| {})
+    }
+
+    // uci_moves() returns the main line in coordinate notation
+
+    pub fn uci_moves(&self, chess960: bool) -> Vec<String> {
+        self.moves
+            .iter()
+            .map(|mv| uci::move_str(mv.m, chess960))
+            .collect()
+    }
+
+    // write() writes the game in PGN export format, with the movetext
+    // wrapped so that no line is longer than the 79 characters allowed by
+    // the standard.
+
+    pub fn write<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        for (name, value) in self.tags.iter() {
+            let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+            writeln!(w, "[{} \"{}\"]", name, value)?;
+        }
+        writeln!(w)?;
+
+        let ply = match self.start_position() {
+            Ok(pos) => pos.game_ply(),
+            Err(_) => 0,
+        };
+        let mut tokens = Vec::new();
+        if let Some(ref c) = self.comment {
+            tokens.push(format!("{{{}}}", c));
+        }
+        write_line(&self.moves, ply, &mut tokens);
+        tokens.push(self.result.clone());
+
+        let mut line = String::new();
+        for token in tokens.iter() {
+            if !line.is_empty() && line.len() + 1 + token.len() > 79 {
+                writeln!(w, "{}", line)?;
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(token);
+        }
+        writeln!(w, "{}", line)?;
+        writeln!(w)
+    }
+}
+
+impl Default for Game {
+    fn default() -> Game {
+        Game::new()
+    }
+}
+
+// write_line() converts a line of moves starting at the given ply into
+// movetext tokens. Move numbers are repeated for Black after comments and
+// variations.
+
+fn write_line(moves: &[GameMove], mut ply: i32, tokens: &mut Vec<String>) {
+    let mut need_number = true;
+
+    for mv in moves.iter() {
+        if let Some(ref c) = mv.comment_before {
+            tokens.push(format!("{{{}}}", c));
+            need_number = true;
+        }
+        if ply % 2 == 0 {
+            tokens.push(format!("{}.", ply / 2 + 1));
+        } else if need_number {
+            tokens.push(format!("{}...", ply / 2 + 1));
+        }
+        tokens.push(mv.san.clone());
+        for nag in mv.nags.iter() {
+            tokens.push(format!("${}", nag));
+        }
+        need_number = false;
+
+        if let Some(ref c) = mv.comment {
+            tokens.push(format!("{{{}}}", c));
+            need_number = true;
+        }
+        for variation in mv.variations.iter() {
+            let mut var_tokens = Vec::new();
+            write_line(variation, ply, &mut var_tokens);
+            if let Some(first) = var_tokens.first_mut() {
+                first.insert(0, '(');
+            }
+            if let Some(last) = var_tokens.last_mut() {
+                last.push(')');
+            }
+            tokens.append(&mut var_tokens);
+            need_number = true;
+        }
+        ply += 1;
+    }
+}
+
+enum Token {
+    Tag(String, String),
+    Move(String),
+    Nag(u8),
+    NullMove,
+    Comment(String),
+    Open,
+    Close,
+    Result(String),
+}
+
+// Lexer splits PGN input into tokens. Input is read line by line, so that
+// large files can be processed without loading them into memory. Bytes
+// that are not valid UTF-8 (e.g. Latin-1 names) are replaced.
+
+struct Lexer<R> {
+    reader: R,
+    line: Vec<char>,
+    idx: usize,
+    eof: bool,
+    pending: Option<Token>,
+}
+
+impl<R: BufRead> Lexer<R> {
+    fn peek_char(&mut self) -> io::Result<Option<char>> {
+        while self.idx >= self.line.len() {
+            if self.eof {
+                return Ok(None);
+            }
+            let mut buf = Vec::new();
+            if self.reader.read_until(b'\n', &mut buf)? == 0 {
+                self.eof = true;
+                return Ok(None);
+            }
+            let line = String::from_utf8_lossy(&buf);
+            // Lines starting with '%' are escaped and ignored
+            if line.starts_with('%') {
+                continue;
+            }
+            self.line = line.chars().collect();
+            self.idx = 0;
+        }
+        Ok(Some(self.line[self.idx]))
+    }
+
+    fn next_char(&mut self) -> io::Result<Option<char>> {
+        let c = self.peek_char()?;
+        if c.is_some() {
+            self.idx += 1;
+        }
+        Ok(c)
+    }
+
+    fn push_back(&mut self, token: Token) {
+        self.pending = Some(token);
+    }
+
+    fn next_token(&mut self) -> Result<Option<Token>, PgnError> {
+        if let Some(token) = self.pending.take() {
+            return Ok(Some(token));
+        }
+
+        loop {
+            let c = match self.next_char()? {
+                Some(c) => c,
+                None => return Ok(None),
+            };
+
+            let token = match c {
+                '[' => self.tag()?,
+                '{' => {
+                    let mut text = String::new();
+                    loop {
+                        match self.next_char()? {
+                            Some('}') => break,
+                            Some(c) => text.push(c),
+                            None => return Err(PgnError::Syntax(String::from("unterminated comment"))),
+                        }
+                    }
+                    Token::Comment(text.split_whitespace().collect::<Vec<_>>().join(" "))
+                }
+                ';' => {
+                    let mut text = String::new();
+                    while let Some(c) = self.next_char()? {
+                        if c == '\n' {
+                            break;
+                        }
+                        text.push(c);
+                    }
+                    Token::Comment(String::from(text.trim()))
+                }
+                // The null move of ChessBase and other programs
+                '-' if self.peek_char()? == Some('-') => {
+                    self.idx += 1;
+                    Token::NullMove
+                }
+                '(' => Token::Open,
+                ')' => Token::Close,
+                '*' => Token::Result(String::from("*")),
+                '$' => {
+                    let digits = self.read_while(|c| c.is_ascii_digit())?;
+                    match digits.parse() {
+                        Ok(n) => Token::Nag(n),
+                        Err(_) => return Err(PgnError::Syntax(format!("invalid NAG ${}", digits))),
+                    }
+                }
+                '!' | '?' => {
+                    let s = format!("{}{}", c, self.read_while(|c| c == '!' || c == '?')?);
+                    match s.as_ref() {
+                        "!" => Token::Nag(1),
+                        "?" => Token::Nag(2),
+                        "!!" => Token::Nag(3),
+                        "??" => Token::Nag(4),
+                        "!?" => Token::Nag(5),
+                        "?!" => Token::Nag(6),
+                        _ => return Err(PgnError::Syntax(format!("invalid annotation {}", s))),
+                    }
+                }
+                c if c.is_alphanumeric() => {
+                    let symbol = format!("{}{}", c, self.read_while(is_symbol_char)?);
+                    match symbol.as_ref() {
+                        "1-0" | "0-1" | "1/2-1/2" => Token::Result(symbol),
+                        // Skip move numbers, the dots are skipped below
+                        s if s.chars().all(|c| c.is_ascii_digit()) => continue,
+                        _ => Token::Move(symbol),
+                    }
+                }
+                c if c.is_whitespace() || c == '.' => continue,
+                c => return Err(PgnError::Syntax(format!("unexpected character '{}'", c))),
+            };
+
+            return Ok(Some(token));
+        }
+    }
+
+    fn read_while<F: Fn(char) -> bool>(&mut self, f: F) -> io::Result<String> {
+        let mut s = String::new();
+        while let Some(c) = self.peek_char()? {
+            if !f(c) {
+                break;
+            }
+            s.push(c);
+            self.idx += 1;
+        }
+        Ok(s)
+    }
+
+    // tag() reads a tag pair after the opening bracket: Name "Value"]
+
+    fn tag(&mut self) -> Result<Token, PgnError> {
+        self.read_while(char::is_whitespace)?;
+        let name = self.read_while(|c| c.is_alphanumeric() || c == '_')?;
+        self.read_while(char::is_whitespace)?;
+
+        if name.is_empty() || self.next_char()? != Some('"') {
+            return Err(PgnError::Syntax(String::from("malformed tag pair")));
+        }
+
+        let mut value = String::new();
+        loop {
+            match self.next_char()? {
+                Some('"') => break,
+                Some('\\') => {
+                    if let Some(c) = self.next_char()? {
+                        value.push(c);
+                    }
+                }
+                Some('\n') | None => {
+                    return Err(PgnError::Syntax(format!("unterminated tag {}", name)))
+                }
+                Some(c) => value.push(c),
+            }
+        }
+
+        self.read_while(|c| c != ']' && c != '\n')?;
+        if self.next_char()? != Some(']') {
+            return Err(PgnError::Syntax(format!("unterminated tag {}", name)));
+        }
+
+        Ok(Token::Tag(name, value))
+    }
+}
+
+fn is_symbol_char(c: char) -> bool {
+    c.is_alphanumeric() || "+#=-:/_".contains(c)
+}
+
+/// PgnReader reads the games of a PGN file one by one. After a game with
+/// an error, reading continues with the next game.
+pub struct PgnReader<R> {
+    lexer: Lexer<R>,
+}
+
+impl<R: BufRead> PgnReader<R> {
+    pub fn new(reader: R) -> PgnReader<R> {
+        PgnReader {
+            lexer: Lexer {
+                reader,
+                line: Vec::new(),
+                idx: 0,
+                eof: false,
+                pending: None,
+            },
+        }
+    }
+
+    fn read_game(&mut self) -> Result<Option<Game>, PgnError> {
+        let mut game = Game::new();
+        game.tags.clear();
+
+        // 1. Tag pair section
+        let mut token = self.lexer.next_token()?;
+        while let Some(Token::Tag(name, value)) = token {
+            game.set_tag(&name, &value);
+            token = self.lexer.next_token()?;
+        }
+        match token {
+            Some(token) => self.lexer.push_back(token),
+            None if game.tags.is_empty() => return Ok(None),
+            None => {}
+        }
+
+        // 2. Movetext section
+        let mut pos = game.start_position()?;
+        let mut result = String::from("*");
+        let (moves, comment) = self.read_line(&mut pos, 0, &mut result)?;
+        game.moves = moves;
+        game.comment = comment;
+        game.result = result;
+
+        Ok(Some(game))
+    }
+
+    // read_line() reads a sequence of moves starting from the given
+    // position until the end of the variation or game. The moves are taken
+    // back before returning. A comment that is not attached to any move is
+    // returned separately.
+
+    fn read_line(
+        &mut self,
+        pos: &mut Position,
+        depth: usize,
+        result: &mut String,
+    ) -> Result<(Vec<GameMove>, Option<String>), PgnError> {
+        let mut moves: Vec<GameMove> = Vec::new();
+        let mut comment: Option<String> = None;
+
+        loop {
+            let token = match self.lexer.next_token()? {
+                Some(token) => token,
+                None if depth > 0 => {
+                    return Err(PgnError::Syntax(String::from("unterminated variation")))
+                }
+                None => break,
+            };
+
+            match token {
+                Token::Move(s) => {
+                    let m = notation::from_san(pos, &s);
+                    if m == Move::NONE {
+                        return Err(PgnError::IllegalMove(format!("{} in {}", s, pos.fen())));
+                    }
+                    let mut mv = GameMove::new(m, notation::to_san(pos, m));
+                    mv.comment_before = comment.take();
+                    let gives_check = pos.gives_check(m);
+                    pos.do_move(m, gives_check);
+                    moves.push(mv);
+                }
+                Token::NullMove => return Err(PgnError::NullMove(pos.fen())),
+                Token::Nag(n) => {
+                    if let Some(last) = moves.last_mut() {
+                        last.nags.push(n);
+                    }
+                }
+                Token::Comment(text) => {
+                    let target = match moves.last_mut() {
+                        Some(last) => &mut last.comment,
+                        None => &mut comment,
+                    };
+                    *target = Some(match target.take() {
+                        Some(c) => format!("{} {}", c, text),
+                        None => text,
+                    });
+                }
+                Token::Open => {
+                    let mut last = match moves.pop() {
+                        Some(last) => last,
+                        None => {
+                            return Err(PgnError::Syntax(String::from(
+                                "variation before the first move",
+                            )))
+                        }
+                    };
+                    pos.undo_move(last.m);
+                    let (variation, _) = self.read_line(pos, depth + 1, result)?;
+                    let gives_check = pos.gives_check(last.m);
+                    pos.do_move(last.m, gives_check);
+                    if !variation.is_empty() {
+                        last.variations.push(variation);
+                    }
+                    moves.push(last);
+                }
+                Token::Close if depth > 0 => break,
+                Token::Close => return Err(PgnError::Syntax(String::from("unexpected ')'"))),
+                Token::Result(r) if depth == 0 => {
+                    *result = r;
+                    break;
+                }
+                Token::Result(r) => {
+                    return Err(PgnError::Syntax(format!("result {} inside variation", r)))
+                }
+                // A new game starts without a result for this one
+                Token::Tag(name, value) if depth == 0 => {
+                    self.lexer.push_back(Token::Tag(name, value));
+                    break;
+                }
+                Token::Tag(..) => {
+                    return Err(PgnError::Syntax(String::from("tag inside variation")))
+                }
+            }
+        }
+
+        for mv in moves.iter().rev() {
+            pos.undo_move(mv.m);
+        }
+
+        Ok((moves, comment))
+    }
+
+    // skip_game() skips the rest of a game after an error
+
+    fn skip_game(&mut self) {
+        loop {
+            match self.lexer.next_token() {
+                Ok(Some(Token::Result(_))) | Ok(None) => break,
+                Ok(Some(Token::Tag(name, value))) => {
+                    self.lexer.push_back(Token::Tag(name, value));
+                    break;
+                }
+                Ok(Some(_)) => {}
+                Err(PgnError::Io(_)) => {
+                    self.lexer.eof = true;
+                    self.lexer.line.clear();
+                    break;
+                }
+                Err(_) => {}
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for PgnReader<R> {
+    type Item = Result<Game, PgnError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.read_game() {
+            Ok(Some(game)) => Some(Ok(game)),
+            Ok(None) => None,
+            Err(e) => {
+                self.skip_game();
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use engine::Engine;
+
+    const OPERA_GAME: &str = r#"[Event "Paris"]
+[Site "Paris FRA"]
+[Date "1858.??.??"]
+[Round "?"]
+[White "Paul Morphy"]
+[Black "Duke Karl / Count Isouard"]
+[Result "1-0"]
+
+{The Opera Game} 1. e4 e5 2. Nf3 d6 3. d4 Bg4 $6 {This is a weak move
+already.} 4. dxe5 Bxf3 (4... dxe5 5. Qxd8+ Kxd8 6. Nxe5 (6. Bc4 $5) 6... Be6)
+5. Qxf3 dxe5 6. Bc4 Nf6 7. Qb3 Qe7 8. Nc3 c6 9. Bg5 b5?! ; Black loses quickly
+10. Nxb5 cxb5 11. Bxb5+ Nbd7 12. O-O-O Rd8 13. Rxd7 Rxd7 14. Rd1 Qe6 15. Bxd7+
+Nxd7 16. Qb8+ Nxb8 17. Rd8# 1-0
+"#;
+
+    fn read(text: &str) -> Vec<Result<Game, PgnError>> {
+        PgnReader::new(text.as_bytes()).collect()
+    }
+
+    fn sans(moves: &[GameMove]) -> Vec<&str> {
+        moves.iter().map(|mv| mv.san.as_str()).collect()
+    }
+
+    #[test]
+    fn read_annotated_game() {
+        Engine::new();
+
+        let games = read(OPERA_GAME);
+        assert_eq!(games.len(), 1);
+        let game = games[0].as_ref().unwrap();
+
+        assert_eq!(game.tags.len(), 7);
+        assert_eq!(game.tag("White"), Some("Paul Morphy"));
+        assert_eq!(game.tag("Black"), Some("Duke Karl / Count Isouard"));
+        assert_eq!(game.result, "1-0");
+        assert_eq!(game.comment, None);
+        assert_eq!(game.moves[0].comment_before.as_deref(), Some("The Opera Game"));
+
+        assert_eq!(game.moves.len(), 33);
+        assert_eq!(game.moves[5].san, "Bg4");
+        assert_eq!(game.moves[5].nags, [6]);
+        assert_eq!(
+            game.moves[5].comment.as_deref(),
+            Some("This is a weak move already.")
+        );
+        assert_eq!(game.moves[17].san, "b5");
+        assert_eq!(game.moves[17].nags, [6]);
+        assert_eq!(game.moves[17].comment.as_deref(), Some("Black loses quickly"));
+        assert_eq!(game.moves[32].san, "Rd8#");
+
+        // A variation with a nested one
+        let variations = &game.moves[7].variations;
+        assert_eq!(variations.len(), 1);
+        assert_eq!(sans(&variations[0]), ["dxe5", "Qxd8+", "Kxd8", "Nxe5", "Be6"]);
+        let nested = &variations[0][3].variations;
+        assert_eq!(nested.len(), 1);
+        assert_eq!(sans(&nested[0]), ["Bc4"]);
+        assert_eq!(nested[0][0].nags, [5]);
+
+        let pos = game.final_position().unwrap();
+        assert!(pos.checkers() != 0);
+        assert_eq!(game.uci_moves(false)[22], "e1c1");
+    }
+
+    #[test]
+    fn results() {
+        Engine::new();
+
+        let games = read(
+            "[White \"a\"]\n1. e4 e5 1/2-1/2\n\n[White \"b\"]\n1. d4 0-1\n\n\
+             [White \"c\"]\n1. c4 *\n\n[White \"d\"]\n1. Nf3\n\n[White \"e\"]\n1. g3 1-0\n",
+        );
+        let results: Vec<(&str, &str)> = games
+            .iter()
+            .map(|g| {
+                let g = g.as_ref().unwrap();
+                (g.tag("White").unwrap(), g.result.as_str())
+            })
+            .collect();
+        assert_eq!(
+            results,
+            [("a", "1/2-1/2"), ("b", "0-1"), ("c", "*"), ("d", "*"), ("e", "1-0")]
+        );
+    }
+
+    #[test]
+    fn errors() {
+        Engine::new();
+
+        // Reading goes on with the next game after an error
+        let games = read(
+            "[White \"n\"]\n1. e4 -- 2. d4 1-0\n\n[White \"i\"]\n1. e5 *\n\n\
+             [White \"v\"]\n1. e4 (1. d4 *\n\n[White \"ok\"]\n1. e4 *\n",
+        );
+        assert_eq!(games.len(), 4);
+        match games[0] {
+            Err(PgnError::NullMove(ref fen)) => {
+                assert_eq!(fen, "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1")
+            }
+            ref r => panic!("{:?}", r),
+        }
+        match games[1] {
+            Err(PgnError::IllegalMove(ref s)) => assert!(s.starts_with("e5 in ")),
+            ref r => panic!("{:?}", r),
+        }
+        match games[2] {
+            Err(PgnError::Syntax(ref s)) => assert_eq!(s, "result * inside variation"),
+            ref r => panic!("{:?}", r),
+        }
+        assert_eq!(games[3].as_ref().unwrap().tag("White"), Some("ok"));
+    }
+
+    #[test]
+    fn write_round_trip() {
+        Engine::new();
+
+        let game = read(OPERA_GAME).remove(0).unwrap();
+        let mut text = Vec::new();
+        game.write(&mut text).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        assert!(text.lines().all(|line| line.len() < 80));
+        assert!(text.contains("{The Opera Game} 1. e4 e5"));
+        assert!(text.contains("(4... dxe5 5. Qxd8+ Kxd8 6. Nxe5 (6. Bc4 $5) 6... Be6)"));
+
+        let again = read(&text).remove(0).unwrap();
+        assert_eq!(again.tags, game.tags);
+        assert_eq!(again.comment, game.comment);
+        assert_eq!(again.result, game.result);
+        assert_eq!(format!("{:?}", again.moves), format!("{:?}", game.moves));
+
+        let mut text_again = Vec::new();
+        again.write(&mut text_again).unwrap();
+        assert_eq!(String::from_utf8(text_again).unwrap(), text);
+    }
+}
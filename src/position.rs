@@ -4,11 +4,12 @@ use bitboard::*;
 use material;
 use movegen::*;
 use movepick::*;
+use nnue;
 use pawns;
 use psqt;
 use search;
 use tb;
-use threads::ThreadCtrl;
+use threads::{SharedState, ThreadCtrl};
 use types::*;
 use uci;
 
@@ -102,6 +103,12 @@ pub struct StateInfo {
     pub blockers_for_king: [Bitboard; 2],
     pub pinners: [Bitboard; 2],
     pub check_squares: [Bitboard; 8],
+    // None if the previous state is not the position before a move, for
+    // instance after set()
+    pub dirty_piece: Option<nnue::DirtyPiece>,
+    // Whether the NNUE accumulator of this state is up to date for each
+    // perspective, see Position::accumulators()
+    pub accumulator_computed: [bool; 2],
 }
 
 impl StateInfo {
@@ -121,6 +128,8 @@ impl StateInfo {
             blockers_for_king: [Bitboard(0); 2],
             pinners: [Bitboard(0); 2],
             check_squares: [Bitboard(0); 8],
+            dirty_piece: None,
+            accumulator_computed: [false; 2],
         }
     }
 
@@ -140,10 +149,66 @@ impl StateInfo {
             blockers_for_king: [Bitboard(0); 2],
             pinners: [Bitboard(0); 2],
             check_squares: [Bitboard(0); 8],
+            dirty_piece: None,
+            accumulator_computed: [false; 2],
         }
     }
 }
 
+// FenError describes why a FEN string could not be parsed into a legal
+// position.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FenError {
+    /// A FEN string has 4 to 6 space-separated fields
+    FieldCount(usize),
+    /// The piece placement field does not have 8 ranks
+    RankCount(usize),
+    /// The given rank (1-8) describes more than 8 squares
+    RankOverflow(Rank),
+    /// The given rank (1-8) describes less than 8 squares
+    RankUnderflow(Rank),
+    BadPiece(char),
+    /// More than 16 pieces or 8 pawns for one side
+    TooManyPieces(Color),
+    KingCount(Color, i32),
+    PawnOnBackRank,
+    BadSideToMove(String),
+    /// The side that just moved is still in check
+    OpponentInCheck,
+    BadCastling(char),
+    /// A castling right without the king and rook on their squares
+    CastlingWithoutRook(char),
+    BadEpSquare(String),
+    BadMoveCounter(String),
+}
+
+impl std::fmt::Display for FenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let color = |c: Color| if c == WHITE { "white" } else { "black" };
+        match *self {
+            FenError::FieldCount(n) => write!(f, "expected 4 to 6 fields, found {}", n),
+            FenError::RankCount(n) => write!(f, "expected 8 ranks, found {}", n),
+            FenError::RankOverflow(r) => write!(f, "rank {} has more than 8 squares", r),
+            FenError::RankUnderflow(r) => write!(f, "rank {} has less than 8 squares", r),
+            FenError::BadPiece(c) => write!(f, "invalid piece character '{}'", c),
+            FenError::TooManyPieces(c) => write!(f, "too many {} pieces", color(c)),
+            FenError::KingCount(c, n) => write!(f, "{} has {} kings", color(c), n),
+            FenError::PawnOnBackRank => write!(f, "pawn on the first or eighth rank"),
+            FenError::BadSideToMove(ref s) => write!(f, "invalid side to move '{}'", s),
+            FenError::OpponentInCheck => write!(f, "side not to move is in check"),
+            FenError::BadCastling(c) => write!(f, "invalid castling character '{}'", c),
+            FenError::CastlingWithoutRook(c) => {
+                write!(f, "castling right '{}' without king and rook in place", c)
+            }
+            FenError::BadEpSquare(ref s) => write!(f, "impossible en passant square '{}'", s),
+            FenError::BadMoveCounter(ref s) => write!(f, "invalid move counter '{}'", s),
+        }
+    }
+}
+
+impl std::error::Error for FenError {}
+
 pub struct Position {
     board: [Piece; 64],
     by_color_bb: [Bitboard; 2],
@@ -157,6 +222,8 @@ pub struct Position {
     game_ply: i32,
     side_to_move: Color,
     states: Vec<StateInfo>,
+    // NNUE accumulators of the states, see accumulators()
+    accumulators: Vec<nnue::Accumulator>,
     chess960: bool,
     // Thread variables from here
     // only for main thread:
@@ -165,6 +232,7 @@ pub struct Position {
     pub previous_time_reduction: f64,
     pub previous_score: Value,
     pub calls_cnt: i32,
+    pub last_info_time: i64,
     // for all threads:
     pub thread_ctrl: Option<Arc<ThreadCtrl>>,
     pub is_main: bool,
@@ -178,13 +246,17 @@ pub struct Position {
     pub tb_hits: u64,
     pub completed_depth: Depth,
     pub root_moves: search::RootMoves,
+    pub limits: search::LimitsType,
+    pub tb_config: tb::TbConfig,
+    // NNUE network used by evaluate(), or None for the classical evaluation
+    pub network: Option<Arc<nnue::Network>>,
     // thread-specific tables
     pub pawns_table: Vec<std::cell::UnsafeCell<pawns::Entry>>,
     pub material_table: Vec<std::cell::UnsafeCell<material::Entry>>,
     pub counter_moves: CounterMoveHistory,
     pub main_history: ButterflyHistory,
     pub capture_history: CapturePieceToHistory,
-    pub cont_history: ContinuationHistory,
+    pub cont_history: Box<ContinuationHistory>,
 }
 
 impl Position {
@@ -202,12 +274,14 @@ impl Position {
             game_ply: 0,
             side_to_move: WHITE,
             states: Vec::new(),
+            accumulators: Vec::new(),
             chess960: false,
             failed_low: false,
             best_move_changes: 0.0,
             previous_time_reduction: 0.0,
             previous_score: Value::ZERO,
             calls_cnt: 0,
+            last_info_time: 0,
             thread_ctrl: None,
             is_main: false,
             thread_idx: 0,
@@ -220,12 +294,15 @@ impl Position {
             tb_hits: 0,
             completed_depth: Depth::ZERO,
             root_moves: Vec::new(),
+            limits: search::LimitsType::new(),
+            tb_config: tb::TbConfig::new(),
+            network: None,
             pawns_table: Vec::new(),
             material_table: Vec::new(),
             counter_moves: unsafe { std::mem::zeroed() },
             main_history: unsafe { std::mem::zeroed() },
             capture_history: unsafe { std::mem::zeroed() },
-            cont_history: unsafe { std::mem::zeroed() },
+            cont_history: ContinuationHistory::new(),
         }
     }
 
@@ -234,6 +311,46 @@ impl Position {
         self.states.push(StateInfo::new());
     }
 
+    // init_tables() allocates the pawn and material hash tables, which are
+    // needed to evaluate the position. Existing entries are discarded.
+
+    pub fn init_tables(&mut self) {
+        self.pawns_table.clear();
+        self.material_table.clear();
+        self.pawns_table.reserve_exact(16384);
+        for _ in 0..16384 {
+            self.pawns_table
+                .push(std::cell::UnsafeCell::new(pawns::Entry::new()));
+        }
+        self.material_table.reserve_exact(8192);
+        for _ in 0..8192 {
+            self.material_table
+                .push(std::cell::UnsafeCell::new(material::Entry::new()));
+        }
+    }
+
+    // shared() returns the state shared by all search threads of the engine
+    // this position belongs to. Only valid for positions owned by a search
+    // thread.
+
+    pub fn shared(&self) -> &SharedState {
+        &self.thread_ctrl.as_ref().unwrap().shared
+    }
+
+    // accumulators() gives access to the states of the moves made so far,
+    // the current one being the last, and to their NNUE accumulators. The
+    // accumulators are only allocated here, the first time a network
+    // evaluates a position this deep, so that the classical evaluation
+    // never touches them.
+
+    pub fn accumulators(&mut self) -> (&mut [StateInfo], &mut [nnue::Accumulator]) {
+        let n = self.states.len();
+        if self.accumulators.len() < n {
+            self.accumulators.resize(n, nnue::Accumulator::new());
+        }
+        (&mut self.states, &mut self.accumulators[..n])
+    }
+
     fn st(&self) -> &StateInfo {
         self.states.last().unwrap()
     }
@@ -457,67 +574,106 @@ impl Position {
             self.checkers()
         );
 
-        if tb::max_cardinality() >= popcount(self.pieces())
+        let tables = self.tb_config.tables.clone();
+        if tables.max_cardinality() >= popcount(self.pieces())
             && !self.has_castling_right(ANY_CASTLING)
         {
             let mut s1 = 1;
             let mut s2 = 1;
-            let wdl = tb::probe_wdl(self, &mut s1);
-            let dtz = tb::probe_dtz(self, &mut s2);
+            let wdl = tb::probe_wdl(&tables, self, &mut s1);
+            let dtz = tb::probe_dtz(&tables, self, &mut s2);
             println!(
                 "Tablebases WDL: {} ({})\nTablebases DTZ: {} ({})",
                 wdl, s1, dtz, s2
             );
             if s1 != 0 {
-                let dtm = tb::probe_dtm(self, wdl, &mut s1);
+                let dtm = tb::probe_dtm(&tables, self, wdl, &mut s1);
                 println!("Tablebases DTM: {} ({})", uci::value(dtm), s1);
             }
         }
     }
 
-    // set() initializes the position objection with the given FEN string.
-    // This function is not very robust - make sure that input FENs are
-    // correct. This is assumed to be the responsibility of the GUI.
+    // from_fen() returns a new position set up from the given FEN string,
+    // or an error describing why the FEN string is invalid.
 
-    pub fn set(&mut self, fen_str: &str, is_chess960: bool) {
-        for c in 0..2 {
-            self.by_color_bb[c] = Bitboard(0);
+    pub fn from_fen(fen_str: &str, is_chess960: bool) -> Result<Position, FenError> {
+        let mut pos = Position::new();
+        pos.init_states();
+        pos.set(fen_str, is_chess960)?;
+        Ok(pos)
+    }
+
+    // set() initializes the position object with the given FEN string. The
+    // FEN string is checked for consistency, but not for reachability. If
+    // an error is returned, the position is left in an undefined state.
+
+    pub fn set(&mut self, fen_str: &str, is_chess960: bool) -> Result<(), FenError> {
+        self.clear();
+
+        let fields: Vec<&str> = fen_str.split_whitespace().collect();
+        if fields.len() < 4 || fields.len() > 6 {
+            return Err(FenError::FieldCount(fields.len()));
         }
-        for t in 0..8 {
-            self.by_type_bb[t] = Bitboard(0);
+
+        // 1. Piece placement
+        let ranks: Vec<&str> = fields[0].split('/').collect();
+        if ranks.len() != 8 {
+            return Err(FenError::RankCount(ranks.len()));
         }
-        for i in 0..16 {
-            self.piece_count[i] = 0;
-            self.castling_path[i] = Bitboard(0);
-            self.castling_rook_square[i] = Square::NONE;
-            for j in 0..16 {
-                self.piece_list[i][j] = Square::NONE;
+        for (i, rank_str) in ranks.iter().enumerate() {
+            let r = 7 - i as Rank;
+            let mut f = 0;
+            for c in rank_str.chars() {
+                if let Some(d) = c.to_digit(10) {
+                    if d == 0 {
+                        return Err(FenError::BadPiece(c));
+                    }
+                    f += d; // Advance the given number of files
+                } else if let Some(idx) = Position::PIECE_TO_CHAR.find(c) {
+                    let pc = Piece(idx as u32);
+                    if f >= 8 {
+                        return Err(FenError::RankOverflow(r + 1));
+                    }
+                    if self.count(pc.color(), ALL_PIECES) == 16 {
+                        return Err(FenError::TooManyPieces(pc.color()));
+                    }
+                    self.put_piece(pc, Square::make(f, r));
+                    f += 1;
+                } else {
+                    return Err(FenError::BadPiece(c));
+                }
+                if f > 8 {
+                    return Err(FenError::RankOverflow(r + 1));
+                }
+            }
+            if f < 8 {
+                return Err(FenError::RankUnderflow(r + 1));
             }
-        }
-        for i in 0..64 {
-            self.board[i] = NO_PIECE;
-            self.castling_rights_mask[i] = CastlingRight(0);
         }
 
-        let mut iter = fen_str.split_whitespace();
-
-        // 1. Piece placement
-        let pieces = iter.next().unwrap();
-        let mut sq = Square::A8;
-        for c in pieces.chars() {
-            if let Some(d) = c.to_digit(10) {
-                sq += (d as i32) * EAST; // Advance the given number of files
-            } else if c == '/' {
-                sq += 2 * SOUTH;
-            } else if let Some(idx) = Position::PIECE_TO_CHAR.find(c) {
-                self.put_piece(Piece(idx as u32), sq);
-                sq += EAST;
+        for &c in [WHITE, BLACK].iter() {
+            if self.count(c, KING) != 1 {
+                return Err(FenError::KingCount(c, self.count(c, KING)));
+            }
+            if self.count(c, PAWN) > 8 {
+                return Err(FenError::TooManyPieces(c));
             }
         }
+        if self.pieces_p(PAWN) & (RANK1_BB | RANK8_BB) != 0 {
+            return Err(FenError::PawnOnBackRank);
+        }
 
         // 2. Active color
-        let color = iter.next().unwrap();
-        self.side_to_move = if color == "b" { BLACK } else { WHITE };
+        self.side_to_move = match fields[1] {
+            "w" => WHITE,
+            "b" => BLACK,
+            _ => return Err(FenError::BadSideToMove(String::from(fields[1]))),
+        };
+
+        let us = self.side_to_move;
+        if self.attackers_to(self.square(!us, KING)) & self.pieces_c(us) != 0 {
+            return Err(FenError::OpponentInCheck);
+        }
 
         // 3. Castling availability. Compatible with 3 standards: Normal FEN
         // standard, Shredder-FEN that uses the letters of the columns on
@@ -525,65 +681,87 @@ impl Position {
         // standard that, in case of Chess960, if an inner rook is associated
         // with the castling right, the castling tag is replaced by the file
         // letter of the involved rook, as for the Shredder-FEN.
-        let castling = iter.next().unwrap();
+        let castling = fields[2];
         if castling != "-" {
             for c in castling.chars() {
                 let color = if c.is_lowercase() { BLACK } else { WHITE };
                 let rook = Piece::make(color, ROOK);
-                let side = c.to_uppercase().next().unwrap();
+                let ksq = self.square(color, KING);
+                let side = c.to_ascii_uppercase();
+                if ksq.relative_rank(color) != RANK_1 {
+                    return Err(FenError::CastlingWithoutRook(c));
+                }
                 let mut rsq;
                 if side == 'K' {
                     rsq = Square::H1.relative(color);
-                    while self.piece_on(rsq) != rook {
+                    while rsq > ksq && self.piece_on(rsq) != rook {
                         rsq += WEST;
                     }
                 } else if side == 'Q' {
                     rsq = Square::A1.relative(color);
-                    while self.piece_on(rsq) != rook {
+                    while rsq < ksq && self.piece_on(rsq) != rook {
                         rsq += EAST;
                     }
                 } else if side >= 'A' && side <= 'H' {
-                    let file = side.to_digit(18).unwrap() - 10;
+                    let file = side as u32 - 'A' as u32;
                     rsq = Square::make(file, relative_rank(color, RANK_1));
                 } else {
-                    continue;
+                    return Err(FenError::BadCastling(c));
+                }
+                if self.piece_on(rsq) != rook {
+                    return Err(FenError::CastlingWithoutRook(c));
                 }
                 self.set_castling_right(color, rsq);
             }
         }
 
         // 4. En passant square. Ignore if no pawn capture is possible
-        let enpassant = iter.next().unwrap();
+        let enpassant = fields[3];
         self.st_mut().ep_square = Square::NONE;
         if enpassant != "-" {
-            let file = enpassant.chars().nth(0).unwrap();
-            let file = file.to_digit(18).unwrap() - 10;
-            let rank = if self.side_to_move == WHITE { 5 } else { 2 };
-            let ep_sq = Square::make(file, rank);
-            if self.attackers_to(ep_sq) & self.pieces_cp(self.side_to_move, PAWN) != 0
-                && self.pieces_cp(!self.side_to_move, PAWN)
-                    & (ep_sq + pawn_push(!self.side_to_move))
-                    != 0
+            let bad_ep = || FenError::BadEpSquare(String::from(enpassant));
+            let chars: Vec<char> = enpassant.chars().collect();
+            if chars.len() != 2
+                || chars[0] < 'a'
+                || chars[0] > 'h'
+                || chars[1] != if us == WHITE { '6' } else { '3' }
+            {
+                return Err(bad_ep());
+            }
+            let file = chars[0] as u32 - 'a' as u32;
+            let ep_sq = Square::make(file, relative_rank(us, RANK_6));
+
+            // The square must have been passed by a double pawn push of the
+            // side that just moved
+            if !self.empty(ep_sq)
+                || !self.empty(ep_sq + pawn_push(us))
+                || self.piece_on(ep_sq + pawn_push(!us)) != Piece::make(!us, PAWN)
             {
+                return Err(bad_ep());
+            }
+            if self.attackers_to(ep_sq) & self.pieces_cp(us, PAWN) != 0 {
                 self.st_mut().ep_square = ep_sq;
             }
         }
 
         // 5-6. Halfmove clock and fullmove number
-        if let Some(halfmove) = iter.next() {
-            self.st_mut().rule50 = halfmove.parse().unwrap();
-        } else {
-            self.st_mut().rule50 = 0;
-        }
+        self.st_mut().rule50 = match fields.get(4) {
+            Some(halfmove) => match halfmove.parse() {
+                Ok(n) if n >= 0 => n,
+                _ => return Err(FenError::BadMoveCounter(String::from(*halfmove))),
+            },
+            None => 0,
+        };
 
         // Convert from fullmove starting from 1 to game_ply starting from 0.
         // Handle also common incorrect FEN with fullmove = 0.
-        if let Some(fullmove) = iter.next() {
-            let fullmove = fullmove.parse::<i32>().unwrap();
-            self.game_ply = std::cmp::max(2 * (fullmove - 1), 0);
-        } else {
-            self.game_ply = 0;
-        }
+        self.game_ply = match fields.get(5) {
+            Some(fullmove) => match fullmove.parse::<i32>() {
+                Ok(n) if n >= 0 => std::cmp::max(2 * (n - 1), 0),
+                _ => return Err(FenError::BadMoveCounter(String::from(*fullmove))),
+            },
+            None => 0,
+        };
         if self.side_to_move == BLACK {
             self.game_ply += 1;
         }
@@ -592,6 +770,72 @@ impl Position {
         self.set_state();
 
         debug_assert!(self.is_ok());
+
+        Ok(())
+    }
+
+    // clear() removes all pieces and castling rights
+
+    fn clear(&mut self) {
+        for c in 0..2 {
+            self.by_color_bb[c] = Bitboard(0);
+        }
+        for t in 0..8 {
+            self.by_type_bb[t] = Bitboard(0);
+        }
+        for i in 0..16 {
+            self.piece_count[i] = 0;
+            self.castling_path[i] = Bitboard(0);
+            self.castling_rook_square[i] = Square::NONE;
+            for j in 0..16 {
+                self.piece_list[i][j] = Square::NONE;
+            }
+        }
+        for i in 0..64 {
+            self.board[i] = NO_PIECE;
+            self.castling_rights_mask[i] = CastlingRight(0);
+        }
+        self.st_mut().castling_rights = CastlingRight(0);
+    }
+
+    // set_pieces() sets up the position with the given pieces, side to move
+    // and rooks that can castle, without en passant square and with the
+    // move counters at zero. Unlike set(), the position is not checked.
+
+    pub fn set_pieces(
+        &mut self,
+        pieces: &[(Square, Piece)],
+        stm: Color,
+        castling_rooks: &[Square],
+        is_chess960: bool,
+    ) {
+        self.clear();
+        for &(s, pc) in pieces.iter() {
+            self.put_piece(pc, s);
+        }
+        self.side_to_move = stm;
+        for &rsq in castling_rooks.iter() {
+            self.set_castling_right(self.piece_on(rsq).color(), rsq);
+        }
+        self.st_mut().ep_square = Square::NONE;
+        self.st_mut().rule50 = 0;
+        self.game_ply = (stm == BLACK) as i32;
+        self.chess960 = is_chess960;
+        self.set_state();
+
+        debug_assert!(self.is_ok());
+    }
+
+    // clear_tables() discards the entries of the pawn and material hash
+    // tables without reallocating them
+
+    pub fn clear_tables(&mut self) {
+        for e in self.pawns_table.iter_mut() {
+            *e.get_mut() = pawns::Entry::new();
+        }
+        for e in self.material_table.iter_mut() {
+            *e.get_mut() = material::Entry::new();
+        }
     }
 
     // set_castling_right() is a helper function used to set castling rights
@@ -674,6 +918,8 @@ impl Position {
     // the correctness of the StateInfo data when running in debug mode.
 
     fn set_state(&mut self) {
+        self.st_mut().dirty_piece = None;
+        self.st_mut().accumulator_computed = [false; 2];
         self.st_mut().key = Key(0);
         self.st_mut().material_key = Key(0);
         self.st_mut().pawn_key = zobrist::no_pawns();
@@ -1067,6 +1313,7 @@ impl Position {
         let from = m.from();
         let mut to = m.to();
         let pc = self.piece_on(from);
+        let mut dp = nnue::DirtyPiece::new();
         let mut captured = if m.move_type() == ENPASSANT {
             Piece::make(them, PAWN)
         } else {
@@ -1079,6 +1326,11 @@ impl Position {
                 || captured.color() == if m.move_type() != CASTLING { them } else { us }
         );
 
+        // The moving piece is recorded first, see nnue::DirtyPiece
+        if m.move_type() != CASTLING {
+            dp.add(pc, from, to);
+        }
+
         if m.move_type() == CASTLING {
             debug_assert!(pc == Piece::make(us, KING));
             debug_assert!(captured == Piece::make(us, ROOK));
@@ -1086,6 +1338,8 @@ impl Position {
             let mut rfrom = Square::A1;
             let mut rto = Square::A1;
             self.do_castling::<True>(us, from, &mut to, &mut rfrom, &mut rto);
+            dp.add(pc, from, to);
+            dp.add(captured, rfrom, rto);
 
             self.st_mut().psq += psqt::psq(captured, rto) - psqt::psq(captured, rfrom);
             k ^= zobrist::psq(captured, rfrom) ^ zobrist::psq(captured, rto);
@@ -1117,6 +1371,7 @@ impl Position {
 
             // Update board and piece lists
             self.remove_piece(captured, capsq);
+            dp.add(captured, capsq, Square::NONE);
 
             // Update material hash key and prefetch access to material_table
             k ^= zobrist::psq(captured, capsq);
@@ -1174,6 +1429,8 @@ impl Position {
 
                 self.remove_piece(pc, to);
                 self.put_piece(promotion, to);
+                dp.to[0] = Square::NONE;
+                dp.add(promotion, Square::NONE, to);
 
                 // Update hash keys
                 k ^= zobrist::psq(pc, to) ^ zobrist::psq(promotion, to);
@@ -1205,6 +1462,7 @@ impl Position {
 
         // Set captured piece
         self.st_mut().captured_piece = captured;
+        self.st_mut().dirty_piece = Some(dp);
 
         // Update the key with the final value
         self.st_mut().key = k;
@@ -1315,6 +1573,8 @@ impl Position {
 
         let st_copy = (*self.st()).clone(); // full copy
         self.states.push(st_copy);
+        self.st_mut().dirty_piece = Some(nnue::DirtyPiece::new());
+        self.st_mut().accumulator_computed = [false; 2];
 
         if self.st().ep_square != Square::NONE {
             let tmp = zobrist::enpassant(self.st().ep_square.file());
@@ -1631,3 +1891,50 @@ impl Position {
         true
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use engine::Engine;
+
+    #[test]
+    fn fen_errors() {
+        Engine::new();
+
+        let cases = [
+            ("4k3/8/8/8/8/8/8/4K3 w", FenError::FieldCount(2)),
+            ("4k3/8/8/8/8/8/8/4K3 w - - 0 1 x", FenError::FieldCount(7)),
+            ("4k3/8/8/8/8/8/4K3 w - - 0 1", FenError::RankCount(7)),
+            ("4k3/8/8/8/8p/8/8/4K3 w - - 0 1", FenError::RankOverflow(4)),
+            ("4k3/8/8/8/8/8/8/4K3p w - - 0 1", FenError::RankOverflow(1)),
+            ("4k3/8/8/9/8/8/8/4K3 w - - 0 1", FenError::RankOverflow(5)),
+            ("4k3/8/8/8/8/8/8/4K2 w - - 0 1", FenError::RankUnderflow(1)),
+            ("4k3/8/8/8/8/8/8/4Kx2 w - - 0 1", FenError::BadPiece('x')),
+            ("4k3/8/8/8/08/8/8/4K3 w - - 0 1", FenError::BadPiece('0')),
+            ("4k3/8/8/8/8/P7/PPPPPPPP/4K3 w - - 0 1", FenError::TooManyPieces(WHITE)),
+            ("4k3/8/8/NNNNNNNN/NNNNNNNN/8/8/4K3 w - - 0 1", FenError::TooManyPieces(WHITE)),
+            ("4k3/8/8/8/8/8/8/8 w - - 0 1", FenError::KingCount(WHITE, 0)),
+            ("4k3/8/8/8/8/8/8/3KK3 w - - 0 1", FenError::KingCount(WHITE, 2)),
+            ("8/8/8/8/8/8/8/4K3 w - - 0 1", FenError::KingCount(BLACK, 0)),
+            ("4k2P/8/8/8/8/8/8/4K3 w - - 0 1", FenError::PawnOnBackRank),
+            ("4k3/8/8/8/8/8/8/p3K3 b - - 0 1", FenError::PawnOnBackRank),
+            ("4k3/8/8/8/8/8/8/4K3 x - - 0 1", FenError::BadSideToMove(String::from("x"))),
+            ("4k3/8/8/8/8/8/8/4R1K1 w - - 0 1", FenError::OpponentInCheck),
+            ("4k3/8/8/8/8/8/8/4K2R w Z - 0 1", FenError::BadCastling('Z')),
+            ("4k3/8/8/8/8/8/8/4K3 w K - 0 1", FenError::CastlingWithoutRook('K')),
+            ("4k3/8/8/8/8/8/4K3/7R w K - 0 1", FenError::CastlingWithoutRook('K')),
+            ("4k3/8/8/8/8/8/8/4K3 w - e3 0 1", FenError::BadEpSquare(String::from("e3"))),
+            ("4k3/8/8/8/8/8/8/4K3 w - e6 0 1", FenError::BadEpSquare(String::from("e6"))),
+            ("4k3/8/8/8/8/8/8/4K3 w - e 0 1", FenError::BadEpSquare(String::from("e"))),
+            ("4k3/8/8/8/8/8/8/4K3 w - - -1 1", FenError::BadMoveCounter(String::from("-1"))),
+            ("4k3/8/8/8/8/8/8/4K3 w - - 0 x", FenError::BadMoveCounter(String::from("x"))),
+        ];
+
+        for (fen, expected) in cases.iter() {
+            assert_eq!(Position::from_fen(fen, false).err().as_ref(), Some(expected), "{}", fen);
+        }
+
+        let pos = Position::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", false).unwrap();
+        assert!(pos.ep_square() == Square::D6);
+    }
+}
@@ -1,23 +1,24 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 use std;
-use std::io::stdout;
-use std::io::Write;
 use std::time::Instant;
 
 use bitboard::*;
 use evaluate;
 use evaluate::evaluate;
+use listener::*;
+use misc;
 use movegen::*;
 use movepick::*;
+use perft;
 use position::*;
 use tb;
 use threads;
-use timeman;
-use tt;
+use threads::SearchOutcome;
+use threads::ThreadPool;
 use types::*;
 use uci;
-use ucioption;
+use ucioption::OptionsMap;
 
 pub const CM_THRESHOLD: i32 = 0;
 
@@ -82,7 +83,7 @@ impl PartialEq for RootMove {
 
 pub type RootMoves = Vec<RootMove>;
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct LimitsType {
     pub time: [i64; 2],
     pub inc: [i64; 2],
@@ -91,6 +92,10 @@ pub struct LimitsType {
     pub movetime: i64,
     pub mate: u32,
     pub perft: u32,
+    // Whether perft reports the count of each root move, and the size of
+    // its hash table in MB, 0 for none
+    pub divide: bool,
+    pub perft_hash: usize,
     pub infinite: bool,
     pub nodes: u64,
     pub start_time: Option<Instant>,
@@ -106,6 +111,8 @@ impl LimitsType {
             movetime: 0,
             mate: 0,
             perft: 0,
+            divide: false,
+            perft_hash: 0,
             infinite: false,
             nodes: 0,
             start_time: Some(Instant::now()),
@@ -122,21 +129,79 @@ impl LimitsType {
     }
 }
 
-pub static mut LIMITS: LimitsType = LimitsType {
-    time: [0; 2],
-    inc: [0; 2],
-    movestogo: 0,
-    depth: 0,
-    movetime: 0,
-    mate: 0,
-    perft: 0,
-    infinite: false,
-    nodes: 0,
-    start_time: None,
-};
-
-pub fn limits() -> &'static mut LimitsType {
-    unsafe { &mut LIMITS }
+// Skill handles the weakening of the engine. With a level below 20 the
+// search runs in MultiPV mode with at least 4 lines, and when the depth
+// reaches 1 + level a move is picked among them by pick_best(). Lower
+// levels pick weaker moves more often.
+
+struct Skill {
+    level: f64,
+    best: Move,
+    rng: misc::Prng,
+}
+
+impl Skill {
+    // new() reads the level from the options. With UCI_LimitStrength the
+    // level is derived from UCI_Elo, see elo_to_level().
+
+    fn new(options: &OptionsMap) -> Skill {
+        let level = if options.get_bool("UCI_LimitStrength") {
+            elo_to_level(options.get_i32("UCI_Elo"))
+        } else {
+            options.get_i32("Skill Level") as f64
+        };
+
+        Skill {
+            level,
+            best: Move::NONE,
+            rng: misc::Prng::new(misc::time_seed()),
+        }
+    }
+
+    fn enabled(&self) -> bool {
+        self.level < 20.0
+    }
+
+    fn time_to_pick(&self, depth: Depth) -> bool {
+        depth / ONE_PLY == 1 + self.level as i32
+    }
+
+    // pick_best() chooses a move among the first multi_pv root moves, which
+    // are sorted by score. Each score gets a bonus made of a deterministic
+    // part, bigger for weaker levels, and a random part, and the move with
+    // the highest result is chosen.
+
+    fn pick_best(&mut self, root_moves: &RootMoves, multi_pv: usize) -> Move {
+        let top_score = root_moves[0].score;
+        let delta = std::cmp::min(top_score - root_moves[multi_pv - 1].score, PawnValueMg);
+        let weakness = 120.0 - 2.0 * self.level;
+        let mut max_score = -Value::INFINITE;
+
+        for rm in root_moves[..multi_pv].iter() {
+            // This is our magic formula
+            let random = (self.rng.rand64() % weakness as u64) as f64;
+            let push = (weakness * (top_score - rm.score).0 as f64 + delta.0 as f64 * random)
+                / 128.0;
+
+            if rm.score + push as i32 >= max_score {
+                max_score = rm.score + push as i32;
+                self.best = rm.pv[0];
+            }
+        }
+
+        self.best
+    }
+}
+
+// elo_to_level() maps UCI_Elo to a (fractional) skill level. The mapping
+// was fitted by Stockfish to the CCRL 40/4 ratings of its skill levels, so
+// for this engine the Elo values are only approximate. The level grows
+// with the Elo: 1350 gives level 0, 2000 about 6.6 and 2850 about 18.5, so
+// even the highest setting is still weakened.
+
+fn elo_to_level(elo: i32) -> f64 {
+    let level = ((elo as f64 - 1346.6) / 143.4).powf(1.0 / 0.806);
+    level.max(0.0).min(20.0)
 }
 
 // Different node types
@@ -162,21 +227,26 @@ impl NodeType for Pv {
 const SKIP_SIZE: [i32; 20] = [1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 3, 3, 4, 4, 4, 4, 4, 4, 4, 4];
 const SKIP_PHASE: [i32; 20] = [0, 1, 0, 1, 2, 3, 0, 1, 2, 3, 4, 5, 0, 1, 2, 3, 4, 5, 6, 7];
 
-fn futility_margin(d: Depth, improving: bool) -> Value {
-    Value((175 - 50 * improving as i32) * d / ONE_PLY)
-}
+tunable! {
+    pub FUTILITY_MARGIN: [i32; 2] = [175, 50];
 
-const RAZOR_MARGIN: [i32; 3] = [0, 590, 604];
+    pub RAZOR_MARGIN: [i32; 3] = [0, 590, 604];
 
-const CAPTURE_PRUNE_MARGIN: [i32; 7] = [
-    0,
-    1 * PawnValueEg.0 * 1055 / 1000,
-    2 * PawnValueEg.0 * 1042 / 1000,
-    3 * PawnValueEg.0 * 963 / 1000,
-    4 * PawnValueEg.0 * 1038 / 1000,
-    5 * PawnValueEg.0 * 950 / 1000,
-    6 * PawnValueEg.0 * 930 / 1000,
-];
+    pub CAPTURE_PRUNE_MARGIN: [i32; 7] = [
+        0,
+        1 * PawnValueEg.0 * 1055 / 1000,
+        2 * PawnValueEg.0 * 1042 / 1000,
+        3 * PawnValueEg.0 * 963 / 1000,
+        4 * PawnValueEg.0 * 1038 / 1000,
+        5 * PawnValueEg.0 * 950 / 1000,
+        6 * PawnValueEg.0 * 930 / 1000,
+    ];
+}
+
+fn futility_margin(d: Depth, improving: bool) -> Value {
+    let margin = param!(FUTILITY_MARGIN);
+    Value((margin[0] - margin[1] * improving as i32) * d / ONE_PLY)
+}
 
 // Futility and reductions lookup tables, initialized at startup
 static mut FUTILITY_MOVE_COUNTS: [[i32; 16]; 2] = [[0; 16]; 2];
@@ -204,36 +274,6 @@ fn stat_bonus(depth: Depth) -> i32 {
     }
 }
 
-// perft() is our utility to verify move generation. All the leaf nodes up
-// to the given depth are generated and counted, and the sum is returned.
-fn perft<Root: Bool>(pos: &mut Position, depth: Depth) -> u64 {
-    let mut nodes = 0u64;
-    let leaf = depth == 2 * ONE_PLY;
-
-    for m in MoveList::new::<Legal>(pos) {
-        let cnt;
-        if Root::BOOL && depth <= ONE_PLY {
-            cnt = 1;
-            nodes += 1;
-        } else {
-            let checks = pos.gives_check(m);
-            pos.do_move(m, checks);
-            cnt = if leaf {
-                MoveList::new::<Legal>(pos).len() as u64
-            } else {
-                perft::<False>(pos, depth - ONE_PLY)
-            };
-            nodes += cnt;
-            pos.undo_move(m);
-        }
-        if Root::BOOL {
-            println!("{}: {}", uci::move_str(m, pos.is_chess960()), cnt);
-            stdout().flush().unwrap();
-        }
-    }
-    nodes
-}
-
 // search::init() is called during startup to initialize various lookup tables
 
 pub fn init() {
@@ -264,65 +304,96 @@ pub fn init() {
 
 // search::clear() resets search state to its initial value
 
-pub fn clear() {
-    threads::wait_for_all();
-    tt::clear();
-    threads::clear_search();
-    threads::wait_for_all();
+pub fn clear(pool: &ThreadPool) {
+    pool.wait_for_all();
+    pool.shared.tt.clear();
+    pool.clear_search();
+    pool.wait_for_all();
 }
 
 // mainthread_search() is called by the main thread when the program
-// received the UCI 'go' command. It searches from the root position and
-// outputs the "bestmove".
+// received the UCI 'go' command. It searches from the root position,
+// outputs the "bestmove" and hands the outcome to the SearchHandle.
 
 pub fn mainthread_search(pos: &mut Position, th: &threads::ThreadCtrl) {
-    if limits().perft != 0 {
-        let nodes = perft::<True>(pos, (limits().perft as i32) * ONE_PLY);
-        println!("\nNodes searched: {}", nodes);
+    if pos.limits.perft != 0 {
+        let mut table = match pos.limits.perft_hash {
+            0 => None,
+            mb => Some(perft::PerftTable::new(mb)),
+        };
+        let divide = pos.limits.divide;
+        let nodes = perft::divide(pos, pos.limits.perft as i32, table.as_mut(), |pos, m, cnt| {
+            if divide {
+                pos.shared().listener().on_perft(pos, m, cnt);
+            }
+        });
+        pos.shared().listener().on_perft_done(nodes);
+        th.common.lock().unwrap().outcome.set(SearchOutcome {
+            best: Move::NONE,
+            ponder: None,
+            score: Value::NONE,
+            depth: pos.limits.perft as i32,
+            pv: Vec::new(),
+            nodes,
+        });
         return;
     }
 
     let us = pos.side_to_move();
-    timeman::init(limits(), us, pos.game_ply());
-    tt::new_search();
+    {
+        let options = th.shared.options.read().unwrap();
+        th.shared.time.init(&options, &pos.limits, us, pos.game_ply());
+    }
+    pos.shared().tt.new_search();
 
     if pos.root_moves.is_empty() {
         pos.root_moves.push(RootMove::new(Move::NONE));
-        println!(
-            "info depth 0 score {}",
-            uci::value(if pos.checkers() != 0 {
+        let line = PvLine {
+            multipv: 1,
+            depth: 0,
+            sel_depth: 0,
+            score: if pos.checkers() != 0 {
                 -Value::MATE
             } else {
                 Value::DRAW
-            })
-        );
-        stdout().flush().unwrap();
+            },
+            bound: ScoreBound::Exact,
+            wdl: None,
+            pv: Vec::new(),
+        };
+        pos.root_moves[0].score = line.score;
+        pos.completed_depth = Depth::ZERO;
+        let stats = search_stats(pos, pos.shared().time.elapsed());
+        pos.shared().listener().on_iteration(pos, 0, &[line], &stats);
     } else {
-        threads::wake_up_slaves();
+        pos.shared().wake_up_slaves();
 
         thread_search(pos, th); // Let's start searching!
     }
 
     // When we reach the maximum depth, we can arrive here without
-    // threads::stop() having been raised. However, if we are pondering or
+    // pos.shared().stop() having been raised. However, if we are pondering or
     // if we are in an infinite search, the UCI protocol states that we
     // shouldn't print the best move before the GUI sends a "stop" or
     // "ponderhit" command. We therefore simply wait here until the GUI sends
-    // one of those commands (which also raised threads::stop()).
-    threads::set_stop_on_ponderhit(true);
+    // one of those commands (which also raised pos.shared().stop()).
+    pos.shared().set_stop_on_ponderhit(true);
 
-    while !threads::stop() && (threads::ponder() || limits().infinite) {} // Busy wait for a stop or a ponder reset
+    while !pos.shared().stop() && (pos.shared().ponder() || pos.limits.infinite) {} // Busy wait for a stop or a ponder reset
 
     // Stop the threads if not already stopped (also raise the stop if
-    // "ponderhit" has just reset threads::ponder()).
-    threads::set_stop(true);
+    // "ponderhit" has just reset pos.shared().ponder()).
+    pos.shared().set_stop(true);
 
     // Wait until all threads have finished
-    threads::wait_for_slaves();
+    pos.shared().wait_for_slaves();
 
     // Check if there are threads with a better score than main thread
-    if ucioption::get_i32("MultiPV") == 1
-        && limits().depth == 0
+    let mut depth = pos.completed_depth;
+    let options = th.shared.options.read().unwrap();
+    if options.get_i32("MultiPV") == 1
+        && pos.limits.depth == 0
+        && !Skill::new(&options).enabled()
         && pos.root_moves[0].pv[0] != Move::NONE
     {
         let common = th.common.lock().unwrap();
@@ -332,25 +403,36 @@ pub fn mainthread_search(pos: &mut Position, th: &threads::ThreadCtrl) {
         {
             pos.root_moves[0].score = result.score;
             pos.root_moves[0].pv = result.pv.clone();
+            depth = result.depth;
         }
     }
+    drop(options);
 
     pos.previous_score = pos.root_moves[0].score;
 
-    print!(
-        "bestmove {}",
-        uci::move_str(pos.root_moves[0].pv[0], pos.is_chess960())
-    );
-
-    if pos.root_moves[0].pv.len() > 1 || extract_ponder_from_tt(pos) {
-        print!(
-            " ponder {}",
-            uci::move_str(pos.root_moves[0].pv[1], pos.is_chess960())
-        );
-    }
+    let ponder = if pos.root_moves[0].pv.len() > 1 || extract_ponder_from_tt(pos) {
+        Some(pos.root_moves[0].pv[1])
+    } else {
+        None
+    };
 
-    print!("\n");
-    stdout().flush().unwrap();
+    pos.shared()
+        .listener()
+        .on_bestmove(pos, pos.root_moves[0].pv[0], ponder);
+
+    let best = pos.root_moves[0].pv[0];
+    th.common.lock().unwrap().outcome.set(SearchOutcome {
+        best,
+        ponder,
+        score: pos.root_moves[0].score,
+        depth: depth / ONE_PLY,
+        pv: if best != Move::NONE {
+            pos.root_moves[0].pv.clone()
+        } else {
+            Vec::new()
+        },
+        nodes: pos.shared().nodes_searched(),
+    });
 }
 
 // thread_search() is the main iterative deepening loop. It calls search()
@@ -358,7 +440,7 @@ pub fn mainthread_search(pos: &mut Position, th: &threads::ThreadCtrl) {
 // been consumed, the user stops the search, or the maximum search depth
 // is reached.
 
-pub fn thread_search(pos: &mut Position, _th: &threads::ThreadCtrl) {
+pub fn thread_search(pos: &mut Position, th: &threads::ThreadCtrl) {
     let mut stack: Vec<Stack> = Vec::with_capacity((MAX_PLY + 7) as usize);
 
     let mut last_best_move = Move::NONE;
@@ -382,6 +464,7 @@ pub fn thread_search(pos: &mut Position, _th: &threads::ThreadCtrl) {
     }
 
     pos.calls_cnt = 0;
+    pos.last_info_time = 0;
     pos.nmp_ply = 0;
     pos.nmp_odd = 0;
 
@@ -397,14 +480,23 @@ pub fn thread_search(pos: &mut Position, _th: &threads::ThreadCtrl) {
 
     let us = pos.side_to_move();
 
-    let mut multi_pv = ucioption::get_i32("MultiPV") as usize;
+    let options = th.shared.options.read().unwrap();
+
+    let mut multi_pv = options.get_i32("MultiPV") as usize;
+    let mut skill = Skill::new(&options);
+
+    // When playing with strength handicap enable MultiPV search that we will
+    // use behind the scenes to retrieve a set of possible moves.
+    if skill.enabled() {
+        multi_pv = std::cmp::max(multi_pv, 4);
+    }
     multi_pv = std::cmp::min(multi_pv, pos.root_moves.len());
 
-    let mut base_ct = ucioption::get_i32("Contempt") * PawnValueEg.0 / 100; // From centipawns
+    let mut base_ct = options.get_i32("Contempt") * PawnValueEg.0 / 100; // From centipawns
 
     // In analysis mode, adjust contempt in accordance with user preference
-    if limits().infinite || ucioption::get_bool("UCI_AnalyseMode") {
-        base_ct = match ucioption::get_string("Analysis Contempt").as_ref() {
+    if pos.limits.infinite || options.get_bool("UCI_AnalyseMode") {
+        base_ct = match options.get_string("Analysis Contempt").as_ref() {
             "off" => 0,
             "white" => {
                 if us == WHITE {
@@ -424,24 +516,20 @@ pub fn thread_search(pos: &mut Position, _th: &threads::ThreadCtrl) {
         }
     }
 
-    unsafe {
-        let contempt = Score::make(base_ct, base_ct / 2);
-        if let Some(th) = &pos.thread_ctrl {
-            th.contempt
-                .set(if us == WHITE { contempt } else { -contempt });
-        } else {
-            evaluate::CONTEMPT = if us == WHITE { contempt } else { -contempt };
-        }
-    }
+    drop(options);
+
+    let contempt = Score::make(base_ct, base_ct / 2);
+    th.contempt
+        .set(if us == WHITE { contempt } else { -contempt });
 
     let mut root_depth = Depth::ZERO;
 
     // Iterative deepening loop until requested to stop or the target depth
     // is reached
-    while !threads::stop() {
+    while !pos.shared().stop() {
         root_depth += ONE_PLY;
         if root_depth >= Depth::MAX
-            || (limits().depth != 0 && pos.is_main && root_depth / ONE_PLY > limits().depth as i32)
+            || (pos.limits.depth != 0 && pos.is_main && root_depth / ONE_PLY > pos.limits.depth as i32)
         {
             break;
         }
@@ -472,7 +560,7 @@ pub fn thread_search(pos: &mut Position, _th: &threads::ThreadCtrl) {
 
         // MultiPV loop. We perform a full root search for each PV line.
         pos.pv_idx = 0;
-        while pos.pv_idx < multi_pv && !threads::stop() {
+        while pos.pv_idx < multi_pv && !pos.shared().stop() {
             if pos.pv_idx == pos.pv_last {
                 pv_first = pos.pv_last;
                 pos.pv_last += 1;
@@ -492,9 +580,9 @@ pub fn thread_search(pos: &mut Position, _th: &threads::ThreadCtrl) {
                 best_value = pos.root_moves[pos.pv_idx].tb_score;
                 pos.root_moves[pos.pv_idx].score = best_value;
                 if pos.is_main
-                    && (threads::stop() || pos.pv_idx + 1 == multi_pv || timeman::elapsed() > 3000)
+                    && (pos.shared().stop() || pos.pv_idx + 1 == multi_pv || pos.shared().time.elapsed() > 3000)
                 {
-                    print_pv(pos, root_depth, -Value::INFINITE, Value::INFINITE);
+                    report_pv(pos, root_depth, -Value::INFINITE, Value::INFINITE);
                 }
                 pos.pv_idx += 1;
                 continue;
@@ -510,11 +598,7 @@ pub fn thread_search(pos: &mut Position, _th: &threads::ThreadCtrl) {
                 // Adjust contempt based on root move's previousScore (dynamic contempt)
                 ct += 88 * previous_score / (previous_score.abs() + 200);
                 let ct = Score::make(ct, ct / 2);
-                if let Some(th) = &pos.thread_ctrl {
-                    th.contempt.set(if us == WHITE { ct } else { -ct });
-                } else {
-                    unsafe { evaluate::CONTEMPT = if us == WHITE { ct } else { -ct } };
-                }
+                th.contempt.set(if us == WHITE { ct } else { -ct });
             }
 
             // Start with a small aspiration window and, in the case of a fail
@@ -536,7 +620,7 @@ pub fn thread_search(pos: &mut Position, _th: &threads::ThreadCtrl) {
                 // If search has been stopped, we break immediately. Sorting
                 // and writing the PV back to TT is safe because root_moves is
                 // still valid, although it refers to the previous iteration.
-                if threads::stop() {
+                if pos.shared().stop() {
                     break;
                 }
 
@@ -545,9 +629,9 @@ pub fn thread_search(pos: &mut Position, _th: &threads::ThreadCtrl) {
                 if pos.is_main
                     && multi_pv == 1
                     && (best_value <= alpha || best_value >= beta)
-                    && timeman::elapsed() > 3000
+                    && pos.shared().time.elapsed() > 3000
                 {
-                    print_pv(pos, root_depth, alpha, beta);
+                    report_pv(pos, root_depth, alpha, beta);
                 }
 
                 // In case of failing low/high increase aspiration window and
@@ -558,7 +642,7 @@ pub fn thread_search(pos: &mut Position, _th: &threads::ThreadCtrl) {
 
                     if pos.is_main {
                         pos.failed_low = true;
-                        threads::set_stop_on_ponderhit(false);
+                        pos.shared().set_stop_on_ponderhit(false);
                     }
                 } else if best_value >= beta {
                     beta = std::cmp::min(best_value + delta, Value::INFINITE);
@@ -575,15 +659,15 @@ pub fn thread_search(pos: &mut Position, _th: &threads::ThreadCtrl) {
             pos.root_moves[pv_first..pos.pv_idx + 1].sort();
 
             if pos.is_main
-                && (threads::stop() || pos.pv_idx + 1 == multi_pv || timeman::elapsed() > 3000)
+                && (pos.shared().stop() || pos.pv_idx + 1 == multi_pv || pos.shared().time.elapsed() > 3000)
             {
-                print_pv(pos, root_depth, alpha, beta);
+                report_pv(pos, root_depth, alpha, beta);
             }
 
             pos.pv_idx += 1;
         }
 
-        if !threads::stop() {
+        if !pos.shared().stop() {
             pos.completed_depth = root_depth;
         }
 
@@ -593,20 +677,26 @@ pub fn thread_search(pos: &mut Position, _th: &threads::ThreadCtrl) {
         }
 
         // Have we found a "mate in x"?
-        if limits().mate != 0
+        if pos.limits.mate != 0
             && best_value >= Value::MATE_IN_MAX_PLY
-            && (Value::MATE - best_value).0 <= 2 * (limits().mate as i32)
+            && (Value::MATE - best_value).0 <= 2 * (pos.limits.mate as i32)
         {
-            threads::set_stop(true);
+            pos.shared().set_stop(true);
         }
 
         if !pos.is_main {
             continue;
         }
 
+        // If skill level is enabled and time is up, pick a sub-optimal best
+        // move
+        if skill.enabled() && skill.time_to_pick(root_depth) {
+            skill.pick_best(&pos.root_moves, multi_pv);
+        }
+
         // Do we have time for the next iteration? Can we stop searching now?
-        if limits().use_time_management() {
-            if !threads::stop() && !threads::stop_on_ponderhit() {
+        if pos.limits.use_time_management() {
+            if !pos.shared().stop() && !pos.shared().stop_on_ponderhit() {
                 // Stop the search if only one legal move is available or
                 // if all of the available time has been used.
                 let f = [pos.failed_low as i32, (best_value - pos.previous_score).0];
@@ -628,8 +718,8 @@ pub fn thread_search(pos: &mut Position, _th: &threads::ThreadCtrl) {
                         pos.previous_time_reduction.powf(0.528) / time_reduction;
 
                     if pos.root_moves.len() == 1
-                        || (timeman::elapsed() as f64)
-                            > (timeman::optimum() as f64)
+                        || (pos.shared().time.elapsed() as f64)
+                            > (pos.shared().time.optimum() as f64)
                                 * best_move_instability
                                 * (improving_factor as f64)
                                 / 581.0
@@ -637,10 +727,10 @@ pub fn thread_search(pos: &mut Position, _th: &threads::ThreadCtrl) {
                         // If we are allowed to ponder do not stop the search
                         // now but keep pondering until the GUI sends
                         // "ponderhit" or "stop".
-                        if threads::ponder() {
-                            threads::set_stop_on_ponderhit(true);
+                        if pos.shared().ponder() {
+                            pos.shared().set_stop_on_ponderhit(true);
                         } else {
-                            threads::set_stop(true);
+                            pos.shared().set_stop(true);
                         }
                     }
                 }
@@ -653,6 +743,19 @@ pub fn thread_search(pos: &mut Position, _th: &threads::ThreadCtrl) {
     }
 
     pos.previous_time_reduction = time_reduction;
+
+    // If skill level is enabled, swap the best PV line with the sub-optimal
+    // one
+    if skill.enabled() {
+        let best = if skill.best != Move::NONE {
+            skill.best
+        } else {
+            skill.pick_best(&pos.root_moves, multi_pv)
+        };
+        if let Some(idx) = pos.root_moves.iter().position(|rm| rm.pv[0] == best) {
+            pos.root_moves.swap(0, idx);
+        }
+    }
 }
 
 // search() is the main search function for both PV and non-PV nodes
@@ -696,7 +799,7 @@ fn search<NT: NodeType>(
     if pos.calls_cnt < 0 {
         pos.calls_cnt = 1023;
         update_counters(pos);
-        check_time();
+        check_time(pos);
     }
 
     // Used to send sel_depth info to GUI
@@ -706,7 +809,7 @@ fn search<NT: NodeType>(
 
     if !root_node {
         // Step 2. Check for aborted search and immediate draw
-        if threads::stop() || pos.is_draw(ss[5].ply) || ss[5].ply >= MAX_PLY {
+        if pos.shared().stop() || pos.is_draw(ss[5].ply) || ss[5].ply >= MAX_PLY {
             return if ss[5].ply >= MAX_PLY && !in_check {
                 evaluate(pos)
             } else {
@@ -743,7 +846,7 @@ fn search<NT: NodeType>(
     // a different position key in case of an excluded move.
     let excluded_move = ss[5].excluded_move;
     let pos_key = pos.key() ^ Key((excluded_move.0 << 16) as u64);
-    let (mut tte, mut tt_hit) = tt::probe(pos_key);
+    let (mut tte, mut tt_hit) = pos.shared().tt.probe(pos_key);
     let tt_value = if tt_hit {
         value_from_tt(tte.value(), ss[5].ply)
     } else {
@@ -799,21 +902,22 @@ fn search<NT: NodeType>(
     }
 
     // Step 5. Tablebase probe
-    if !root_node && tb::cardinality() != 0 {
+    if !root_node && pos.tb_config.cardinality != 0 {
         let pieces_cnt = popcount(pos.pieces());
 
-        if pieces_cnt <= tb::cardinality()
-            && (pieces_cnt < tb::cardinality() || depth >= tb::probe_depth())
+        if pieces_cnt <= pos.tb_config.cardinality
+            && (pieces_cnt < pos.tb_config.cardinality || depth >= pos.tb_config.probe_depth)
             && pos.rule50_count() == 0
             && !pos.has_castling_right(ANY_CASTLING)
         {
             let mut found = 1;
-            let wdl = tb::probe_wdl(pos, &mut found);
+            let tables = pos.tb_config.tables.clone();
+            let wdl = tb::probe_wdl(&tables, pos, &mut found);
 
             if found != 0 {
                 pos.tb_hits += 1;
 
-                let draw_score = if tb::use_rule_50() { 1 } else { 0 };
+                let draw_score = if pos.tb_config.use_rule_50 { 1 } else { 0 };
 
                 let value = if wdl < -draw_score {
                     -Value::MATE + MAX_MATE_PLY + 1 + ss[5].ply
@@ -845,13 +949,13 @@ fn search<NT: NodeType>(
                         std::cmp::min(Depth::MAX - ONE_PLY, depth + 6 * ONE_PLY),
                         Move::NONE,
                         Value::NONE,
-                        tt::generation(),
+                        pos.shared().tt.generation(),
                     );
                     return value;
                 }
 
-                if pieces_cnt <= tb::cardinality_dtm() {
-                    let mut mate = tb::probe_dtm(pos, wdl, &mut found);
+                if pieces_cnt <= pos.tb_config.cardinality_dtm {
+                    let mut mate = tb::probe_dtm(&tables, pos, wdl, &mut found);
                     if found != 0 {
                         mate += if wdl > 0 { -ss[5].ply } else { ss[5].ply };
                         tte.save(
@@ -861,7 +965,7 @@ fn search<NT: NodeType>(
                             std::cmp::min(Depth::MAX - ONE_PLY, depth + 6 * ONE_PLY),
                             Move::NONE,
                             Value::NONE,
-                            tt::generation(),
+                            pos.shared().tt.generation(),
                         );
                         return mate;
                     }
@@ -915,7 +1019,7 @@ fn search<NT: NodeType>(
             eval = if ss[4].current_move != Move::NULL {
                 evaluate(pos)
             } else {
-                -ss[4].static_eval + 2 * evaluate::TEMPO
+                -ss[4].static_eval + 2 * param!(evaluate::TEMPO)
             };
             ss[5].static_eval = eval;
             tte.save(
@@ -925,17 +1029,17 @@ fn search<NT: NodeType>(
                 Depth::NONE,
                 Move::NONE,
                 eval,
-                tt::generation(),
+                pos.shared().tt.generation(),
             );
         }
 
         // Step 7. Razoring (~2 Elo)
         if !pv_node
             && depth < 3 * ONE_PLY
-            && eval <= alpha - Value(RAZOR_MARGIN[(depth / ONE_PLY) as usize])
+            && eval <= alpha - Value(param!(RAZOR_MARGIN[(depth / ONE_PLY) as usize]))
         {
             let ralpha = alpha
-                - Value((depth >= 2 * ONE_PLY) as i32 * RAZOR_MARGIN[(depth / ONE_PLY) as usize]);
+                - Value((depth >= 2 * ONE_PLY) as i32 * param!(RAZOR_MARGIN[(depth / ONE_PLY) as usize]));
             let v = qsearch::<NonPv>(pos, ss, ralpha, ralpha + 1, Depth::ZERO);
             if depth < 2 * ONE_PLY || v <= alpha {
                 return v;
@@ -1056,7 +1160,7 @@ fn search<NT: NodeType>(
             let d = (3 * depth / (4 * ONE_PLY) - 2) * ONE_PLY;
             search::<NT>(pos, ss, alpha, beta, d, cut_node);
 
-            let (tmp_tte, tmp_tt_hit) = tt::probe(pos_key);
+            let (tmp_tte, tmp_tt_hit) = pos.shared().tt.probe(pos_key);
             tte = tmp_tte;
             tt_hit = tmp_tt_hit;
             tt_move = if tt_hit { tte.mov() } else { Move::NONE };
@@ -1106,14 +1210,11 @@ fn search<NT: NodeType>(
         move_count += 1;
         ss[5].move_count = move_count;
 
-        if root_node && pos.is_main && timeman::elapsed() > 3000 {
-            println!(
-                "info depth {} currmove {} currmovenumber {}",
-                depth / ONE_PLY,
-                uci::move_str(m, pos.is_chess960()),
-                move_count + pos.pv_idx as i32
-            );
-            stdout().flush().unwrap();
+        if root_node && pos.is_main && pos.shared().time.elapsed() > 3000 {
+            let number = move_count as usize + pos.pv_idx;
+            pos.shared()
+                .listener()
+                .on_currmove(pos, depth / ONE_PLY, m, number);
         }
 
         if pv_node {
@@ -1209,7 +1310,7 @@ fn search<NT: NodeType>(
                 }
             } else if depth < 7 * ONE_PLY // (~20 Elo)
                 && extension == Depth::ZERO
-                && !pos.see_ge(m, -Value(CAPTURE_PRUNE_MARGIN[(depth / ONE_PLY) as usize]))
+                && !pos.see_ge(m, -Value(param!(CAPTURE_PRUNE_MARGIN[(depth / ONE_PLY) as usize])))
             {
                 continue;
             }
@@ -1340,7 +1441,7 @@ fn search<NT: NodeType>(
         // Finished searching the move. If a stop occurred, the return value
         // of the search cannot be trusted, and we return immediately without
         // updating best move, PV and TT.
-        if threads::stop() {
+        if pos.shared().stop() {
             return Value::ZERO;
         }
 
@@ -1476,7 +1577,7 @@ fn search<NT: NodeType>(
             depth,
             best_move,
             ss[5].static_eval,
-            tt::generation(),
+            pos.shared().tt.generation(),
         );
     }
 
@@ -1534,7 +1635,7 @@ fn qsearch<NT: NodeType>(
     };
     // Transposition table lookup
     let pos_key = pos.key();
-    let (tte, tt_hit) = tt::probe(pos_key);
+    let (tte, tt_hit) = pos.shared().tt.probe(pos_key);
     let tt_move = if tt_hit { tte.mov() } else { Move::NONE };
     let tt_value = if tt_hit {
         value_from_tt(tte.value(), ss[5].ply)
@@ -1585,7 +1686,7 @@ fn qsearch<NT: NodeType>(
             best_value = if ss[4].current_move != Move::NULL {
                 evaluate(pos)
             } else {
-                -ss[4].static_eval + 2 * evaluate::TEMPO
+                -ss[4].static_eval + 2 * param!(evaluate::TEMPO)
             };
             ss[5].static_eval = best_value;
         }
@@ -1600,7 +1701,7 @@ fn qsearch<NT: NodeType>(
                     Depth::NONE,
                     Move::NONE,
                     ss[5].static_eval,
-                    tt::generation(),
+                    pos.shared().tt.generation(),
                 );
             }
 
@@ -1707,7 +1808,7 @@ fn qsearch<NT: NodeType>(
                         tt_depth,
                         m,
                         ss[5].static_eval,
-                        tt::generation(),
+                        pos.shared().tt.generation(),
                     );
 
                     return value;
@@ -1733,7 +1834,7 @@ fn qsearch<NT: NodeType>(
         tt_depth,
         best_move,
         ss[5].static_eval,
-        tt::generation(),
+        pos.shared().tt.generation(),
     );
 
     debug_assert!(best_value > -Value::INFINITE && best_value < Value::INFINITE);
@@ -1860,35 +1961,63 @@ fn update_counters(pos: &Position) {
     th.tb_hits.set(pos.tb_hits);
 }
 
-// check_time() is used to print debug info and, more importantly, to detect
-// when we are out of available time and have to stop the search.
+// Interval in milliseconds between two progress reports of the main thread
+const INFO_INTERVAL: i64 = 1000;
+
+// check_time() is used to report progress info and, more importantly, to
+// detect when we are out of available time and have to stop the search.
+
+fn check_time(pos: &mut Position) {
+    let elapsed = pos.shared().time.elapsed();
+
+    // Let the GUI know the search is alive during long iterations
+    if pos.is_main && elapsed - pos.last_info_time >= INFO_INTERVAL {
+        pos.last_info_time = elapsed;
+        let stats = search_stats(pos, elapsed);
+        pos.shared().listener().on_progress(&stats);
+    }
 
-fn check_time() {
     // An engine may not stop pondering until told so by the GUI
-    if threads::ponder() {
+    if pos.shared().ponder() {
         return;
     }
 
-    let elapsed = timeman::elapsed();
-
-    if (limits().use_time_management() && elapsed > timeman::maximum() - 10)
-        || (limits().movetime != 0 && elapsed >= limits().movetime)
-        || (limits().nodes != 0 && threads::nodes_searched() >= limits().nodes)
+    if (pos.limits.use_time_management() && elapsed > pos.shared().time.maximum() - 10)
+        || (pos.limits.movetime != 0 && elapsed >= pos.limits.movetime)
+        || (pos.limits.nodes != 0 && pos.shared().nodes_searched() >= pos.limits.nodes)
     {
-        threads::set_stop(true);
+        pos.shared().set_stop(true);
+    }
+}
+
+// search_stats() collects the node count, speed, hash usage and tablebase
+// hits of the search so far
+
+fn search_stats(pos: &Position, elapsed: i64) -> SearchStats {
+    let nodes = pos.shared().nodes_searched();
+    SearchStats {
+        nodes,
+        nps: nodes * 1000 / std::cmp::max(elapsed, 1) as u64,
+        hashfull: pos.shared().tt.hashfull(),
+        tb_hits: pos.shared().tb_hits(),
+        time: elapsed,
     }
 }
 
-// print_pv() prints PV information according to the UCI protocol. UCI
-// requires that all (if any) unsearched PV lines are sent using a previous
-// search score.
+// report_pv() passes the PV lines to the listener. UCI requires that all
+// (if any) unsearched PV lines are sent using a previous search score.
 
-fn print_pv(pos: &mut Position, depth: Depth, alpha: Value, beta: Value) {
-    let elapsed = timeman::elapsed() + 1;
+fn report_pv(pos: &mut Position, depth: Depth, alpha: Value, beta: Value) {
+    let elapsed = pos.shared().time.elapsed() + 1;
     let pv_idx = pos.pv_idx;
-    let multi_pv = std::cmp::min(ucioption::get_i32("MultiPV") as usize, pos.root_moves.len());
-    let nodes_searched = threads::nodes_searched();
-    let tb_hits = threads::tb_hits();
+    let (multi_pv, show_wdl) = {
+        let options = pos.shared().options.read().unwrap();
+        (
+            std::cmp::min(options.get_i32("MultiPV") as usize, pos.root_moves.len()),
+            options.get_bool("UCI_ShowWDL"),
+        )
+    };
+    let mut lines = Vec::new();
 
     for i in 0..multi_pv {
         let updated = i <= pv_idx && pos.root_moves[i].score != -Value::INFINITE;
@@ -1904,7 +2033,7 @@ fn print_pv(pos: &mut Position, depth: Depth, alpha: Value, beta: Value) {
             pos.root_moves[i].previous_score
         };
 
-        let tb = tb::root_in_tb() && v.abs() < Value::MATE - MAX_MATE_PLY;
+        let tb = pos.tb_config.root_in_tb && v.abs() < Value::MATE - MAX_MATE_PLY;
         if tb {
             v = pos.root_moves[i].tb_score;
         }
@@ -1914,45 +2043,46 @@ fn print_pv(pos: &mut Position, depth: Depth, alpha: Value, beta: Value) {
         // latter case.
         if v.abs() > Value::MATE - MAX_MATE_PLY
             && (pos.root_moves[i].pv.len() as i32) < (Value::MATE - v.abs()).0
-            && tb::cardinality_dtm() > 0
+            && pos.tb_config.cardinality_dtm > 0
         {
-            tb::expand_mate(pos, i);
-        }
-
-        print!(
-            "info depth {} seldepth {} multipv {} score {} ",
-            d / ONE_PLY,
-            pos.root_moves[i].sel_depth + 1,
-            i + 1,
-            uci::value(v)
-        );
-
-        if !tb && i == pv_idx {
-            if v >= beta {
-                print!("lowerbound ");
-            } else if v <= alpha {
-                print!("upperbound ");
-            }
+            let tb_config = pos.tb_config.clone();
+            tb::expand_mate(pos, i, &tb_config);
         }
 
-        print!(
-            "nodes {} nps {}",
-            nodes_searched,
-            nodes_searched * 1000 / (elapsed as u64)
-        );
-
-        if elapsed > 1000 {
-            print!(" hashfull {}", tt::hashfull());
-        }
+        // Inside the tablebases the outcome is known exactly
+        let wdl = if !show_wdl {
+            None
+        } else if tb {
+            Some(tb::root_wdl(&pos.root_moves[i]))
+        } else {
+            Some(uci::wdl(v, pos.game_ply()))
+        };
 
-        print!(" tbhits {} time {} pv", tb_hits, elapsed);
+        let bound = if tb || i != pv_idx {
+            ScoreBound::Exact
+        } else if v >= beta {
+            ScoreBound::Lower
+        } else if v <= alpha {
+            ScoreBound::Upper
+        } else {
+            ScoreBound::Exact
+        };
 
-        for &m in pos.root_moves[i].pv.iter() {
-            print!(" {}", uci::move_str(m, pos.is_chess960()));
-        }
-        println!();
+        lines.push(PvLine {
+            multipv: i + 1,
+            depth: d / ONE_PLY,
+            sel_depth: pos.root_moves[i].sel_depth + 1,
+            score: v,
+            bound,
+            wdl,
+            pv: pos.root_moves[i].pv.clone(),
+        });
     }
-    stdout().flush().unwrap();
+
+    let stats = search_stats(pos, elapsed);
+    pos.shared()
+        .listener()
+        .on_iteration(pos, depth / ONE_PLY, &lines, &stats);
 }
 
 // extract_ponder_from_tt() is called in case we have no ponder move before
@@ -1971,7 +2101,7 @@ fn extract_ponder_from_tt(pos: &mut Position) -> bool {
 
     let gives_check = pos.gives_check(m1);
     pos.do_move(m1, gives_check);
-    let (tte, tt_hit) = tt::probe(pos.key());
+    let (tte, tt_hit) = pos.shared().tt.probe(pos.key());
 
     if tt_hit {
         let m2 = tte.mov(); // Local copy to be SMP safe.
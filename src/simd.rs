@@ -0,0 +1,304 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+// Vector kernels of the NNUE inference. Each kernel has an AVX2, an SSE4.1
+// and a plain Rust version. The binary is built for the baseline x86-64
+// target, so the vector versions are compiled with #[target_feature] and
+// only called after detect() has found the instructions at runtime.
+//
+// All versions give exactly the same results: the i16 additions wrap and
+// the dot products cannot overflow the i16 intermediate sums, as the
+// inputs are at most 127.
+
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Simd {
+    Scalar,
+    Sse41,
+    Avx2,
+}
+
+impl Simd {
+    pub fn name(self) -> &'static str {
+        match self {
+            Simd::Scalar => "scalar",
+            Simd::Sse41 => "SSE4.1",
+            Simd::Avx2 => "AVX2",
+        }
+    }
+}
+
+// detect() returns the best instruction set supported by the CPU
+
+#[cfg(target_arch = "x86_64")]
+pub fn detect() -> Simd {
+    if is_x86_feature_detected!("avx2") {
+        Simd::Avx2
+    } else if is_x86_feature_detected!("sse4.1") {
+        Simd::Sse41
+    } else {
+        Simd::Scalar
+    }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+pub fn detect() -> Simd {
+    Simd::Scalar
+}
+
+// add_i16() adds w to acc element-wise, sub_i16() subtracts it. The slices
+// have the same length, a multiple of 16.
+
+pub fn add_i16(simd: Simd, acc: &mut [i16], w: &[i16]) {
+    debug_assert!(acc.len() == w.len() && acc.len().is_multiple_of(16));
+
+    #[cfg(target_arch = "x86_64")]
+    unsafe {
+        match simd {
+            Simd::Avx2 => return avx2::add_i16(acc, w),
+            Simd::Sse41 => return sse41::add_i16(acc, w),
+            Simd::Scalar => {}
+        }
+    }
+
+    for (a, &b) in acc.iter_mut().zip(w.iter()) {
+        *a = a.wrapping_add(b);
+    }
+}
+
+pub fn sub_i16(simd: Simd, acc: &mut [i16], w: &[i16]) {
+    debug_assert!(acc.len() == w.len() && acc.len().is_multiple_of(16));
+
+    #[cfg(target_arch = "x86_64")]
+    unsafe {
+        match simd {
+            Simd::Avx2 => return avx2::sub_i16(acc, w),
+            Simd::Sse41 => return sse41::sub_i16(acc, w),
+            Simd::Scalar => {}
+        }
+    }
+
+    for (a, &b) in acc.iter_mut().zip(w.iter()) {
+        *a = a.wrapping_sub(b);
+    }
+}
+
+// clamp_u8() clamps the values of input to [0, 127]. The length of the
+// slices is a multiple of 32.
+
+pub fn clamp_u8(simd: Simd, input: &[i16], output: &mut [u8]) {
+    debug_assert!(input.len() == output.len() && input.len().is_multiple_of(32));
+
+    #[cfg(target_arch = "x86_64")]
+    unsafe {
+        match simd {
+            Simd::Avx2 => return avx2::clamp_u8(input, output),
+            Simd::Sse41 => return sse41::clamp_u8(input, output),
+            Simd::Scalar => {}
+        }
+    }
+
+    for (o, &v) in output.iter_mut().zip(input.iter()) {
+        *o = v.clamp(0, 127) as u8;
+    }
+}
+
+// dot_u8_i8() returns the dot product of input, with values in [0, 127],
+// and w. The length of the slices is a multiple of 32.
+
+pub fn dot_u8_i8(simd: Simd, input: &[u8], w: &[i8]) -> i32 {
+    debug_assert!(input.len() == w.len() && input.len().is_multiple_of(32));
+
+    #[cfg(target_arch = "x86_64")]
+    unsafe {
+        match simd {
+            Simd::Avx2 => return avx2::dot_u8_i8(input, w),
+            Simd::Sse41 => return sse41::dot_u8_i8(input, w),
+            Simd::Scalar => {}
+        }
+    }
+
+    input
+        .iter()
+        .zip(w.iter())
+        .map(|(&x, &w)| x as i32 * w as i32)
+        .sum()
+}
+
+#[cfg(target_arch = "x86_64")]
+mod avx2 {
+    use std::arch::x86_64::*;
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn add_i16(acc: &mut [i16], w: &[i16]) {
+        for i in (0..acc.len()).step_by(16) {
+            let a = acc.as_mut_ptr().add(i) as *mut __m256i;
+            let b = w.as_ptr().add(i) as *const __m256i;
+            _mm256_storeu_si256(a, _mm256_add_epi16(_mm256_loadu_si256(a), _mm256_loadu_si256(b)));
+        }
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn sub_i16(acc: &mut [i16], w: &[i16]) {
+        for i in (0..acc.len()).step_by(16) {
+            let a = acc.as_mut_ptr().add(i) as *mut __m256i;
+            let b = w.as_ptr().add(i) as *const __m256i;
+            _mm256_storeu_si256(a, _mm256_sub_epi16(_mm256_loadu_si256(a), _mm256_loadu_si256(b)));
+        }
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn clamp_u8(input: &[i16], output: &mut [u8]) {
+        let zero = _mm256_setzero_si256();
+        for i in (0..input.len()).step_by(32) {
+            let lo = _mm256_loadu_si256(input.as_ptr().add(i) as *const __m256i);
+            let hi = _mm256_loadu_si256(input.as_ptr().add(i + 16) as *const __m256i);
+            // packs works on 128-bit lanes, so the result has to be
+            // put back in order
+            let packed = _mm256_max_epi8(_mm256_packs_epi16(lo, hi), zero);
+            let ordered = _mm256_permute4x64_epi64(packed, 0b11_01_10_00);
+            _mm256_storeu_si256(output.as_mut_ptr().add(i) as *mut __m256i, ordered);
+        }
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn dot_u8_i8(input: &[u8], w: &[i8]) -> i32 {
+        let ones = _mm256_set1_epi16(1);
+        let mut sum = _mm256_setzero_si256();
+        for i in (0..input.len()).step_by(32) {
+            let x = _mm256_loadu_si256(input.as_ptr().add(i) as *const __m256i);
+            let y = _mm256_loadu_si256(w.as_ptr().add(i) as *const __m256i);
+            let products = _mm256_madd_epi16(_mm256_maddubs_epi16(x, y), ones);
+            sum = _mm256_add_epi32(sum, products);
+        }
+        let sum = _mm_add_epi32(_mm256_castsi256_si128(sum), _mm256_extracti128_si256(sum, 1));
+        super::hadd_epi32(sum)
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod sse41 {
+    use std::arch::x86_64::*;
+
+    #[target_feature(enable = "sse4.1")]
+    pub unsafe fn add_i16(acc: &mut [i16], w: &[i16]) {
+        for i in (0..acc.len()).step_by(8) {
+            let a = acc.as_mut_ptr().add(i) as *mut __m128i;
+            let b = w.as_ptr().add(i) as *const __m128i;
+            _mm_storeu_si128(a, _mm_add_epi16(_mm_loadu_si128(a), _mm_loadu_si128(b)));
+        }
+    }
+
+    #[target_feature(enable = "sse4.1")]
+    pub unsafe fn sub_i16(acc: &mut [i16], w: &[i16]) {
+        for i in (0..acc.len()).step_by(8) {
+            let a = acc.as_mut_ptr().add(i) as *mut __m128i;
+            let b = w.as_ptr().add(i) as *const __m128i;
+            _mm_storeu_si128(a, _mm_sub_epi16(_mm_loadu_si128(a), _mm_loadu_si128(b)));
+        }
+    }
+
+    #[target_feature(enable = "sse4.1")]
+    pub unsafe fn clamp_u8(input: &[i16], output: &mut [u8]) {
+        let zero = _mm_setzero_si128();
+        for i in (0..input.len()).step_by(16) {
+            let lo = _mm_loadu_si128(input.as_ptr().add(i) as *const __m128i);
+            let hi = _mm_loadu_si128(input.as_ptr().add(i + 8) as *const __m128i);
+            let packed = _mm_max_epi8(_mm_packs_epi16(lo, hi), zero);
+            _mm_storeu_si128(output.as_mut_ptr().add(i) as *mut __m128i, packed);
+        }
+    }
+
+    #[target_feature(enable = "sse4.1")]
+    pub unsafe fn dot_u8_i8(input: &[u8], w: &[i8]) -> i32 {
+        let ones = _mm_set1_epi16(1);
+        let mut sum = _mm_setzero_si128();
+        for i in (0..input.len()).step_by(16) {
+            let x = _mm_loadu_si128(input.as_ptr().add(i) as *const __m128i);
+            let y = _mm_loadu_si128(w.as_ptr().add(i) as *const __m128i);
+            let products = _mm_madd_epi16(_mm_maddubs_epi16(x, y), ones);
+            sum = _mm_add_epi32(sum, products);
+        }
+        super::hadd_epi32(sum)
+    }
+}
+
+// hadd_epi32() returns the sum of the four 32-bit integers of a vector
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "sse4.1")]
+unsafe fn hadd_epi32(v: __m128i) -> i32 {
+    let v = _mm_add_epi32(v, _mm_shuffle_epi32(v, 0b01_00_11_10));
+    let v = _mm_add_epi32(v, _mm_shuffle_epi32(v, 0b10_11_00_01));
+    _mm_cvtsi128_si32(v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use misc::Prng;
+
+    // supported() lists the instruction sets the CPU can run
+
+    fn supported() -> Vec<Simd> {
+        let mut list = vec![Simd::Scalar];
+        #[cfg(target_arch = "x86_64")]
+        {
+            if is_x86_feature_detected!("sse4.1") {
+                list.push(Simd::Sse41);
+            }
+            if is_x86_feature_detected!("avx2") {
+                list.push(Simd::Avx2);
+            }
+        }
+        list
+    }
+
+    // Every kernel must give the same results as the plain Rust version,
+    // including wrapping additions and values out of the clamped range
+
+    #[test]
+    fn same_as_scalar() {
+        let mut rng = Prng::new(1070372);
+        for &len in [32, 256, 512].iter() {
+            let a: Vec<i16> = (0..len).map(|_| rng.rand64() as i16).collect();
+            let w: Vec<i16> = (0..len).map(|_| rng.rand64() as i16).collect();
+            let x: Vec<u8> = (0..len).map(|_| (rng.rand64() % 128) as u8).collect();
+            let y: Vec<i8> = (0..len).map(|_| rng.rand64() as i8).collect();
+
+            let mut add = a.clone();
+            add_i16(Simd::Scalar, &mut add, &w);
+            let mut sub = a.clone();
+            sub_i16(Simd::Scalar, &mut sub, &w);
+            let mut clamped = vec![0u8; len];
+            clamp_u8(Simd::Scalar, &a, &mut clamped);
+            let dot = dot_u8_i8(Simd::Scalar, &x, &y);
+
+            for &simd in supported().iter() {
+                let mut v = a.clone();
+                add_i16(simd, &mut v, &w);
+                assert_eq!(v, add, "add_i16 {}", simd.name());
+
+                let mut v = a.clone();
+                sub_i16(simd, &mut v, &w);
+                assert_eq!(v, sub, "sub_i16 {}", simd.name());
+
+                let mut v = vec![0u8; len];
+                clamp_u8(simd, &a, &mut v);
+                assert_eq!(v, clamped, "clamp_u8 {}", simd.name());
+
+                assert_eq!(dot_u8_i8(simd, &x, &y), dot, "dot_u8_i8 {}", simd.name());
+            }
+        }
+
+        // The largest products of the dot product
+        let x = vec![127u8; 64];
+        for &w in [-128i8, 127].iter() {
+            let y = vec![w; 64];
+            for &simd in supported().iter() {
+                assert_eq!(dot_u8_i8(simd, &x, &y), 64 * 127 * w as i32, "{}", simd.name());
+            }
+        }
+    }
+}
@@ -0,0 +1,318 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+// Statistics of engine matches. Results are given as counts of outcomes
+// with evenly spaced scores from 0 to 1, lowest score first:
+//
+//   trinomial:   [losses, draws, wins], the results of single games
+//   pentanomial: the results of game pairs, played from the same opening
+//                with the colors reversed, scored 0, 1/4, 1/2, 3/4 and 1
+//
+// Pairs cancel most of the bias of the openings, so the pentanomial
+// estimates have a smaller variance and are preferred when available.
+// Elo differences are logistic: a score s corresponds to
+// 400 * log10(s / (1 - s)).
+//
+// The sequential probability ratio test compares the likelihood of the
+// results under H0 and H1, as in fishtest. Each hypothesis fixes the
+// expected score, s0 or s1, and the distribution of the outcomes is taken
+// as the one closest to the observed frequencies with that expectation.
+// This maximum likelihood distribution is p_i / (1 + l * (a_i - s)), where
+// the p_i are the observed frequencies of the scores a_i and l is chosen
+// so that the probabilities add up to 1.
+
+// elo() converts an expected score to an Elo difference, which is infinite
+// for a score of 0 or 1
+
+pub fn elo(score: f64) -> f64 {
+    let s = score.clamp(0.0, 1.0);
+    400.0 * (s / (1.0 - s)).log10()
+}
+
+// expected_score() is the inverse of elo()
+
+pub fn expected_score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+// erf() is the error function, from Abramowitz and Stegun 7.1.26. The
+// absolute error is below 1.5e-7.
+
+fn erf(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.3275911 * x.abs());
+    let poly = t
+        * (0.254829592
+            + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    let y = 1.0 - poly * (-x * x).exp();
+    if x < 0.0 {
+        -y
+    } else {
+        y
+    }
+}
+
+// frequencies() returns the number of samples and the frequency of each
+// score. With regularize set, outcomes not seen yet are counted as a small
+// fraction of a sample, so that no outcome is impossible.
+
+fn frequencies(counts: &[u32], regularize: bool) -> (f64, Vec<(f64, f64)>) {
+    let count = |c: u32| if regularize && c == 0 { 1e-3 } else { c as f64 };
+    let n: f64 = counts.iter().map(|&c| count(c)).sum();
+    if counts.len() < 2 || counts.iter().all(|&c| c == 0) {
+        return (0.0, Vec::new());
+    }
+
+    let k = (counts.len() - 1) as f64;
+    let freqs = counts
+        .iter()
+        .enumerate()
+        .map(|(i, &c)| (i as f64 / k, count(c) / n))
+        .collect();
+
+    (n, freqs)
+}
+
+// mean_variance() returns the number of samples and the mean and variance
+// of their scores
+
+fn mean_variance(counts: &[u32], regularize: bool) -> (f64, f64, f64) {
+    let (n, freqs) = frequencies(counts, regularize);
+    if n == 0.0 {
+        return (0.0, 0.5, 0.0);
+    }
+
+    let mean: f64 = freqs.iter().map(|&(a, p)| p * a).sum();
+    let variance: f64 = freqs.iter().map(|&(a, p)| p * (a - mean).powi(2)).sum();
+
+    (n, mean, variance)
+}
+
+// score() returns the average score per game
+
+pub fn score(counts: &[u32]) -> f64 {
+    mean_variance(counts, false).1
+}
+
+// elo_estimate() returns the Elo difference implied by the results and the
+// half width of its 95% confidence interval
+
+pub fn elo_estimate(counts: &[u32]) -> (f64, f64) {
+    let (n, mean, variance) = mean_variance(counts, false);
+    if n == 0.0 || mean <= 0.0 || mean >= 1.0 {
+        return (elo(mean), f64::INFINITY);
+    }
+
+    let margin = 1.959964 * (variance / n).sqrt();
+    (elo(mean), (elo(mean + margin) - elo(mean - margin)) / 2.0)
+}
+
+// los() returns the likelihood of superiority, the probability that the
+// first player is the stronger one
+
+pub fn los(counts: &[u32]) -> f64 {
+    let (n, mean, variance) = mean_variance(counts, true);
+    if n == 0.0 {
+        return 0.5;
+    }
+
+    0.5 * (1.0 + erf((mean - 0.5) / (2.0 * variance / n).sqrt()))
+}
+
+// mle_lambda() returns the l for which the distribution p_i / (1 + l *
+// (a_i - s)) adds up to 1. The sum decreases with l, which is found by
+// bisection between the values where a probability would become infinite.
+
+fn mle_lambda(freqs: &[(f64, f64)], s: f64) -> f64 {
+    let (mut lo, mut hi) = (-1.0 / (1.0 - s), 1.0 / s);
+
+    for _ in 0..100 {
+        let l = (lo + hi) / 2.0;
+        let sum: f64 = freqs.iter().map(|&(a, p)| p * (a - s) / (1.0 + l * (a - s))).sum();
+        if sum > 0.0 {
+            lo = l;
+        } else {
+            hi = l;
+        }
+    }
+
+    (lo + hi) / 2.0
+}
+
+// llr() returns the log-likelihood ratio of H1 (the Elo difference is
+// elo1) against H0 (the Elo difference is elo0)
+
+pub fn llr(counts: &[u32], elo0: f64, elo1: f64) -> f64 {
+    let (n, freqs) = frequencies(counts, true);
+    if n == 0.0 {
+        return 0.0;
+    }
+
+    let (s0, s1) = (expected_score(elo0), expected_score(elo1));
+    let (l0, l1) = (mle_lambda(&freqs, s0), mle_lambda(&freqs, s1));
+
+    n * freqs
+        .iter()
+        .map(|&(a, p)| p * ((1.0 + l0 * (a - s0)).ln() - (1.0 + l1 * (a - s1)).ln()))
+        .sum::<f64>()
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SprtStatus {
+    Continue,
+    AcceptH0,
+    AcceptH1,
+}
+
+/// Sprt is a sequential probability ratio test of H0: the Elo difference
+/// is elo0, against H1: it is elo1. Alpha and beta are the probabilities
+/// of accepting H1 when H0 holds and H0 when H1 holds.
+#[derive(Clone, Copy, Debug)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+impl Sprt {
+    pub fn new(elo0: f64, elo1: f64) -> Sprt {
+        Sprt {
+            elo0,
+            elo1,
+            alpha: 0.05,
+            beta: 0.05,
+        }
+    }
+
+    // bounds() returns the LLR below which H0 and above which H1 is
+    // accepted
+
+    pub fn bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1.0 - self.alpha)).ln(),
+            ((1.0 - self.beta) / self.alpha).ln(),
+        )
+    }
+
+    pub fn llr(&self, counts: &[u32]) -> f64 {
+        llr(counts, self.elo0, self.elo1)
+    }
+
+    // status() tells whether the test can stop after the given results
+
+    pub fn status(&self, counts: &[u32]) -> SprtStatus {
+        let llr = self.llr(counts);
+        let (lower, upper) = self.bounds();
+
+        if llr <= lower {
+            SprtStatus::AcceptH0
+        } else if llr >= upper {
+            SprtStatus::AcceptH1
+        } else {
+            SprtStatus::Continue
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Results of a long test, [losses, draws, wins] and the pentanomial of
+    // a similar one
+    const TRINOMIAL: [u32; 3] = [11281, 20851, 11612];
+    const PENTANOMIAL: [u32; 5] = [184, 5110, 10895, 5411, 272];
+
+    fn assert_near(x: f64, expected: f64, eps: f64) {
+        assert!((x - expected).abs() < eps, "{} != {}", x, expected);
+    }
+
+    #[test]
+    fn elo_conversions() {
+        assert_eq!(elo(0.5), 0.0);
+        assert_near(elo(0.75), 400.0 * 3f64.log10(), 1e-9);
+        assert_near(expected_score(elo(0.3)), 0.3, 1e-12);
+        assert!(elo(1.0).is_infinite());
+    }
+
+    #[test]
+    fn sprt_bounds() {
+        // The (-2.94, 2.94) shown by fishtest for alpha = beta = 0.05
+        let (lower, upper) = Sprt::new(0.0, 2.0).bounds();
+        assert_near(lower, -2.944439, 1e-6);
+        assert_near(upper, 2.944439, 1e-6);
+    }
+
+    #[test]
+    fn mle_distribution() {
+        // The distribution has the expected score of the hypothesis
+        let (_, freqs) = frequencies(&PENTANOMIAL, true);
+        for &s in [0.45, 0.5, 0.52].iter() {
+            let l = mle_lambda(&freqs, s);
+            let probs: Vec<(f64, f64)> =
+                freqs.iter().map(|&(a, p)| (a, p / (1.0 + l * (a - s)))).collect();
+            assert_near(probs.iter().map(|&(_, p)| p).sum(), 1.0, 1e-9);
+            assert_near(probs.iter().map(|&(a, p)| a * p).sum(), s, 1e-9);
+        }
+
+        // It is the observed one when the expectation is the observed mean
+        let (_, mean, _) = mean_variance(&TRINOMIAL, true);
+        let (_, freqs) = frequencies(&TRINOMIAL, true);
+        assert_near(mle_lambda(&freqs, mean), 0.0, 1e-9);
+    }
+
+    #[test]
+    fn log_likelihood_ratio() {
+        // Reference values from the formulas of fishtest's LLR_logistic()
+        assert_near(llr(&TRINOMIAL, 0.0, 2.0), 2.255992, 1e-5);
+        assert_near(llr(&TRINOMIAL, -1.0, 1.0), 3.640796, 1e-5);
+        assert_near(llr(&PENTANOMIAL, 0.0, 2.0), 7.168915, 1e-5);
+        assert_near(llr(&[52, 118, 130], 0.0, 5.0), 1.802630, 1e-5);
+        assert_near(llr(&[30, 40, 30], 0.0, 5.0), -0.017257, 1e-5);
+        assert_eq!(llr(&[0, 0, 0], 0.0, 5.0), 0.0);
+
+        // For many games and close hypotheses, the LLR is near fishtest's
+        // normal approximation N (s1 - s0) (2 mean - s0 - s1) / (2 var)
+        let (n, mean, variance) = mean_variance(&TRINOMIAL, true);
+        let (s0, s1) = (expected_score(0.0), expected_score(2.0));
+        let approx = n * (s1 - s0) * (2.0 * mean - s0 - s1) / (2.0 * variance);
+        assert_near(llr(&TRINOMIAL, 0.0, 2.0), approx, 1e-3);
+
+        let sprt = Sprt::new(0.0, 2.0);
+        assert_eq!(sprt.status(&TRINOMIAL), SprtStatus::Continue);
+        assert_eq!(sprt.status(&PENTANOMIAL), SprtStatus::AcceptH1);
+        assert_eq!(Sprt::new(5.0, 10.0).status(&PENTANOMIAL), SprtStatus::AcceptH0);
+    }
+
+    #[test]
+    fn elo_error_bars() {
+        // Reference values from the formulas of fishtest's get_elo() and
+        // cutechess-cli's Elo class, which agree on single games
+        let (e, margin) = elo_estimate(&[20, 40, 40]);
+        assert_near(e, 70.436504, 1e-5);
+        assert_near(margin, 53.552652, 1e-5);
+
+        let (e, margin) = elo_estimate(&TRINOMIAL);
+        assert_near(e, 2.629009, 1e-5);
+        assert_near(margin, 2.355391, 1e-5);
+
+        let (e, margin) = elo_estimate(&PENTANOMIAL);
+        assert_near(e, 3.788710, 1e-5);
+        assert_near(margin, 1.729106, 1e-5);
+
+        assert_eq!(elo_estimate(&[0, 0, 10]).1, f64::INFINITY);
+    }
+
+    #[test]
+    fn likelihood_of_superiority() {
+        assert_near(los(&[30, 40, 30]), 0.5, 1e-6);
+        assert_near(los(&[20, 40, 40]), 0.996237, 1e-5);
+        assert_near(los(&PENTANOMIAL), 0.999991, 1e-5);
+
+        // cutechess-cli computes it from wins and losses only, as
+        // 1/2 + 1/2 erf((W - L) / sqrt(2 (W + L)))
+        let (w, l) = (TRINOMIAL[2] as f64, TRINOMIAL[0] as f64);
+        let cutechess = 0.5 + 0.5 * erf((w - l) / (2.0 * (w + l)).sqrt());
+        assert_near(cutechess, 0.985652, 1e-5);
+        assert_near(los(&TRINOMIAL), cutechess, 1e-4);
+    }
+}
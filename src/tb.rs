@@ -4,9 +4,9 @@ use bitboard::*;
 use movegen::*;
 use position::zobrist::material;
 use position::Position;
-use search::RootMoves;
+use search::{RootMove, RootMoves};
 use types::*;
-use ucioption;
+use ucioption::OptionsMap;
 
 use memmap::*;
 
@@ -17,58 +17,54 @@ use std::fs;
 use std::path::Path;
 use std::slice;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex, Once, Weak};
 
 const TB_PIECES: usize = 7;
 
-static mut MAX_CARDINALITY: u32 = 0;
-static mut MAX_CARDINALITY_DTM: u32 = 0;
-static mut CARDINALITY: u32 = 0;
-static mut CARDINALITY_DTM: u32 = 0;
-static mut ROOT_IN_TB: bool = false;
-static mut USE_RULE_50: bool = true;
-static mut PROBE_DEPTH: Depth = Depth(0);
-
-pub fn read_options() {
-    unsafe {
-        USE_RULE_50 = ucioption::get_bool("Syzygy50MoveRule");
-        PROBE_DEPTH = ucioption::get_i32("SyzygyProbeDepth") * ONE_PLY;
-        CARDINALITY = ucioption::get_i32("SyzygyProbeLimit") as u32;
-        if CARDINALITY > MAX_CARDINALITY {
-            CARDINALITY = MAX_CARDINALITY;
-            PROBE_DEPTH = Depth::ZERO;
-        }
-        CARDINALITY_DTM = if ucioption::get_bool("SyzygyUseDTM") {
-            std::cmp::min(CARDINALITY, MAX_CARDINALITY_DTM)
-        } else {
-            0
-        };
-    }
-}
-
-pub fn max_cardinality() -> u32 {
-    unsafe { MAX_CARDINALITY }
-}
-
-pub fn cardinality() -> u32 {
-    unsafe { CARDINALITY }
-}
+// TbConfig holds the tablebases and the tablebase settings of a single
+// search. Every engine probes the tablebases according to its own options.
 
-pub fn cardinality_dtm() -> u32 {
-    unsafe { CARDINALITY_DTM }
-}
-
-pub fn root_in_tb() -> bool {
-    unsafe { ROOT_IN_TB }
-}
-
-pub fn use_rule_50() -> bool {
-    unsafe { USE_RULE_50 }
+#[derive(Clone)]
+pub struct TbConfig {
+    pub tables: Arc<Tablebases>,
+    pub cardinality: u32,
+    pub cardinality_dtm: u32,
+    pub root_in_tb: bool,
+    pub use_rule_50: bool,
+    pub probe_depth: Depth,
+}
+
+impl TbConfig {
+    pub fn new() -> TbConfig {
+        TbConfig {
+            tables: Arc::new(Tablebases::new()),
+            cardinality: 0,
+            cardinality_dtm: 0,
+            root_in_tb: false,
+            use_rule_50: true,
+            probe_depth: Depth::ZERO,
+        }
+    }
+}
+
+pub fn read_options(options: &OptionsMap, tables: &Arc<Tablebases>) -> TbConfig {
+    let mut config = TbConfig::new();
+    config.tables = tables.clone();
+    config.use_rule_50 = options.get_bool("Syzygy50MoveRule");
+    config.probe_depth = options.get_i32("SyzygyProbeDepth") * ONE_PLY;
+    config.cardinality = options.get_i32("SyzygyProbeLimit") as u32;
+    if config.cardinality > tables.max_cardinality {
+        config.cardinality = tables.max_cardinality;
+        config.probe_depth = Depth::ZERO;
+    }
+    config.cardinality_dtm = if options.get_bool("SyzygyUseDTM") {
+        std::cmp::min(config.cardinality, tables.max_cardinality_dtm)
+    } else {
+        0
+    };
+    config
 }
 
-pub fn probe_depth() -> Depth {
-    unsafe { PROBE_DEPTH }
-}
 
 struct EncInfo {
     precomp: Option<Box<PairsData>>,
@@ -723,8 +719,6 @@ fn calc_key_from_pieces(pieces: &[u8]) -> Key {
     key
 }
 
-static mut PATH: Option<String> = None;
-
 fn sep_char() -> char {
     if cfg!(target_os = "windows") {
         ';'
@@ -733,356 +727,263 @@ fn sep_char() -> char {
     }
 }
 
-fn test_tb(name: &str, suffix: &str) -> bool {
-    let dirs = unsafe { PATH.as_ref().unwrap().split(sep_char()) };
-    for dir in dirs {
-        let file_name = format!("{}{}{}{}", dir, '/', name, suffix);
-        let path = Path::new(&file_name);
-        if path.is_file() {
-            return true;
-        }
-    }
-
-    false
+/// Tablebases holds the tablebase files found in the directories of a
+/// SyzygyPath. Every engine holds the tablebases it probes through an Arc,
+/// so the files stay mapped as long as one of its searches may use them,
+/// and engines using the same path share them, see open().
+pub struct Tablebases {
+    path: String,
+    piece_entries: Vec<PieceEntry>,
+    pawn_entries: Vec<PawnEntry>,
+    map: HashMap<Key, TbHashEntry>,
+    num_wdl: u32,
+    num_dtm: u32,
+    num_dtz: u32,
+    max_cardinality: u32,
+    max_cardinality_dtm: u32,
 }
 
-fn open_tb(name: &str, suffix: &str) -> Option<fs::File> {
-    let dirs = unsafe { PATH.as_ref().unwrap().split(sep_char()) };
-    for dir in dirs {
-        let file_name = format!("{}{}{}{}", dir, '/', name, suffix);
-        if let Ok(file) = fs::File::open(file_name) {
-            return Some(file);
-        }
-    }
-
-    None
-}
+// The entries are only added while loading. After that, the tables of an
+// entry are mapped on first use, under the lock of the entry, see
+// probe_helper().
+unsafe impl Send for Tablebases {}
+unsafe impl Sync for Tablebases {}
 
-fn map_file(name: &str, suffix: &str) -> Option<Box<Mmap>> {
-    let file = open_tb(name, suffix);
-    if file.is_none() {
-        return None;
-    }
-
-    let file = file.unwrap();
-    match unsafe { MmapOptions::new().map(&file) } {
-        Ok(mmap) => Some(Box::new(mmap)),
-        Err(err) => {
-            eprintln!("{:?}", err.kind());
-            None
+impl Tablebases {
+    pub fn new() -> Tablebases {
+        Tablebases {
+            path: String::new(),
+            piece_entries: Vec::new(),
+            pawn_entries: Vec::new(),
+            map: HashMap::new(),
+            num_wdl: 0,
+            num_dtm: 0,
+            num_dtz: 0,
+            max_cardinality: 0,
+            max_cardinality_dtm: 0,
         }
     }
-}
-
-struct GlobalVec<T> {
-    v: *mut T,
-    cap: usize,
-    len: usize,
-}
-
-impl<T> GlobalVec<T> {
-    pub fn init(&mut self, cap: usize) {
-        self.save(Vec::with_capacity(cap));
-    }
-
-    fn save(&mut self, mut v: Vec<T>) {
-        self.v = v.as_mut_ptr();
-        self.len = v.len();
-        self.cap = v.capacity();
-        std::mem::forget(v);
-    }
 
-    fn get_vec(&self) -> Vec<T> {
-        unsafe { Vec::from_raw_parts(self.v, self.len, self.cap) }
+    pub fn path(&self) -> &str {
+        &self.path
     }
 
-    pub fn push(&mut self, item: T) {
-        let mut v = self.get_vec();
-        v.push(item);
-        self.save(v);
-    }
+    // max_cardinality() returns the largest number of pieces of the
+    // tables found
 
-    pub fn len(&self) -> usize {
-        self.len
+    pub fn max_cardinality(&self) -> u32 {
+        self.max_cardinality
     }
 
-    pub unsafe fn reset(&mut self) {
-        let mut v = self.get_vec();
-        v.truncate(0);
-        self.save(v);
-    }
+    fn test_tb(&self, name: &str, suffix: &str) -> bool {
+        for dir in self.path.split(sep_char()) {
+            let file_name = format!("{}{}{}{}", dir, '/', name, suffix);
+            let path = Path::new(&file_name);
+            if path.is_file() {
+                return true;
+            }
+        }
 
-    pub unsafe fn free(&mut self) {
-        std::mem::drop(self.get_vec());
+        false
     }
-}
 
-impl<T> std::ops::Index<usize> for GlobalVec<T>
-where
-    T: 'static,
-{
-    type Output = T;
-
-    fn index(&self, idx: usize) -> &'static T {
-        unsafe {
-            let elt_ref: &'static T = &*self.v.offset(idx as isize);
-            elt_ref
+    fn open_tb(&self, name: &str, suffix: &str) -> Option<fs::File> {
+        for dir in self.path.split(sep_char()) {
+            let file_name = format!("{}{}{}{}", dir, '/', name, suffix);
+            if let Ok(file) = fs::File::open(file_name) {
+                return Some(file);
+            }
         }
-    }
-}
-
-static mut PIECE_ENTRIES: GlobalVec<PieceEntry> = GlobalVec {
-    v: 0 as *mut PieceEntry,
-    len: 0,
-    cap: 0,
-};
-
-static mut PAWN_ENTRIES: GlobalVec<PawnEntry> = GlobalVec {
-    v: 0 as *mut PawnEntry,
-    len: 0,
-    cap: 0,
-};
-
-static mut TB_MAP: *mut HashMap<Key, TbHashEntry> = 0 as *mut HashMap<Key, TbHashEntry>;
-
-static mut NUM_WDL: u32 = 0;
-static mut NUM_DTM: u32 = 0;
-static mut NUM_DTZ: u32 = 0;
 
-pub fn init_tb(name: &str) {
-    if !test_tb(&name, WDL_SUFFIX) {
-        return;
+        None
     }
 
-    let has_dtm = test_tb(&name, DTM_SUFFIX);
-    let has_dtz = test_tb(&name, DTZ_SUFFIX);
-
-    let mut pcs = [0; 16];
-    let mut color = 0;
-    for c in name.chars() {
-        match c {
-            'P' => pcs[PAWN.0 as usize | color] += 1,
-            'N' => pcs[KNIGHT.0 as usize | color] += 1,
-            'B' => pcs[BISHOP.0 as usize | color] += 1,
-            'R' => pcs[ROOK.0 as usize | color] += 1,
-            'Q' => pcs[QUEEN.0 as usize | color] += 1,
-            'K' => pcs[KING.0 as usize | color] += 1,
-            'v' => color = 8,
-            _ => {}
-        }
-    }
-
-    let key = calc_key_from_pcs(&pcs, false);
-    let key2 = calc_key_from_pcs(&pcs, true);
-    let symmetric = key == key2;
-
-    let num = pcs.iter().sum::<i32>() as u32;
-    unsafe {
-        if num > MAX_CARDINALITY {
-            MAX_CARDINALITY = num;
-        }
-        if has_dtm && num > MAX_CARDINALITY_DTM {
-            MAX_CARDINALITY_DTM = num;
-        }
-    }
-
-    let mut map = unsafe { Box::from_raw(TB_MAP) };
-
-    let tb_entry;
-
-    if pcs[W_PAWN.0 as usize] + pcs[B_PAWN.0 as usize] == 0 {
-        let entry = PieceEntry {
-            key: key,
-            lock: Mutex::new(()),
-            num: num as u8,
-            symmetric: symmetric,
-            kk_enc: pcs.iter().filter(|&n| *n == 1).count() == 2,
-            has_dtm: has_dtm,
-            has_dtz: has_dtz,
-            wdl: UnsafeCell::new(WdlPiece {
-                mapping: None,
-                ready: AtomicBool::new(false),
-                ei: [EncInfo::new(), EncInfo::new()],
-            }),
-            dtm: UnsafeCell::new(DtmPiece {
-                mapping: None,
-                ready: AtomicBool::new(false),
-                ei: [EncInfo::new(), EncInfo::new()],
-                map: &[],
-                map_idx: [[0; 2]; 2],
-                loss_only: false,
-            }),
-            dtz: UnsafeCell::new(DtzPiece {
-                mapping: None,
-                ready: AtomicBool::new(false),
-                flags: 0,
-                ei: EncInfo::new(),
-                map: &[],
-                map_idx: [0; 4],
-            }),
-        };
-        unsafe {
-            PIECE_ENTRIES.push(entry);
-        }
-        tb_entry = TbHashEntry::Piece(unsafe { PIECE_ENTRIES.len() - 1 });
-    } else {
-        let mut p0 = pcs[W_PAWN.0 as usize];
-        let mut p1 = pcs[B_PAWN.0 as usize];
-        if p1 > 0 && (p0 == 0 || p0 > p1) {
-            std::mem::swap(&mut p0, &mut p1);
-        }
-        let entry = PawnEntry {
-            key: key,
-            lock: Mutex::new(()),
-            num: num as u8,
-            symmetric: symmetric,
-            pawns: [p0 as u8, p1 as u8],
-            has_dtm: has_dtm,
-            has_dtz: has_dtz,
-            wdl: UnsafeCell::new(WdlPawn {
-                mapping: None,
-                ready: AtomicBool::new(false),
-                ei: [
-                    [EncInfo::new(), EncInfo::new()],
-                    [EncInfo::new(), EncInfo::new()],
-                    [EncInfo::new(), EncInfo::new()],
-                    [EncInfo::new(), EncInfo::new()],
-                ],
-            }),
-            dtm: UnsafeCell::new(DtmPawn {
-                mapping: None,
-                ready: AtomicBool::new(false),
-                ei: [
-                    [EncInfo::new(), EncInfo::new()],
-                    [EncInfo::new(), EncInfo::new()],
-                    [EncInfo::new(), EncInfo::new()],
-                    [EncInfo::new(), EncInfo::new()],
-                    [EncInfo::new(), EncInfo::new()],
-                    [EncInfo::new(), EncInfo::new()],
-                ],
-                map: &[],
-                map_idx: [[[0; 2]; 2]; 6],
-                loss_only: false,
-                switched: false,
-            }),
-            dtz: UnsafeCell::new(DtzPawn {
-                mapping: None,
-                ready: AtomicBool::new(false),
-                flags: [0; 4],
-                ei: [
-                    EncInfo::new(),
-                    EncInfo::new(),
-                    EncInfo::new(),
-                    EncInfo::new(),
-                ],
-                map: &[],
-                map_idx: [[0; 4]; 4],
-            }),
-        };
-        unsafe {
-            PAWN_ENTRIES.push(entry);
+    fn map_file(&self, name: &str, suffix: &str) -> Option<Box<Mmap>> {
+        let file = self.open_tb(name, suffix)?;
+        match unsafe { MmapOptions::new().map(&file) } {
+            Ok(mmap) => Some(Box::new(mmap)),
+            Err(err) => {
+                eprintln!("{:?}", err.kind());
+                None
+            }
         }
-        tb_entry = TbHashEntry::Pawn(unsafe { PAWN_ENTRIES.len() - 1 });
     }
 
-    map.insert(key, tb_entry.clone());
-    if key != key2 {
-        map.insert(key2, tb_entry);
-    }
+    // add() registers the table with the given name if its WDL file exists
 
-    unsafe {
-        TB_MAP = Box::into_raw(map);
-        NUM_WDL += 1;
-        NUM_DTM += has_dtm as u32;
-        NUM_DTZ += has_dtz as u32;
-    }
-}
-
-pub fn free() {
-    unsafe {
-        std::mem::drop(Box::from_raw(TB_MAP));
-        PIECE_ENTRIES.free();
-        PAWN_ENTRIES.free();
-    }
-}
+    fn add(&mut self, name: &str) {
+        if !self.test_tb(&name, WDL_SUFFIX) {
+            return;
+        }
 
-pub fn init(path: String) {
-    const P: [char; 5] = ['Q', 'R', 'B', 'N', 'P'];
-    static mut INITIALIZED: bool = false;
+        let has_dtm = self.test_tb(&name, DTM_SUFFIX);
+        let has_dtz = self.test_tb(&name, DTZ_SUFFIX);
 
-    // Restrict engine to 5-piece TBs on platforms with 32-bit address space
-    let max5 = std::mem::size_of::<usize>() < 8;
+        let mut pcs = [0; 16];
+        let mut color = 0;
+        for c in name.chars() {
+            match c {
+                'P' => pcs[PAWN.0 as usize | color] += 1,
+                'N' => pcs[KNIGHT.0 as usize | color] += 1,
+                'B' => pcs[BISHOP.0 as usize | color] += 1,
+                'R' => pcs[ROOK.0 as usize | color] += 1,
+                'Q' => pcs[QUEEN.0 as usize | color] += 1,
+                'K' => pcs[KING.0 as usize | color] += 1,
+                'v' => color = 8,
+                _ => {}
+            }
+        }
 
-    unsafe {
-        if !INITIALIZED {
-            init_indices();
-            PIECE_ENTRIES.init(if max5 { 84 } else { 254 });
-            PAWN_ENTRIES.init(if max5 { 61 } else { 256 });
-            TB_MAP = Box::into_raw(Box::new(HashMap::new()));
-            INITIALIZED = true;
+        let key = calc_key_from_pcs(&pcs, false);
+        let key2 = calc_key_from_pcs(&pcs, true);
+        let symmetric = key == key2;
+
+        let num = pcs.iter().sum::<i32>() as u32;
+        if num > self.max_cardinality {
+            self.max_cardinality = num;
+        }
+        if has_dtm && num > self.max_cardinality_dtm {
+            self.max_cardinality_dtm = num;
+        }
+
+        let tb_entry;
+
+        if pcs[W_PAWN.0 as usize] + pcs[B_PAWN.0 as usize] == 0 {
+            let entry = PieceEntry {
+                key: key,
+                lock: Mutex::new(()),
+                num: num as u8,
+                symmetric: symmetric,
+                kk_enc: pcs.iter().filter(|&n| *n == 1).count() == 2,
+                has_dtm: has_dtm,
+                has_dtz: has_dtz,
+                wdl: UnsafeCell::new(WdlPiece {
+                    mapping: None,
+                    ready: AtomicBool::new(false),
+                    ei: [EncInfo::new(), EncInfo::new()],
+                }),
+                dtm: UnsafeCell::new(DtmPiece {
+                    mapping: None,
+                    ready: AtomicBool::new(false),
+                    ei: [EncInfo::new(), EncInfo::new()],
+                    map: &[],
+                    map_idx: [[0; 2]; 2],
+                    loss_only: false,
+                }),
+                dtz: UnsafeCell::new(DtzPiece {
+                    mapping: None,
+                    ready: AtomicBool::new(false),
+                    flags: 0,
+                    ei: EncInfo::new(),
+                    map: &[],
+                    map_idx: [0; 4],
+                }),
+            };
+            self.piece_entries.push(entry);
+            tb_entry = TbHashEntry::Piece(self.piece_entries.len() - 1);
+        } else {
+            let mut p0 = pcs[W_PAWN.0 as usize];
+            let mut p1 = pcs[B_PAWN.0 as usize];
+            if p1 > 0 && (p0 == 0 || p0 > p1) {
+                std::mem::swap(&mut p0, &mut p1);
+            }
+            let entry = PawnEntry {
+                key: key,
+                lock: Mutex::new(()),
+                num: num as u8,
+                symmetric: symmetric,
+                pawns: [p0 as u8, p1 as u8],
+                has_dtm: has_dtm,
+                has_dtz: has_dtz,
+                wdl: UnsafeCell::new(WdlPawn {
+                    mapping: None,
+                    ready: AtomicBool::new(false),
+                    ei: [
+                        [EncInfo::new(), EncInfo::new()],
+                        [EncInfo::new(), EncInfo::new()],
+                        [EncInfo::new(), EncInfo::new()],
+                        [EncInfo::new(), EncInfo::new()],
+                    ],
+                }),
+                dtm: UnsafeCell::new(DtmPawn {
+                    mapping: None,
+                    ready: AtomicBool::new(false),
+                    ei: [
+                        [EncInfo::new(), EncInfo::new()],
+                        [EncInfo::new(), EncInfo::new()],
+                        [EncInfo::new(), EncInfo::new()],
+                        [EncInfo::new(), EncInfo::new()],
+                        [EncInfo::new(), EncInfo::new()],
+                        [EncInfo::new(), EncInfo::new()],
+                    ],
+                    map: &[],
+                    map_idx: [[[0; 2]; 2]; 6],
+                    loss_only: false,
+                    switched: false,
+                }),
+                dtz: UnsafeCell::new(DtzPawn {
+                    mapping: None,
+                    ready: AtomicBool::new(false),
+                    flags: [0; 4],
+                    ei: [
+                        EncInfo::new(),
+                        EncInfo::new(),
+                        EncInfo::new(),
+                        EncInfo::new(),
+                    ],
+                    map: &[],
+                    map_idx: [[0; 4]; 4],
+                }),
+            };
+            self.pawn_entries.push(entry);
+            tb_entry = TbHashEntry::Pawn(self.pawn_entries.len() - 1);
         }
 
-        if PATH != None {
-            PATH = None;
-            std::mem::drop(Box::from_raw(TB_MAP));
-            TB_MAP = Box::into_raw(Box::new(HashMap::new()));
-            PIECE_ENTRIES.reset();
-            PAWN_ENTRIES.reset();
-            NUM_WDL = 0;
-            NUM_DTM = 0;
-            NUM_DTZ = 0;
-            MAX_CARDINALITY = 0;
-            MAX_CARDINALITY_DTM = 0;
+        self.map.insert(key, tb_entry.clone());
+        if key != key2 {
+            self.map.insert(key2, tb_entry);
         }
-    }
 
-    if path == "" || path == "<empty>" {
-        return;
+        self.num_wdl += 1;
+        self.num_dtm += has_dtm as u32;
+        self.num_dtz += has_dtz as u32;
     }
 
-    unsafe {
-        PATH = Some(path);
-    }
+    // load() registers the tables found in the directories of the path
 
-    for i in 0..5 {
-        init_tb(&format!("K{}vK", P[i]));
-    }
+    fn load(path: &str) -> Tablebases {
+        const P: [char; 5] = ['Q', 'R', 'B', 'N', 'P'];
+
+        // Restrict engine to 5-piece TBs on platforms with 32-bit address space
+        let max5 = std::mem::size_of::<usize>() < 8;
 
-    for i in 0..5 {
-        for j in i..5 {
-            init_tb(&format!("K{}vK{}", P[i], P[j]));
+        let mut tbs = Tablebases::new();
+        tbs.path = String::from(path);
+
+        if path == "" || path == "<empty>" {
+            return tbs;
         }
-    }
 
-    for i in 0..5 {
-        for j in i..5 {
-            init_tb(&format!("K{}{}vK", P[i], P[j]));
+        for i in 0..5 {
+            tbs.add(&format!("K{}vK", P[i]));
         }
-    }
 
-    for i in 0..5 {
-        for j in i..5 {
-            for k in 0..5 {
-                init_tb(&format!("K{}{}vK{}", P[i], P[j], P[k]));
+        for i in 0..5 {
+            for j in i..5 {
+                tbs.add(&format!("K{}vK{}", P[i], P[j]));
             }
         }
-    }
 
-    for i in 0..5 {
-        for j in i..5 {
-            for k in j..5 {
-                init_tb(&format!("K{}{}{}vK", P[i], P[j], P[k]));
+        for i in 0..5 {
+            for j in i..5 {
+                tbs.add(&format!("K{}{}vK", P[i], P[j]));
             }
         }
-    }
 
-    if !max5 {
         for i in 0..5 {
             for j in i..5 {
-                for k in i..5 {
-                    for l in (if i == k { j } else { k })..5 {
-                        init_tb(&format!("K{}{}vK{}{}", P[i], P[j], P[k], P[l]));
-                    }
+                for k in 0..5 {
+                    tbs.add(&format!("K{}{}vK{}", P[i], P[j], P[k]));
                 }
             }
         }
@@ -1090,69 +991,115 @@ pub fn init(path: String) {
         for i in 0..5 {
             for j in i..5 {
                 for k in j..5 {
-                    for l in 0..5 {
-                        init_tb(&format!("K{}{}{}vK{}", P[i], P[j], P[k], P[l]));
-                    }
+                    tbs.add(&format!("K{}{}{}vK", P[i], P[j], P[k]));
                 }
             }
         }
 
-        for i in 0..5 {
-            for j in i..5 {
-                for k in j..5 {
-                    for l in k..5 {
-                        init_tb(&format!("K{}{}{}{}vK", P[i], P[j], P[k], P[l]));
+        if !max5 {
+            for i in 0..5 {
+                for j in i..5 {
+                    for k in i..5 {
+                        for l in (if i == k { j } else { k })..5 {
+                            tbs.add(&format!("K{}{}vK{}{}", P[i], P[j], P[k], P[l]));
+                        }
                     }
                 }
             }
-        }
 
-        for i in 0..5 {
-            for j in i..5 {
-                for k in j..5 {
-                    for l in 0..5 {
-                        for m in l..5 {
-                            init_tb(&format!("K{}{}{}vK{}{}", P[i], P[j], P[k], P[l], P[m]));
+            for i in 0..5 {
+                for j in i..5 {
+                    for k in j..5 {
+                        for l in 0..5 {
+                            tbs.add(&format!("K{}{}{}vK{}", P[i], P[j], P[k], P[l]));
                         }
                     }
                 }
             }
-        }
 
-        for i in 0..5 {
-            for j in i..5 {
-                for k in j..5 {
-                    for l in k..5 {
-                        for m in 0..5 {
-                            init_tb(&format!("K{}{}{}{}vK{}", P[i], P[j], P[k], P[l], P[m]));
+            for i in 0..5 {
+                for j in i..5 {
+                    for k in j..5 {
+                        for l in k..5 {
+                            tbs.add(&format!("K{}{}{}{}vK", P[i], P[j], P[k], P[l]));
                         }
                     }
                 }
             }
-        }
 
-        for i in 0..5 {
-            for j in i..5 {
-                for k in j..5 {
-                    for l in k..5 {
-                        for m in l..5 {
-                            init_tb(&format!("K{}{}{}{}{}vK", P[i], P[j], P[k], P[l], P[m]));
+            for i in 0..5 {
+                for j in i..5 {
+                    for k in j..5 {
+                        for l in 0..5 {
+                            for m in l..5 {
+                                tbs.add(&format!("K{}{}{}vK{}{}", P[i], P[j], P[k], P[l], P[m]));
+                            }
+                        }
+                    }
+                }
+            }
+
+            for i in 0..5 {
+                for j in i..5 {
+                    for k in j..5 {
+                        for l in k..5 {
+                            for m in 0..5 {
+                                tbs.add(&format!("K{}{}{}{}vK{}", P[i], P[j], P[k], P[l], P[m]));
+                            }
+                        }
+                    }
+                }
+            }
+
+            for i in 0..5 {
+                for j in i..5 {
+                    for k in j..5 {
+                        for l in k..5 {
+                            for m in l..5 {
+                                tbs.add(&format!("K{}{}{}{}{}vK", P[i], P[j], P[k], P[l], P[m]));
+                            }
                         }
                     }
                 }
             }
         }
+
+        println!(
+            "info string Found {} WDL, {} DTM and {} DTZ tablebase files.",
+            tbs.num_wdl, tbs.num_dtm, tbs.num_dtz
+        );
+
+        tbs
     }
 
-    println!(
-        "info string Found {} WDL, {} DTM and {} DTZ tablebase files.",
-        unsafe { NUM_WDL },
-        unsafe { NUM_DTM },
-        unsafe { NUM_DTZ }
-    );
+    // place k like pieces on n squares
+}
+
+// The tablebases loaded by the engines, see open()
+static LOADED: Mutex<Vec<Weak<Tablebases>>> = Mutex::new(Vec::new());
+
+// open() returns the tablebases found in the directories of the path. If
+// another engine already uses the same path, its tablebases are shared.
+
+pub fn open(path: &str) -> Arc<Tablebases> {
+    static INIT: Once = Once::new();
+    INIT.call_once(init_indices);
+
+    let mut loaded = LOADED.lock().unwrap();
+    loaded.retain(|tbs| tbs.strong_count() > 0);
+    if let Some(tbs) = loaded
+        .iter()
+        .filter_map(|tbs| tbs.upgrade())
+        .find(|tbs| tbs.path == path)
+    {
+        return tbs;
+    }
+
+    let tbs = Arc::new(Tablebases::load(path));
+    loaded.push(Arc::downgrade(&tbs));
+    tbs
 }
 
-// place k like pieces on n squares
 fn subfactor(k: usize, n: usize) -> usize {
     let mut f = n;
     let mut l = 1;
@@ -1407,8 +1354,8 @@ fn mmap_to_slice(mmap: &Option<Box<Mmap>>) -> &'static [u8] {
     unsafe { slice::from_raw_parts(data.as_ptr(), data.len()) }
 }
 
-fn init_table<T: TbTable>(e: &T::Entry, name: &str) -> bool {
-    let tb_map = map_file(name, T::Type::suffix());
+fn init_table<T: TbTable>(tbs: &Tablebases, e: &T::Entry, name: &str) -> bool {
+    let tb_map = tbs.map_file(name, T::Type::suffix());
     if tb_map.is_none() {
         return false;
     }
@@ -1545,6 +1492,7 @@ fn fill_squares(
 }
 
 fn probe_helper<T: TbTable>(
+    tbs: &Tablebases,
     pos: &Position,
     e: &T::Entry,
     s: <T::Type as TbType>::Select,
@@ -1561,7 +1509,7 @@ fn probe_helper<T: TbTable>(
     if !tb.ready().load(Ordering::Acquire) {
         let _lock = e.lock().lock().unwrap();
         if !tb.ready().load(Ordering::Relaxed) {
-            if !init_table::<T>(e, &prt_str(pos, e.key() != key)) {
+            if !init_table::<T>(tbs, e, &prt_str(pos, e.key() != key)) {
                 *success = 0;
                 return 0;
             }
@@ -1600,7 +1548,7 @@ fn probe_helper<T: TbTable>(
     tb.map(t, bside, res, s)
 }
 
-fn probe_table<T: TbType>(pos: &Position, s: T::Select, success: &mut i32) -> i32 {
+fn probe_table<T: TbType>(tbs: &Tablebases, pos: &Position, s: T::Select, success: &mut i32) -> i32 {
     // Obtain the position's material signature key
     let key = pos.material_key();
 
@@ -1609,26 +1557,18 @@ fn probe_table<T: TbType>(pos: &Position, s: T::Select, success: &mut i32) -> i3
         return 0;
     }
 
-    let mut res = 0;
-    let map = unsafe { Box::from_raw(TB_MAP) };
-
-    match map.get(&key) {
+    match tbs.map.get(&key) {
         None => {
             *success = 0;
+            0
         }
         Some(&TbHashEntry::Piece(idx)) => {
-            let e = unsafe { &PIECE_ENTRIES[idx] };
-            res = probe_helper::<T::PieceTable>(pos, e, s, success);
+            probe_helper::<T::PieceTable>(tbs, pos, &tbs.piece_entries[idx], s, success)
         }
         Some(&TbHashEntry::Pawn(idx)) => {
-            let e = unsafe { &PAWN_ENTRIES[idx] };
-            res = probe_helper::<T::PawnTable>(pos, e, s, success);
+            probe_helper::<T::PawnTable>(tbs, pos, &tbs.pawn_entries[idx], s, success)
         }
     }
-
-    std::mem::forget(map);
-
-    res
 }
 
 // Add underpromotion captures to list of captures.
@@ -1648,7 +1588,7 @@ fn add_underprom_caps(pos: &Position, list: &mut [ExtMove], end: usize) -> usize
     extra
 }
 
-fn probe_ab(pos: &mut Position, mut alpha: i32, beta: i32, success: &mut i32) -> i32 {
+fn probe_ab(tbs: &Tablebases, pos: &mut Position, mut alpha: i32, beta: i32, success: &mut i32) -> i32 {
     assert!(pos.ep_square() == Square::NONE);
 
     let mut list: [ExtMove; 64] = [ExtMove {
@@ -1669,7 +1609,7 @@ fn probe_ab(pos: &mut Position, mut alpha: i32, beta: i32, success: &mut i32) ->
         }
         let gives_check = pos.gives_check(m.m);
         pos.do_move(m.m, gives_check);
-        let v = -probe_ab(pos, -beta, -alpha, success);
+        let v = -probe_ab(tbs, pos, -beta, -alpha, success);
         pos.undo_move(m.m);
         if *success == 0 {
             return 0;
@@ -1682,7 +1622,7 @@ fn probe_ab(pos: &mut Position, mut alpha: i32, beta: i32, success: &mut i32) ->
         }
     }
 
-    let v = probe_table::<Wdl>(pos, (), success);
+    let v = probe_table::<Wdl>(tbs, pos, (), success);
 
     if alpha >= v {
         alpha
@@ -1706,7 +1646,7 @@ fn probe_ab(pos: &mut Position, mut alpha: i32, beta: i32, success: &mut i32) ->
 //  0 : draw
 //  1 : win, but draw under the 50-move rule
 //  2 : win
-pub fn probe_wdl(pos: &mut Position, success: &mut i32) -> i32 {
+pub fn probe_wdl(tbs: &Tablebases, pos: &mut Position, success: &mut i32) -> i32 {
     // Generate (at least) all legal en-passant captures
     let mut list: [ExtMove; 64] = [ExtMove {
         m: Move::NONE,
@@ -1729,7 +1669,7 @@ pub fn probe_wdl(pos: &mut Position, success: &mut i32) -> i32 {
         }
         let gives_check = pos.gives_check(m.m);
         pos.do_move(m.m, gives_check);
-        let v = -probe_ab(pos, -2, -best_cap, success);
+        let v = -probe_ab(tbs, pos, -2, -best_cap, success);
         pos.undo_move(m.m);
         if *success == 0 {
             return 0;
@@ -1747,7 +1687,7 @@ pub fn probe_wdl(pos: &mut Position, success: &mut i32) -> i32 {
         }
     }
 
-    let v = probe_table::<Wdl>(pos, (), success);
+    let v = probe_table::<Wdl>(tbs, pos, (), success);
     if *success == 0 {
         return 0;
     }
@@ -1803,7 +1743,7 @@ pub fn probe_wdl(pos: &mut Position, success: &mut i32) -> i32 {
 
 // Probe a position known to lose by probing the DTM table and looking
 // at captures.
-fn probe_dtm_loss(pos: &mut Position, success: &mut i32) -> Value {
+fn probe_dtm_loss(tbs: &Tablebases, pos: &mut Position, success: &mut i32) -> Value {
     let mut best = -Value::INFINITE;
     let mut num_ep = 0;
 
@@ -1830,7 +1770,7 @@ fn probe_dtm_loss(pos: &mut Position, success: &mut i32) -> Value {
         let gives_check = pos.gives_check(m.m);
         pos.do_move(m.m, gives_check);
 
-        let v = -probe_dtm_win(pos, success) + 1;
+        let v = -probe_dtm_win(tbs, pos, success) + 1;
 
         pos.undo_move(m.m);
 
@@ -1846,11 +1786,11 @@ fn probe_dtm_loss(pos: &mut Position, success: &mut i32) -> Value {
         return best;
     }
 
-    let v = -Value::MATE + 2 * probe_table::<Dtm>(pos, false, success);
+    let v = -Value::MATE + 2 * probe_table::<Dtm>(tbs, pos, false, success);
     std::cmp::max(best, v)
 }
 
-fn probe_dtm_win(pos: &mut Position, success: &mut i32) -> Value {
+fn probe_dtm_win(tbs: &Tablebases, pos: &mut Position, success: &mut i32) -> Value {
     let mut best = -Value::INFINITE;
 
     // Generate all moves
@@ -1874,12 +1814,12 @@ fn probe_dtm_win(pos: &mut Position, success: &mut i32) -> Value {
         pos.do_move(m.m, gives_check);
 
         let wdl = if pos.ep_square() != Square::NONE {
-            probe_wdl(pos, success)
+            probe_wdl(tbs, pos, success)
         } else {
-            probe_ab(pos, -1, 0, success)
+            probe_ab(tbs, pos, -1, 0, success)
         };
         let v = if wdl < 0 && *success != 0 {
-            -probe_dtm_loss(pos, success) - 1
+            -probe_dtm_loss(tbs, pos, success) - 1
         } else {
             -Value::INFINITE
         };
@@ -1895,13 +1835,13 @@ fn probe_dtm_win(pos: &mut Position, success: &mut i32) -> Value {
     best
 }
 
-pub fn probe_dtm(pos: &mut Position, wdl: i32, success: &mut i32) -> Value {
+pub fn probe_dtm(tbs: &Tablebases, pos: &mut Position, wdl: i32, success: &mut i32) -> Value {
     debug_assert!(wdl != 0);
 
     if wdl > 0 {
-        probe_dtm_win(pos, success)
+        probe_dtm_win(tbs, pos, success)
     } else {
-        probe_dtm_loss(pos, success)
+        probe_dtm_loss(tbs, pos, success)
     }
 }
 
@@ -1935,8 +1875,8 @@ const WDL_TO_DTZ: [i32; 5] = [-1, -101, 0, 101, 1];
 // In short, if a move is available resulting in dtz + 50-move-counter <= 99,
 // then do not accept moves leading to dtz + 50-move-counter == 100.
 //
-pub fn probe_dtz(pos: &mut Position, success: &mut i32) -> i32 {
-    let wdl = probe_wdl(pos, success);
+pub fn probe_dtz(tbs: &Tablebases, pos: &mut Position, success: &mut i32) -> i32 {
+    let wdl = probe_wdl(tbs, pos, success);
     if *success == 0 {
         return 0;
     }
@@ -1971,7 +1911,7 @@ pub fn probe_dtz(pos: &mut Position, success: &mut i32) -> i32 {
             }
             let gives_check = pos.gives_check(m.m);
             pos.do_move(m.m, gives_check);
-            let v = -probe_wdl(pos, success);
+            let v = -probe_wdl(tbs, pos, success);
             pos.undo_move(m.m);
             if *success == 0 {
                 return 0;
@@ -1987,7 +1927,7 @@ pub fn probe_dtz(pos: &mut Position, success: &mut i32) -> i32 {
     // the position without ep rights. It is therefore safe to probe the
     // DTZ table with the current value of wdl.
 
-    let dtz = probe_table::<Dtz>(pos, wdl, success);
+    let dtz = probe_table::<Dtz>(tbs, pos, wdl, success);
     if *success >= 0 {
         return WDL_TO_DTZ[(wdl + 2) as usize] + if wdl > 0 { dtz } else { -dtz };
     }
@@ -2019,7 +1959,7 @@ pub fn probe_dtz(pos: &mut Position, success: &mut i32) -> i32 {
         }
         let gives_check = pos.gives_check(m.m);
         pos.do_move(m.m, gives_check);
-        let v = -probe_dtz(pos, success);
+        let v = -probe_dtz(tbs, pos, success);
         pos.undo_move(m.m);
         if *success == 0 {
             return 0;
@@ -2040,7 +1980,7 @@ pub fn probe_dtz(pos: &mut Position, success: &mut i32) -> i32 {
 
 // Use the DTZ tables to rank and score all root moves in the list.
 // A return value of false means that not all probes were successful.
-fn root_probe_dtz(pos: &mut Position, root_moves: &mut RootMoves) -> bool {
+fn root_probe_dtz(tbs: &Tablebases, pos: &mut Position, root_moves: &mut RootMoves, config: &TbConfig) -> bool {
     let mut success = 1;
 
     // Obtain 50-move counter for the root position
@@ -2053,7 +1993,7 @@ fn root_probe_dtz(pos: &mut Position, root_moves: &mut RootMoves) -> bool {
 
     // The border between draw and win lies at rank 1 or rank 900, depending
     // on whether the 50-move rule is used
-    let bound = if ucioption::get_bool("Syzygy50MoveRule") {
+    let bound = if config.use_rule_50 {
         900
     } else {
         1
@@ -2069,11 +2009,11 @@ fn root_probe_dtz(pos: &mut Position, root_moves: &mut RootMoves) -> bool {
         let mut v;
         if pos.rule50_count() == 0 {
             // If the move resets the 50-move counter, dtz is -10/-1/0/1/101
-            v = -probe_wdl(pos, &mut success);
+            v = -probe_wdl(tbs, pos, &mut success);
             v = WDL_TO_DTZ[(v + 2) as usize];
         } else {
             // Otherwise, take dtz for the new position and correct by 1 ply
-            v = -probe_dtz(pos, &mut success);
+            v = -probe_dtz(tbs, pos, &mut success);
             if v > 0 {
                 v += 1;
             } else if v < 0 {
@@ -2134,7 +2074,7 @@ fn root_probe_dtz(pos: &mut Position, root_moves: &mut RootMoves) -> bool {
 // Use the WDL tables to rank all root moves in the list.
 // This is a fallback for the case that some or all DTZ tables are missing.
 // A return value of false means that not all probes were successful.
-fn root_probe_wdl(pos: &mut Position, root_moves: &mut RootMoves) -> bool {
+fn root_probe_wdl(tbs: &Tablebases, pos: &mut Position, root_moves: &mut RootMoves, config: &TbConfig) -> bool {
     const WDL_TO_RANK: [i32; 5] = [-1000, -899, 0, 899, 1000];
     const WDL_TO_VALUE: [Value; 5] = [
         Value(-32000 + 128 + 1), Value(-2), Value(0), Value(2),
@@ -2144,7 +2084,7 @@ fn root_probe_wdl(pos: &mut Position, root_moves: &mut RootMoves) -> bool {
     ];
 
     let mut success = 1;
-    let move50 = ucioption::get_bool("Syzygy50MoveRule");
+    let move50 = config.use_rule_50;
 
     // Probe, rank and score each move
     for ref mut rm in root_moves.iter_mut() {
@@ -2152,7 +2092,7 @@ fn root_probe_wdl(pos: &mut Position, root_moves: &mut RootMoves) -> bool {
         let gives_check = pos.gives_check(m);
         pos.do_move(m, gives_check);
 
-        let mut v = -probe_wdl(pos, &mut success);
+        let mut v = -probe_wdl(tbs, pos, &mut success);
 
         pos.undo_move(m);
 
@@ -2178,7 +2118,7 @@ fn root_probe_wdl(pos: &mut Position, root_moves: &mut RootMoves) -> bool {
 // Use the DTM tables to find mate scores.
 // Either DTZ or WDL must have been probed successfully earlier.
 // A return value of 0 means that not all probes were successful.
-fn root_probe_dtm(pos: &mut Position, root_moves: &mut RootMoves) -> bool {
+fn root_probe_dtm(tbs: &Tablebases, pos: &mut Position, root_moves: &mut RootMoves) -> bool {
     let mut success = 1;
 
     let mut tmp_score = Vec::new();
@@ -2199,7 +2139,7 @@ fn root_probe_dtm(pos: &mut Position, root_moves: &mut RootMoves) -> bool {
         } else {
             let gives_check = pos.gives_check(rm.pv[0]);
             pos.do_move(rm.pv[0], gives_check);
-            let v = -probe_dtm(pos, -wdl, &mut success);
+            let v = -probe_dtm(tbs, pos, -wdl, &mut success);
             pos.undo_move(rm.pv[0]);
             if success == 0 {
                 return false;
@@ -2222,7 +2162,8 @@ fn root_probe_dtm(pos: &mut Position, root_moves: &mut RootMoves) -> bool {
     true
 }
 
-pub fn expand_mate(pos: &mut Position, idx: usize) {
+pub fn expand_mate(pos: &mut Position, idx: usize, config: &TbConfig) {
+    let tbs = &*config.tables;
     let mut success = 1;
     let mut chk = 0;
 
@@ -2239,7 +2180,7 @@ pub fn expand_mate(pos: &mut Position, idx: usize) {
     }
 
     // Now try to expand until the actual mate
-    if popcount(pos.pieces()) <= cardinality_dtm() {
+    if popcount(pos.pieces()) <= config.cardinality_dtm {
         while v != -Value::MATE {
             v = if v > Value::ZERO { -v - 1 } else { -v + 1 };
             wdl = -wdl;
@@ -2249,10 +2190,10 @@ pub fn expand_mate(pos: &mut Position, idx: usize) {
                 pos.do_move(m, gives_check);
                 if wdl < 0 {
                     // We must check that the move is winning
-                    chk = probe_wdl(pos, &mut success);
+                    chk = probe_wdl(tbs, pos, &mut success);
                 }
                 let w = if success != 0 && (wdl > 0 || chk < 0) {
-                    probe_dtm(pos, wdl, &mut success)
+                    probe_dtm(tbs, pos, wdl, &mut success)
                 } else {
                     Value::ZERO
                 };
@@ -2281,26 +2222,43 @@ pub fn expand_mate(pos: &mut Position, idx: usize) {
     }
 }
 
-pub fn rank_root_moves(pos: &mut Position, root_moves: &mut RootMoves) {
+// root_wdl() returns the win, draw and loss probabilities in per mille of
+// a root move ranked by the tablebases. They are exact: the outcome is
+// known, except that with the 50-move rule cursed wins and blessed losses
+// count as draws. As in root_probe_dtm(), a score above a pawn means a win.
+
+pub fn root_wdl(rm: &RootMove) -> (i32, i32, i32) {
+    if rm.tb_score > PawnValueEg {
+        (1000, 0, 0)
+    } else if rm.tb_score < -PawnValueEg {
+        (0, 0, 1000)
+    } else {
+        (0, 1000, 0)
+    }
+}
+
+pub fn rank_root_moves(pos: &mut Position, root_moves: &mut RootMoves, config: &mut TbConfig) {
+    let tables = config.tables.clone();
+    let tbs = &*tables;
     let mut root_in_tb = false;
     let mut dtz_available = true;
     let mut dtm_available = false;
 
-    if cardinality() >= popcount(pos.pieces()) && !pos.has_castling_right(ANY_CASTLING) {
+    if config.cardinality >= popcount(pos.pieces()) && !pos.has_castling_right(ANY_CASTLING) {
         // Try to rank moves using DTZ tables
-        root_in_tb = root_probe_dtz(pos, root_moves);
+        root_in_tb = root_probe_dtz(tbs, pos, root_moves, config);
 
         if !root_in_tb {
             // DTZ tables are missing
             dtz_available = false;
 
             // Try to rank moves using WDL tables as fallback
-            root_in_tb = root_probe_wdl(pos, root_moves);
+            root_in_tb = root_probe_wdl(tbs, pos, root_moves, config);
         }
 
         // If ranking was successful, try to obtain mate values from DTM tables
-        if root_in_tb && cardinality_dtm() >= popcount(pos.pieces()) {
-            dtm_available = root_probe_dtm(pos, root_moves);
+        if root_in_tb && config.cardinality_dtm >= popcount(pos.pieces()) {
+            dtm_available = root_probe_dtm(tbs, pos, root_moves);
         }
     }
 
@@ -2312,9 +2270,7 @@ pub fn rank_root_moves(pos: &mut Position, root_moves: &mut RootMoves) {
         // Probe during search only if neither DTM nor DTZ is available
         // and we are winning.
         if dtm_available || dtz_available || root_moves[0].tb_rank <= 0 {
-            unsafe {
-                CARDINALITY = 0;
-            }
+            config.cardinality = 0;
         }
     } else {
         // Ranking was not successful, clean up
@@ -2323,9 +2279,7 @@ pub fn rank_root_moves(pos: &mut Position, root_moves: &mut RootMoves) {
         }
     }
 
-    unsafe {
-        ROOT_IN_TB = root_in_tb;
-    }
+    config.root_in_tb = root_in_tb;
 }
 
 const OFF_DIAG: [i8; 64] = [
@@ -0,0 +1,676 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+// Test suites. A test suite is an EPD file: each line holds the first four
+// fields of a FEN string followed by operations, an opcode with its
+// operands ended by a semicolon, e.g.
+//
+//   r1b1k2r/ppppnppp/2n2q2/2b5/3NP3/2P1B3/PP3PPP/RN1QKB1R w KQkq - bm Nf5; id "ECM.001";
+//
+// Each position is searched with the given limit, starting from an empty
+// hash table. A position is solved when the move played is one of the
+// best moves (bm) and none of the moves to avoid (am). Positions with
+// neither take the first move of the predicted variation (pv) as the best
+// move. Moves may be given in SAN or coordinate notation, and a position
+// with a move that is not legal is reported and skipped. For a solved
+// position, the depth and time at which the engine settled on a right move
+// are reported as well.
+//
+// Positional suites such as the Strategic Test Suite (STS) also give
+// partial credit: the c0 operation lists the points of several moves, e.g.
+//
+//   ... bm Nf5; id "STS(v1.0) Undermine.001"; c0 "Nf5=10, Qd5=3, Rxc5=2";
+//
+// and the score of a suite is the sum of the points of the moves played.
+// Several suites, e.g. the theme files of STS, can be run at once. Their
+// results are summarized per file and can be written to a CSV file, one
+// row per position.
+
+use engine::Engine;
+use listener::*;
+use misc;
+use notation;
+use position::Position;
+use search::LimitsType;
+use types::*;
+
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Epd is a position of an EPD file with its operations. Quotes around
+/// operands are removed.
+#[derive(Clone, Debug)]
+pub struct Epd {
+    pub fen: String,
+    pub ops: Vec<(String, String)>,
+}
+
+impl Epd {
+    // parse() reads an EPD line. Move counters are taken from the hmvc and
+    // fmvn operations, or from the line itself if it holds a full FEN
+    // string.
+
+    pub fn parse(line: &str) -> Option<Epd> {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if tokens.len() < 4 {
+            return None;
+        }
+
+        let mut fields = 4;
+        let mut counters = None;
+        if tokens.len() >= 6 && tokens[4].parse::<u32>().is_ok() && tokens[5].parse::<u32>().is_ok()
+        {
+            fields = 6;
+            counters = Some(tokens[4..6].join(" "));
+        }
+
+        // Skip the FEN fields of the line, keeping the rest as it is
+        let mut rest = line.trim_start();
+        for _ in 0..fields {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            rest = rest[end..].trim_start();
+        }
+
+        let mut epd = Epd {
+            fen: tokens[..4].join(" "),
+            ops: parse_ops(rest),
+        };
+        let counters = counters.unwrap_or_else(|| {
+            format!(
+                "{} {}",
+                epd.op("hmvc").unwrap_or("0"),
+                epd.op("fmvn").unwrap_or("1")
+            )
+        });
+        epd.fen = format!("{} {}", epd.fen, counters);
+
+        Some(epd)
+    }
+
+    // op() returns the operands of the first operation with the given
+    // opcode
+
+    pub fn op(&self, opcode: &str) -> Option<&str> {
+        self.ops
+            .iter()
+            .find(|op| op.0 == opcode)
+            .map(|op| op.1.as_str())
+    }
+
+    // points() returns the moves and points listed by the c0 operation,
+    // e.g. "Nf5=10, Qd5=3". A comment without '=' gives no points, and an
+    // item that is not a legal move with a number of points is an error.
+
+    pub fn points(&self, pos: &mut Position) -> Result<Vec<(Move, i32)>, String> {
+        let comment = match self.op("c0") {
+            Some(comment) if comment.contains('=') => comment,
+            _ => return Ok(Vec::new()),
+        };
+
+        let mut points = Vec::new();
+        for item in comment.split(',') {
+            let mut parts = item.split('=');
+            let (m, n) = match (parts.next(), parts.next(), parts.next()) {
+                (Some(m), Some(n), None) => {
+                    (notation::from_san(pos, m.trim()), n.trim().parse().ok())
+                }
+                _ => (Move::NONE, None),
+            };
+            match n {
+                Some(n) if m != Move::NONE => points.push((m, n)),
+                _ => return Err(format!("bad item in c0: {}", item.trim())),
+            }
+        }
+
+        Ok(points)
+    }
+
+    // moves() converts the operands of an operation to moves. An operand
+    // that is not a legal move is an error.
+
+    pub fn moves(&self, pos: &mut Position, opcode: &str) -> Result<Vec<Move>, String> {
+        match self.op(opcode) {
+            Some(operands) => operands
+                .split_whitespace()
+                .map(|s| operand_move(pos, opcode, s))
+                .collect(),
+            None => Ok(Vec::new()),
+        }
+    }
+}
+
+// operand_move() converts an operand of an operation to a legal move
+
+fn operand_move(pos: &mut Position, opcode: &str, s: &str) -> Result<Move, String> {
+    match notation::from_san(pos, s) {
+        Move::NONE => Err(format!("illegal move in {}: {}", opcode, s)),
+        m => Ok(m),
+    }
+}
+
+// parse_ops() splits the operations of an EPD line. Semicolons within
+// quotes do not end an operation.
+
+fn parse_ops(s: &str) -> Vec<(String, String)> {
+    let mut ops = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+
+    for c in s.chars().chain(Some(';')) {
+        match c {
+            '"' => quoted = !quoted,
+            ';' if !quoted => {
+                let op = current.trim();
+                if let Some(end) = op.find(char::is_whitespace) {
+                    ops.push((String::from(&op[..end]), String::from(op[end..].trim())));
+                } else if !op.is_empty() {
+                    ops.push((String::from(op), String::new()));
+                }
+                current.clear();
+            }
+            _ => current.push(c),
+        }
+    }
+
+    ops
+}
+
+// load() reads the positions of an EPD file
+
+pub fn load(file_name: &str) -> Result<Vec<Epd>, String> {
+    let file = File::open(file_name).map_err(|e| format!("Unable to open {}: {}", file_name, e))?;
+    let mut epds = Vec::new();
+
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(|e| format!("Unable to read {}: {}", file_name, e))?;
+        if line.trim_start().starts_with('#') {
+            continue;
+        }
+        if let Some(epd) = Epd::parse(&line) {
+            epds.push(epd);
+        }
+    }
+
+    Ok(epds)
+}
+
+// Recorder keeps the best move of every iteration, with its depth and the
+// time it was found at
+
+struct Recorder {
+    iterations: Mutex<Vec<(i32, i64, Move)>>,
+}
+
+impl SearchListener for Recorder {
+    fn on_iteration(&self, _pos: &Position, depth: i32, lines: &[PvLine], stats: &SearchStats) {
+        if let Some(&m) = lines.first().and_then(|line| line.pv.first()) {
+            self.iterations.lock().unwrap().push((depth, stats.time, m));
+        }
+    }
+}
+
+/// TestResult is the outcome of the search of one position of a suite.
+/// found is the depth and time in milliseconds from which the engine kept
+/// playing a right move, if it solved the position. points holds the
+/// points scored and the most that could be scored, if the position lists
+/// points.
+#[derive(Clone, Debug)]
+pub struct TestResult {
+    pub file: String,
+    pub id: String,
+    pub best: Move,
+    // The move played in SAN
+    pub san: String,
+    pub solved: bool,
+    pub found: Option<(i32, i64)>,
+    pub points: Option<(i32, i32)>,
+    pub depth: i32,
+    pub nodes: u64,
+}
+
+// run_position() searches one position and checks the move played against
+// the operations of the EPD. It returns None if there is nothing to check.
+
+fn run_position(
+    engine: &mut Engine,
+    recorder: &Recorder,
+    epd: &Epd,
+    limits: &LimitsType,
+) -> Result<Option<TestResult>, String> {
+    let chess960 = engine.options().get_bool("UCI_Chess960");
+    let mut pos = Box::new(
+        Position::from_fen(&epd.fen, chess960)
+            .map_err(|e| format!("invalid position: {}", e))?,
+    );
+
+    // A position with bad points is still run, without them
+    let points = epd.points(&mut pos).unwrap_or_else(|e| {
+        println!("info string {}: {}", epd.op("id").unwrap_or(&epd.fen), e);
+        Vec::new()
+    });
+    let max_points = points.iter().map(|p| p.1).max();
+    let mut bm = epd.moves(&mut pos, "bm")?;
+    let am = epd.moves(&mut pos, "am")?;
+    if bm.is_empty() && am.is_empty() {
+        // Only the first move of the variation is played in this position
+        if let Some(s) = epd.op("pv").and_then(|pv| pv.split_whitespace().next()) {
+            bm.push(operand_move(&mut pos, "pv", s)?);
+        }
+    }
+    if bm.is_empty() && am.is_empty() {
+        // The moves with the most points are the best ones
+        bm = points
+            .iter()
+            .filter(|p| Some(p.1) == max_points)
+            .map(|p| p.0)
+            .collect();
+        if bm.is_empty() {
+            return Ok(None);
+        }
+    }
+    let right = |m: Move| (bm.is_empty() || bm.contains(&m)) && !am.contains(&m);
+
+    engine.set_position(&epd.fen, &[]).map_err(|e| e.to_string())?;
+    engine.new_game();
+    recorder.iterations.lock().unwrap().clear();
+    let mut limits = limits.clone();
+    limits.start_time = Some(Instant::now());
+    let outcome = engine.go(&limits, Vec::new(), false).wait();
+
+    // The position was solved from the last iteration on which a wrong
+    // move was preferred
+    let solved = right(outcome.best);
+    let mut found = None;
+    if solved {
+        for &(depth, time, m) in recorder.iterations.lock().unwrap().iter() {
+            if !right(m) {
+                found = None;
+            } else if found.is_none() {
+                found = Some((depth, time));
+            }
+        }
+    }
+
+    Ok(Some(TestResult {
+        file: String::new(),
+        id: String::from(epd.op("id").unwrap_or("")),
+        best: outcome.best,
+        san: san(&mut pos, outcome.best),
+        solved,
+        // Without iterations, e.g. in a position with a single legal move,
+        // the move is taken as found at the end
+        found: if solved {
+            found.or(Some((outcome.depth, 0)))
+        } else {
+            None
+        },
+        points: max_points.map(|max| {
+            let scored = points.iter().find(|p| p.0 == outcome.best).map_or(0, |p| p.1);
+            (scored, max)
+        }),
+        depth: outcome.depth,
+        nodes: outcome.nodes,
+    }))
+}
+
+// run_file() runs the positions of one suite and prints their results.
+// Positions without anything to check are skipped.
+
+fn run_file(
+    engine: &mut Engine,
+    recorder: &Recorder,
+    file_name: &str,
+    epds: &[Epd],
+    limits: &LimitsType,
+) -> Vec<TestResult> {
+    let mut results = Vec::new();
+
+    for (i, epd) in epds.iter().enumerate() {
+        let mut result = match run_position(engine, recorder, epd, limits) {
+            Ok(Some(result)) => result,
+            Ok(None) => {
+                println!("{:4} {}: skipped, no bm, am, pv or points", i + 1, epd.fen);
+                continue;
+            }
+            Err(e) => {
+                println!("{:4} {}: skipped, {}", i + 1, epd.fen, e);
+                continue;
+            }
+        };
+        result.file = String::from(file_name);
+        if result.id.is_empty() {
+            result.id = epd.fen.clone();
+        }
+
+        let expected = ["bm", "am", "pv"]
+            .iter()
+            .filter_map(|&op| epd.op(op).map(|s| format!("{} {}", op, s)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let points = match result.points {
+            Some((scored, max)) => format!(", {}/{} points", scored, max),
+            None => String::new(),
+        };
+
+        match result.found {
+            Some((depth, time)) => println!(
+                "{:4} {}: solved, {} ({}) found at depth {} in {} ms{}",
+                i + 1,
+                result.id,
+                result.san,
+                expected,
+                depth,
+                time,
+                points
+            ),
+            None => println!(
+                "{:4} {}: unsolved, {} ({}) depth {}{}",
+                i + 1,
+                result.id,
+                result.san,
+                expected,
+                result.depth,
+                points
+            ),
+        }
+
+        results.push(result);
+    }
+
+    results
+}
+
+fn san(pos: &mut Position, m: Move) -> String {
+    if m == Move::NONE {
+        String::from("(none)")
+    } else {
+        notation::to_san(pos, m)
+    }
+}
+
+// Totals sums up the results of a suite
+
+struct Totals {
+    positions: usize,
+    solved: usize,
+    points: i32,
+    max_points: i32,
+}
+
+impl Totals {
+    fn new(results: &[&TestResult]) -> Totals {
+        Totals {
+            positions: results.len(),
+            solved: results.iter().filter(|r| r.solved).count(),
+            points: results.iter().filter_map(|r| r.points).map(|p| p.0).sum(),
+            max_points: results.iter().filter_map(|r| r.points).map(|p| p.1).sum(),
+        }
+    }
+
+    fn solved(&self) -> String {
+        format!(
+            "{} of {} ({:.1} %)",
+            self.solved,
+            self.positions,
+            100.0 * self.solved as f64 / self.positions.max(1) as f64
+        )
+    }
+
+    fn score(&self) -> String {
+        format!(
+            "{} of {} ({:.1} %)",
+            self.points,
+            self.max_points,
+            100.0 * self.points as f64 / self.max_points.max(1) as f64
+        )
+    }
+}
+
+// csv_field() quotes a CSV field if needed
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        String::from(s)
+    }
+}
+
+// write_csv() writes one row per position, with the name of the engine so
+// that the files of several versions can be put together
+
+fn write_csv(file_name: &str, results: &[TestResult]) -> io::Result<()> {
+    let name = misc::engine_info(true);
+    let name = name.lines().next().unwrap();
+    let mut out = BufWriter::new(File::create(file_name)?);
+
+    writeln!(
+        out,
+        "engine,file,id,move,solved,points,max_points,depth,found_depth,found_time,nodes"
+    )?;
+    for r in results {
+        let (points, max_points) = match r.points {
+            Some((scored, max)) => (scored.to_string(), max.to_string()),
+            None => (String::new(), String::new()),
+        };
+        let (found_depth, found_time) = match r.found {
+            Some((depth, time)) => (depth.to_string(), time.to_string()),
+            None => (String::new(), String::new()),
+        };
+        writeln!(
+            out,
+            "{},{},{},{},{},{},{},{},{},{},{}",
+            csv_field(name),
+            csv_field(&r.file),
+            csv_field(&r.id),
+            csv_field(&r.san),
+            r.solved as i32,
+            points,
+            max_points,
+            r.depth,
+            found_depth,
+            found_time,
+            r.nodes
+        )?;
+    }
+
+    out.flush()
+}
+
+// run() runs one or more test suites, printing the result of every
+// position, a summary per suite and a total. The listener of the engine is
+// restored afterwards.
+
+pub fn run(
+    engine: &mut Engine,
+    file_names: &[String],
+    limits: &LimitsType,
+    csv: Option<&str>,
+) -> Vec<TestResult> {
+    let mut suites = Vec::new();
+    for file_name in file_names {
+        match load(file_name) {
+            Ok(epds) => suites.push((file_name, epds)),
+            Err(e) => {
+                println!("info string {}", e);
+                return Vec::new();
+            }
+        }
+    }
+
+    let listener = engine.listener();
+    let recorder = Arc::new(Recorder {
+        iterations: Mutex::new(Vec::new()),
+    });
+    engine.set_listener(recorder.clone());
+
+    let start = Instant::now();
+    let mut results = Vec::new();
+    for &(file_name, ref epds) in suites.iter() {
+        if suites.len() > 1 {
+            println!("\n{}", file_name);
+        }
+        results.extend(run_file(engine, &recorder, file_name, epds, limits));
+    }
+
+    engine.set_listener(listener);
+
+    let all: Vec<&TestResult> = results.iter().collect();
+    let totals = Totals::new(&all);
+    let solved: Vec<&TestResult> = results.iter().filter(|r| r.solved).collect();
+    let nodes: u64 = results.iter().map(|r| r.nodes).sum();
+    let elapsed = start.elapsed().as_millis() as u64 + 1;
+
+    println!("\n===========================");
+    if suites.len() > 1 {
+        for &(file_name, _) in suites.iter() {
+            let file: Vec<&TestResult> = results.iter().filter(|r| &r.file == file_name).collect();
+            let t = Totals::new(&file);
+            print!("{:<24}: solved {}", file_name, t.solved());
+            if t.max_points > 0 {
+                print!(", score {}", t.score());
+            }
+            println!();
+        }
+        println!();
+    }
+    println!("Solved          : {}", totals.solved());
+    if totals.max_points > 0 {
+        println!("Score           : {}", totals.score());
+    }
+    if !solved.is_empty() {
+        let time: i64 = solved.iter().map(|r| r.found.unwrap().1).sum();
+        println!("Avg solve time  : {} ms", time / solved.len() as i64);
+    }
+    println!("Total time (ms) : {}", elapsed);
+    println!("Nodes searched  : {}", nodes);
+    println!("Nodes/second    : {}", nodes * 1000 / elapsed);
+
+    if let Some(csv) = csv {
+        if let Err(e) = write_csv(csv, &results) {
+            println!("info string Unable to write {}: {}", csv, e);
+        }
+    }
+
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_operations() {
+        let epd = Epd::parse(
+            "r1b1k2r/ppppnppp/2n2q2/2b5/3NP3/2P1B3/PP3PPP/RN1QKB1R w KQkq - \
+             bm Nf5; id \"ECM;001\"; c0 \"a; b\";noop;",
+        )
+        .unwrap();
+        assert_eq!(
+            epd.fen,
+            "r1b1k2r/ppppnppp/2n2q2/2b5/3NP3/2P1B3/PP3PPP/RN1QKB1R w KQkq - 0 1"
+        );
+        assert_eq!(epd.op("bm"), Some("Nf5"));
+        assert_eq!(epd.op("id"), Some("ECM;001"));
+        assert_eq!(epd.op("c0"), Some("a; b"));
+        assert_eq!(epd.op("noop"), Some(""));
+        assert_eq!(epd.op("am"), None);
+
+        assert!(Epd::parse("8/8/8/8 w -").is_none());
+    }
+
+    #[test]
+    fn parse_counters() {
+        let fen = "4k3/8/8/8/8/8/8/4K2R w K -";
+
+        let epd = Epd::parse(&format!("{} hmvc 12; fmvn 34; bm O-O;", fen)).unwrap();
+        assert_eq!(epd.fen, format!("{} 12 34", fen));
+
+        // Counters on the line itself are not taken as operations and win
+        // over hmvc and fmvn
+        let epd = Epd::parse(&format!("{} 5 20 bm O-O; hmvc 12;", fen)).unwrap();
+        assert_eq!(epd.fen, format!("{} 5 20", fen));
+        assert_eq!(epd.op("bm"), Some("O-O"));
+        assert_eq!(epd.ops.len(), 2);
+    }
+
+    #[test]
+    fn operand_moves() {
+        Engine::new();
+
+        let epd = Epd::parse(
+            "r1b1k2r/ppppnppp/2n2q2/2b5/3NP3/2P1B3/PP3PPP/RN1QKB1R w KQkq - \
+             bm Nf5 Nxc6!; am Qd2; pv d4f5 e7f5;",
+        )
+        .unwrap();
+        let mut pos = Position::from_fen(&epd.fen, false).unwrap();
+        let d4 = Square(27);
+
+        let bm = epd.moves(&mut pos, "bm").unwrap();
+        assert_eq!(bm, [Move::make(d4, Square(37)), Move::make(d4, Square(42))]);
+        let am = epd.moves(&mut pos, "am").unwrap();
+        assert_eq!(am, [Move::make(Square(3), Square(11))]);
+        assert_eq!(epd.moves(&mut pos, "id"), Ok(Vec::new()));
+
+        // Only the first move of a variation is legal in the position
+        assert_eq!(
+            epd.moves(&mut pos, "pv"),
+            Err(String::from("illegal move in pv: e7f5"))
+        );
+
+        let epd = Epd::parse(&format!("{} bm Nf5 Nf7;", epd.fen)).unwrap();
+        assert_eq!(
+            epd.moves(&mut pos, "bm"),
+            Err(String::from("illegal move in bm: Nf7"))
+        );
+    }
+    #[test]
+    fn sts_points() {
+        let mut engine = Engine::new();
+
+        // The first position of STS 1, Undermine
+        let line = "1kr5/3n4/q3p2p/p2n2p1/PppB1P2/5BP1/1P2Q2P/3R2K1 w - - bm f5; \
+                    id \"Undermine.001\"; c0 \"f5=10, Be5+=2, Bf2=3, Bg4=2\";";
+        let epd = Epd::parse(line).unwrap();
+        let mut pos = Position::from_fen(&epd.fen, false).unwrap();
+        let (d4, f3, f4) = (Square(27), Square(21), Square(29));
+        assert_eq!(
+            epd.points(&mut pos),
+            Ok(vec![
+                (Move::make(f4, Square(37)), 10),
+                (Move::make(d4, Square(36)), 2),
+                (Move::make(d4, Square(13)), 3),
+                (Move::make(f3, Square(30)), 2),
+            ])
+        );
+
+        let mut limits = LimitsType::new();
+        limits.depth = 1;
+        let recorder = Arc::new(Recorder {
+            iterations: Mutex::new(Vec::new()),
+        });
+        engine.set_listener(recorder.clone());
+        let result = run_position(&mut engine, &recorder, &epd, &limits)
+            .unwrap()
+            .unwrap();
+        assert_eq!(result.id, "Undermine.001");
+        assert!([0, 2, 3, 10].contains(&result.points.unwrap().0));
+        assert_eq!(result.points.unwrap().1, 10);
+
+        // A bad item is named, a plain comment gives no points
+        let bad = Epd::parse(&line.replace("Bg4=2", "Bg5=2")).unwrap();
+        assert_eq!(
+            bad.points(&mut pos),
+            Err(String::from("bad item in c0: Bg5=2"))
+        );
+        let bad = Epd::parse(&line.replace("Bf2=3", "Bf2=three")).unwrap();
+        assert_eq!(
+            bad.points(&mut pos),
+            Err(String::from("bad item in c0: Bf2=three"))
+        );
+        let comment = Epd::parse(&line.replace("f5=10, Be5+=2, Bf2=3, Bg4=2", "STS")).unwrap();
+        assert_eq!(comment.points(&mut pos), Ok(Vec::new()));
+    }
+}
@@ -0,0 +1,281 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+// Texel tuning of the evaluation weights. The tuner reads a file of quiet
+// positions labeled with the result of the game they were taken from and
+// minimizes the mean squared difference between the results and the
+// evaluations, mapped to expected scores by a logistic function:
+//
+//   E = 1/N * sum (result - 1 / (1 + 10^(-K * eval / 400)))^2
+//
+// K is fitted once to the starting weights. The weights listed by
+// tune::eval_params() are then optimized with Adam, using gradients
+// computed by central differences. At the end, the tuned weights are
+// printed as Rust source, ready to be pasted into the tunable! blocks.
+//
+// Each line of the input holds a FEN string followed by the result of the
+// game, either as "1-0", "0-1" or "1/2-1/2" (optionally quoted, as in EPD
+// files with a c9 opcode) or as white's score in brackets, e.g. "[0.5]".
+// Positions with the side to move in check are skipped.
+
+use evaluate;
+use position::Position;
+use psqt;
+use tune;
+use tune::Param;
+use types::*;
+
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+// A Sample is a labeled position. The position is kept as its pieces,
+// side to move and castling rooks, see Position::set_pieces(): its
+// piece-square score depends on the weights, so it is set up again for
+// every evaluation.
+
+struct Sample {
+    pieces: Vec<(Square, Piece)>,
+    side_to_move: Color,
+    castling_rooks: Vec<Square>,
+    result: f64,
+}
+
+impl Sample {
+    fn new(pos: &Position, result: f64) -> Sample {
+        Sample {
+            pieces: (0..64)
+                .map(Square)
+                .filter(|&s| pos.piece_on(s) != NO_PIECE)
+                .map(|s| (s, pos.piece_on(s)))
+                .collect(),
+            side_to_move: pos.side_to_move(),
+            castling_rooks: [WHITE_OO, WHITE_OOO, BLACK_OO, BLACK_OOO]
+                .iter()
+                .filter(|&&cr| pos.has_castling_right(cr))
+                .map(|&cr| pos.castling_rook_square(cr))
+                .collect(),
+            result,
+        }
+    }
+}
+
+// parse_result() finds the game result in the part of a line following the
+// FEN string and returns it as white's score
+
+fn parse_result(s: &str) -> Option<f64> {
+    if s.contains("1/2-1/2") {
+        Some(0.5)
+    } else if s.contains("1-0") {
+        Some(1.0)
+    } else if s.contains("0-1") {
+        Some(0.0)
+    } else {
+        let start = s.find('[')?;
+        let end = s[start..].find(']')? + start;
+        s[start + 1..end].trim().parse().ok()
+    }
+}
+
+// parse_line() splits a line into the FEN string, made of four fields and
+// the optional move counters, and the result
+
+fn parse_line(line: &str) -> Option<(String, f64)> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    if tokens.len() < 5 {
+        return None;
+    }
+
+    let mut n = 4;
+    while n < 6 && n < tokens.len() && tokens[n].parse::<u32>().is_ok() {
+        n += 1;
+    }
+
+    Some((tokens[..n].join(" "), parse_result(&tokens[n..].join(" "))?))
+}
+
+fn load(file_name: &str, pos: &mut Position) -> Result<Vec<Sample>, String> {
+    let file = File::open(file_name).map_err(|e| format!("Unable to open {}: {}", file_name, e))?;
+
+    let mut samples = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(|e| format!("Unable to read {}: {}", file_name, e))?;
+        if let Some((fen, result)) = parse_line(&line) {
+            pos.init_states();
+            if pos.set(&fen, false).is_ok() && pos.checkers() == 0 {
+                samples.push(Sample::new(pos, result));
+            }
+        }
+    }
+
+    Ok(samples)
+}
+
+fn sigmoid(k: f64, v: Value) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-k * v.0 as f64 / 400.0))
+}
+
+// Weights gives flat access to the integer components of all tuned
+// parameters.
+
+struct Weights {
+    params: Vec<(&'static str, &'static dyn Param)>,
+}
+
+impl Weights {
+    fn get(&self) -> Vec<i32> {
+        let mut values = Vec::new();
+        for &(name, p) in self.params.iter() {
+            p.visit(name, &mut |_, v| values.push(*v));
+        }
+        values
+    }
+
+    // set() changes the weights. The piece-square tables are derived from
+    // the weights, so they are computed again.
+
+    fn set(&self, values: &[i32]) {
+        let mut it = values.iter();
+        for &(name, p) in self.params.iter() {
+            p.visit(name, &mut |_, v| *v = *it.next().unwrap());
+        }
+        psqt::init();
+    }
+}
+
+// error() returns the mean squared error of the current weights
+
+fn error(pos: &mut Position, samples: &[Sample], k: f64) -> f64 {
+    // Cached pawn and material entries depend on the weights
+    pos.clear_tables();
+
+    let mut sum = 0.0;
+    for sample in samples.iter() {
+        pos.init_states();
+        pos.set_pieces(
+            &sample.pieces,
+            sample.side_to_move,
+            &sample.castling_rooks,
+            false,
+        );
+        let v = evaluate::evaluate(pos);
+        let v = if pos.side_to_move() == WHITE { v } else { -v };
+        let d = sample.result - sigmoid(k, v);
+        sum += d * d;
+    }
+
+    sum / samples.len() as f64
+}
+
+// fit_k() finds the scaling constant K that minimizes the error of the
+// current weights by golden-section search
+
+fn fit_k(pos: &mut Position, samples: &[Sample]) -> f64 {
+    let phi = (5f64.sqrt() - 1.0) / 2.0;
+    let (mut a, mut b) = (0.0, 3.0);
+
+    while b - a > 0.0001 {
+        let c = b - phi * (b - a);
+        let d = a + phi * (b - a);
+        if error(pos, samples, c) < error(pos, samples, d) {
+            b = d;
+        } else {
+            a = c;
+        }
+    }
+
+    (a + b) / 2.0
+}
+
+// run() tunes the weights with the given number of iterations and learning
+// rate and prints the result
+
+pub fn run(file_name: &str, iterations: usize, rate: f64) {
+    let mut pos = Box::new(Position::new());
+    pos.init_tables();
+
+    let samples = match load(file_name, &mut pos) {
+        Ok(samples) => samples,
+        Err(e) => {
+            println!("info string {}", e);
+            return;
+        }
+    };
+
+    if samples.is_empty() {
+        println!("info string No labeled positions found in {}", file_name);
+        return;
+    }
+
+    let weights = Weights {
+        params: tune::eval_params(),
+    };
+    let mut values = weights.get();
+    let mut theta: Vec<f64> = values.iter().map(|&v| v as f64).collect();
+    let n = values.len();
+
+    let k = fit_k(&mut pos, &samples);
+    println!(
+        "Positions: {}, weights: {}, K: {:.4}, error: {:.6}",
+        samples.len(),
+        n,
+        k,
+        error(&mut pos, &samples, k)
+    );
+
+    // Adam optimizer state
+    let (beta1, beta2, eps) = (0.9, 0.999, 1e-8);
+    let mut m = vec![0.0; n];
+    let mut v = vec![0.0; n];
+
+    // Weights that do not affect any position, such as unused table
+    // entries, are skipped after the first iteration
+    let mut active = vec![true; n];
+
+    for iter in 1..=iterations {
+        let mut gradient = vec![0.0; n];
+
+        for i in 0..n {
+            if !active[i] {
+                continue;
+            }
+
+            values[i] += 1;
+            weights.set(&values);
+            let e_plus = error(&mut pos, &samples, k);
+
+            values[i] -= 2;
+            weights.set(&values);
+            let e_minus = error(&mut pos, &samples, k);
+
+            values[i] += 1;
+            gradient[i] = (e_plus - e_minus) / 2.0;
+
+            if iter == 1 && e_plus == e_minus {
+                active[i] = false;
+            }
+        }
+
+        for i in 0..n {
+            let g = gradient[i];
+            m[i] = beta1 * m[i] + (1.0 - beta1) * g;
+            v[i] = beta2 * v[i] + (1.0 - beta2) * g * g;
+            let m_hat = m[i] / (1.0 - beta1.powi(iter as i32));
+            let v_hat = v[i] / (1.0 - beta2.powi(iter as i32));
+            if active[i] {
+                theta[i] -= rate * m_hat / (v_hat.sqrt() + eps);
+            }
+            values[i] = theta[i].round() as i32;
+        }
+
+        weights.set(&values);
+        println!(
+            "Iteration {}: error {:.6}",
+            iter,
+            error(&mut pos, &samples, k)
+        );
+    }
+
+    println!();
+    for &(name, p) in weights.params.iter() {
+        print!("{}", p.source(name));
+    }
+}
@@ -1,13 +1,17 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
-use material;
+use book;
+use listener::SearchListener;
 use movegen::*;
-use pawns;
+use nnue::Network;
 use position::Position;
 use search::*;
 use tb;
+use timeman::TimeManagement;
+use tt::TranspositionTable;
 use types::*;
-use ucioption;
+use uci;
+use ucioption::OptionsMap;
 
 use std;
 use std::cell::Cell;
@@ -27,6 +31,125 @@ pub struct SearchResult {
     pub pv: Vec<Move>,
 }
 
+/// SearchOutcome is the final result of a search. For a move from the
+/// opening book and for perft the score is Value::NONE; for perft, nodes is
+/// the leaf count. best is Move::NONE if there is no legal move.
+#[derive(Clone)]
+pub struct SearchOutcome {
+    pub best: Move,
+    pub ponder: Option<Move>,
+    pub score: Value,
+    pub depth: i32,
+    pub pv: Vec<Move>,
+    pub nodes: u64,
+}
+
+// OutcomeSlot is where the main thread leaves the outcome of a search for
+// the SearchHandle returned by start_thinking()
+
+enum SlotState {
+    Pending,
+    Done(SearchOutcome),
+    // The search thread panicked before setting an outcome
+    Failed,
+}
+
+pub struct OutcomeSlot {
+    state: Mutex<SlotState>,
+    cv: Condvar,
+}
+
+impl OutcomeSlot {
+    fn new() -> OutcomeSlot {
+        OutcomeSlot {
+            state: Mutex::new(SlotState::Pending),
+            cv: Condvar::new(),
+        }
+    }
+
+    pub fn set(&self, outcome: SearchOutcome) {
+        *self.state.lock().unwrap() = SlotState::Done(outcome);
+        self.cv.notify_all();
+    }
+
+    fn fail(&self) {
+        let mut state = self.state.lock().unwrap();
+        if let SlotState::Pending = *state {
+            *state = SlotState::Failed;
+        }
+        self.cv.notify_all();
+    }
+}
+
+// OutcomeGuard marks the outcome of a search as failed if the main thread
+// unwinds out of the search without setting it. The helper threads are
+// stopped, as the main thread can no longer do so.
+
+struct OutcomeGuard {
+    slot: Arc<OutcomeSlot>,
+    shared: Arc<SharedState>,
+}
+
+impl Drop for OutcomeGuard {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            self.shared.set_stop(true);
+            self.slot.fail();
+        }
+    }
+}
+
+/// SearchHandle controls a search started by Engine::go() and gives access
+/// to its outcome. It can be sent to another thread and outlives the
+/// search.
+pub struct SearchHandle {
+    shared: Arc<SharedState>,
+    slot: Arc<OutcomeSlot>,
+}
+
+impl SearchHandle {
+    // stop() stops the search as soon as possible
+
+    pub fn stop(&self) {
+        self.shared.set_stop(true);
+    }
+
+    pub fn ponderhit(&self) {
+        if self.try_result().is_none() {
+            self.shared.ponderhit();
+        }
+    }
+
+    // wait() blocks until the search has finished and returns its outcome.
+    // An infinite or pondering search must be stopped first. It panics if
+    // the search thread panicked.
+
+    pub fn wait(&self) -> SearchOutcome {
+        let mut state = self.slot.state.lock().unwrap();
+        while let SlotState::Pending = *state {
+            state = self.slot.cv.wait(state).unwrap();
+        }
+        let outcome = match *state {
+            SlotState::Done(ref outcome) => Some(outcome.clone()),
+            _ => None,
+        };
+        drop(state);
+        outcome.expect("the search thread panicked")
+    }
+
+    // try_result() returns the outcome if the search has finished. Like
+    // wait(), it panics if the search thread panicked.
+
+    pub fn try_result(&self) -> Option<SearchOutcome> {
+        let outcome = match *self.slot.state.lock().unwrap() {
+            SlotState::Pending => return None,
+            SlotState::Done(ref outcome) => Some(outcome.clone()),
+            SlotState::Failed => None,
+        };
+        Some(outcome.expect("the search thread panicked"))
+    }
+}
+
 pub struct ThreadState {
     pub exit: bool,
     pub searching: bool,
@@ -37,6 +160,9 @@ pub struct CommonState {
     pub root_moves: Arc<RootMoves>,
     pub pos_data: Arc<RwLock<PosData>>,
     pub result: Arc<Mutex<SearchResult>>,
+    pub outcome: Arc<OutcomeSlot>,
+    pub limits: LimitsType,
+    pub tb_config: tb::TbConfig,
 }
 
 pub struct ThreadCtrl {
@@ -48,10 +174,11 @@ pub struct ThreadCtrl {
     pub nodes: Cell<u64>,
     pub tb_hits: Cell<u64>,
     pub contempt: Cell<Score>,
+    pub shared: Arc<SharedState>,
 }
 
 impl ThreadCtrl {
-    pub fn new(idx: usize) -> ThreadCtrl {
+    pub fn new(idx: usize, shared: Arc<SharedState>) -> ThreadCtrl {
         let thread_ctrl = ThreadCtrl {
             idx: idx,
             state: Mutex::new(ThreadState {
@@ -70,11 +197,15 @@ impl ThreadCtrl {
                     score: -Value::INFINITE,
                     pv: Vec::new(),
                 })),
+                outcome: Arc::new(OutcomeSlot::new()),
+                limits: LimitsType::new(),
+                tb_config: tb::TbConfig::new(),
             }),
             cv: Condvar::new(),
             nodes: Cell::new(0),
             tb_hits: Cell::new(0),
             contempt: Cell::new(Score::ZERO),
+            shared,
         };
         thread_ctrl
     }
@@ -86,100 +217,309 @@ unsafe impl Sync for ThreadCtrl {}
 type Handlers = Vec<thread::JoinHandle<()>>;
 type Threads = Vec<Arc<ThreadCtrl>>;
 
-static mut HANDLERS: *mut Handlers = 0 as *mut Handlers;
-static mut THREADS: *mut Threads = 0 as *mut Threads;
+// SharedState contains everything the search threads of one engine have in
+// common: the UCI options, the transposition table, the NNUE network, the
+// time management and the flags used to stop the search. Search threads
+// reach it through their ThreadCtrl.
+
+pub struct SharedState {
+    pub options: RwLock<OptionsMap>,
+    pub tt: TranspositionTable,
+    // Loaded network if "Use NNUE" is set, see ucioption::on_eval_file()
+    pub network: RwLock<Option<Arc<Network>>>,
+    // Tablebases found in SyzygyPath, see ucioption::on_tb_path()
+    pub tablebases: RwLock<Arc<tb::Tablebases>>,
+    pub time: TimeManagement,
+    // Receives the output of the search, see Engine::set_listener()
+    pub listener: RwLock<Arc<dyn SearchListener>>,
+    threads: RwLock<Threads>,
+    stop: AtomicBool,
+    ponder: AtomicBool,
+    stop_on_ponderhit: AtomicBool,
+}
 
-static STOP: AtomicBool = AtomicBool::new(false);
-static PONDER: AtomicBool = AtomicBool::new(false);
-static STOP_ON_PONDERHIT: AtomicBool = AtomicBool::new(false);
+impl SharedState {
+    pub fn listener(&self) -> Arc<dyn SearchListener> {
+        self.listener.read().unwrap().clone()
+    }
 
-pub fn stop() -> bool {
-    STOP.load(Ordering::Relaxed)
-}
+    pub fn stop(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
+    }
 
-pub fn ponder() -> bool {
-    PONDER.load(Ordering::Relaxed)
-}
+    pub fn ponder(&self) -> bool {
+        self.ponder.load(Ordering::Relaxed)
+    }
 
-pub fn stop_on_ponderhit() -> bool {
-    STOP_ON_PONDERHIT.load(Ordering::Relaxed)
-}
+    pub fn stop_on_ponderhit(&self) -> bool {
+        self.stop_on_ponderhit.load(Ordering::Relaxed)
+    }
 
-pub fn set_stop(b: bool) {
-    STOP.store(b, Ordering::SeqCst);
-}
+    pub fn set_stop(&self, b: bool) {
+        self.stop.store(b, Ordering::SeqCst);
+    }
+
+    pub fn set_ponder(&self, b: bool) {
+        self.ponder.store(b, Ordering::SeqCst);
+    }
+
+    pub fn set_stop_on_ponderhit(&self, b: bool) {
+        self.stop_on_ponderhit.store(b, Ordering::SeqCst);
+    }
+
+    // ponderhit() is called when the expected move was played. The search
+    // continues but switches from pondering to normal search. In case
+    // stop_on_ponderhit() is set, the search was only waiting for
+    // 'ponderhit' to stop, for instance because max depth was reached.
+
+    pub fn ponderhit(&self) {
+        if self.stop_on_ponderhit() {
+            self.set_stop(true);
+        } else {
+            self.set_ponder(false); // Switch to normal search
+        }
+    }
+
+    pub fn wake_up_slaves(&self) {
+        for th in self.threads.read().unwrap().iter() {
+            if th.idx != 0 {
+                wake_up(th, false, false);
+            }
+        }
+    }
+
+    pub fn wait_for_slaves(&self) {
+        for th in self.threads.read().unwrap().iter() {
+            if th.idx != 0 {
+                wait_for(th);
+            }
+        }
+    }
 
-pub fn set_ponder(b: bool) {
-    PONDER.store(b, Ordering::SeqCst);
+    pub fn nodes_searched(&self) -> u64 {
+        self.threads
+            .read()
+            .unwrap()
+            .iter()
+            .map(|th| th.nodes.get())
+            .sum()
+    }
+
+    pub fn tb_hits(&self) -> u64 {
+        self.threads
+            .read()
+            .unwrap()
+            .iter()
+            .map(|th| th.tb_hits.get())
+            .sum()
+    }
 }
 
-pub fn set_stop_on_ponderhit(b: bool) {
-    STOP_ON_PONDERHIT.store(b, Ordering::SeqCst);
+// ThreadPool owns the search threads of one engine together with the state
+// they share. Dropping the pool terminates the threads.
+
+pub struct ThreadPool {
+    handlers: Handlers,
+    pub shared: Arc<SharedState>,
 }
 
-pub fn init(requested: usize) {
-    let handlers: Box<Handlers> = Box::new(Vec::new());
-    let threads: Box<Threads> = Box::new(Vec::new());
-    unsafe {
-        HANDLERS = Box::into_raw(handlers);
-        THREADS = Box::into_raw(threads);
+impl ThreadPool {
+    pub fn new(options: OptionsMap) -> ThreadPool {
+        ThreadPool {
+            handlers: Vec::new(),
+            shared: Arc::new(SharedState {
+                options: RwLock::new(options),
+                tt: TranspositionTable::new(),
+                network: RwLock::new(None),
+                tablebases: RwLock::new(Arc::new(tb::Tablebases::new())),
+                time: TimeManagement::new(),
+                listener: RwLock::new(Arc::new(uci::UciListener)),
+                threads: RwLock::new(Vec::new()),
+                stop: AtomicBool::new(false),
+                ponder: AtomicBool::new(false),
+                stop_on_ponderhit: AtomicBool::new(false),
+            }),
+        }
+    }
+
+    /// set() creates/destroys threads to match the requested number.
+    /// Created and launched threads wil go immediately to sleep in idle_loop.
+    /// Upon resizing, threads are recreated to allow for binding if necessary.
+    pub fn set(&mut self, requested: usize) {
+        self.wait_for_all();
+
+        while self.handlers.len() < requested {
+            let idx = self.handlers.len();
+            let shared = self.shared.clone();
+            let (tx, rx) = channel();
+            // 16 MB stacks are now too small in debug mode, so use 32 MB stacks
+            let builder = thread::Builder::new().stack_size(32 * 1024 * 1024);
+            let handler = builder.spawn(move || run_thread(idx, shared, tx)).unwrap();
+            let th = rx.recv().unwrap();
+            self.handlers.push(handler);
+            self.shared.threads.write().unwrap().push(th);
+        }
+
+        while self.handlers.len() > requested {
+            let handler = self.handlers.pop().unwrap();
+            let th = self.shared.threads.write().unwrap().pop().unwrap();
+            wake_up(&th, true, false);
+            let _ = handler.join();
+        }
     }
 
-    set(requested);
-}
+    pub fn clear_search(&self) {
+        for th in self.shared.threads.read().unwrap().iter() {
+            wake_up(th, false, true);
+        }
+    }
+
+    pub fn wait_for_main(&self) {
+        for th in self.shared.threads.read().unwrap().iter() {
+            if th.idx == 0 {
+                wait_for(th);
+            }
+        }
+    }
+
+    pub fn wait_for_all(&self) {
+        for th in self.shared.threads.read().unwrap().iter() {
+            wait_for(th);
+        }
+    }
+
+    pub fn start_thinking(
+        &self,
+        pos: &mut Position,
+        pos_data: &Arc<RwLock<PosData>>,
+        limits: &LimitsType,
+        searchmoves: Vec<Move>,
+        ponder_mode: bool,
+    ) -> SearchHandle {
+        self.wait_for_main();
+
+        let handle = SearchHandle {
+            shared: self.shared.clone(),
+            slot: Arc::new(OutcomeSlot::new()),
+        };
+
+        self.shared.set_stop_on_ponderhit(false);
+        self.shared.set_stop(false);
+        self.shared.set_ponder(ponder_mode);
+
+        // A book move is played without searching. While pondering or in
+        // analysis the search must run until told to stop, so the book is
+        // only used when the engine is to move in a game.
+        if !ponder_mode && !limits.infinite && limits.mate == 0 && limits.perft == 0 {
+            let m = self.book_move(pos, &searchmoves);
+            if m != Move::NONE {
+                self.shared.listener().on_bestmove(pos, m, None);
+                handle.slot.set(SearchOutcome {
+                    best: m,
+                    ponder: None,
+                    score: Value::NONE,
+                    depth: 0,
+                    pv: vec![m],
+                    nodes: 0,
+                });
+                return handle;
+            }
+        }
+
+        if self.shared.options.read().unwrap().get_bool("Use NNUE")
+            && self.shared.network.read().unwrap().is_none()
+        {
+            self.shared
+                .listener()
+                .on_message("No NNUE network loaded, using classical evaluation");
+        }
+
+        let mut root_moves = RootMoves::new();
+        for m in MoveList::new::<Legal>(pos) {
+            if searchmoves.is_empty() || searchmoves.iter().any(|&x| x == m) {
+                root_moves.push(RootMove::new(m));
+            }
+        }
+
+        let mut tb_config = tb::read_options(
+            &self.shared.options.read().unwrap(),
+            &self.shared.tablebases.read().unwrap(),
+        );
+        tb::rank_root_moves(pos, &mut root_moves, &mut tb_config);
+
+        let root_moves = Arc::new(root_moves);
+        let result = Arc::new(Mutex::new(SearchResult {
+            depth: Depth::ZERO,
+            score: -Value::INFINITE,
+            pv: Vec::new(),
+        }));
+
+        let threads = self.shared.threads.read().unwrap();
+
+        for th in threads.iter() {
+            th.nodes.set(0);
+            th.tb_hits.set(0);
+            let mut common = th.common.lock().unwrap();
+            common.root_moves = root_moves.clone();
+            common.pos_data = pos_data.clone();
+            common.result = result.clone();
+            common.outcome = handle.slot.clone();
+            common.limits = limits.clone();
+            common.tb_config = tb_config.clone();
+        }
+
+        wake_up(&threads[0], false, false);
+        handle
+    }
+
+    // book_move() returns a move from the opening book if OwnBook is set,
+    // the game has not gone past BookDepth moves and the position is in the
+    // book. Otherwise it returns Move::NONE.
+
+    fn book_move(&self, pos: &Position, searchmoves: &[Move]) -> Move {
+        let options = self.shared.options.read().unwrap();
 
-pub fn free() {
-    set(0);
-    unsafe {
-        std::mem::drop(Box::from_raw(HANDLERS));
-        std::mem::drop(Box::from_raw(THREADS));
+        if !options.get_bool("OwnBook")
+            || pos.game_ply() >= 2 * options.get_i32("BookDepth")
+        {
+            return Move::NONE;
+        }
+
+        let m = book::probe(
+            pos,
+            &options.get_string("BookFile"),
+            options.get_bool("BestBookMove"),
+        );
+
+        if searchmoves.is_empty() || searchmoves.contains(&m) {
+            m
+        } else {
+            Move::NONE
+        }
+    }
+
+    pub fn nodes_searched(&self) -> u64 {
+        self.shared.nodes_searched()
+    }
+
+    pub fn tb_hits(&self) -> u64 {
+        self.shared.tb_hits()
     }
 }
 
-/// set() creates/destroys threads to match the requested number.
-/// Created and launched threads wil go immediately to sleep in idle_loop.
-/// Upon resizing, threads are recreated to allow for binding if necessary.
-pub fn set(requested: usize) {
-    let mut handlers = unsafe { Box::from_raw(HANDLERS) };
-    let mut threads = unsafe { Box::from_raw(THREADS) };
-
-    while handlers.len() < requested {
-        let idx = handlers.len();
-        let (tx, rx) = channel();
-        // 16 MB stacks are now too small in debug mode, so use 32 MB stacks
-        let builder = thread::Builder::new().stack_size(32 * 1024 * 1024);
-        let handler = builder.spawn(move || run_thread(idx, tx)).unwrap();
-        let th = rx.recv().unwrap();
-        handlers.push(handler);
-        threads.push(th);
-    }
-
-    while handlers.len() > requested {
-        let handler = handlers.pop().unwrap();
-        let th = threads.pop().unwrap();
-        wake_up(&th, true, false);
-        let _ = handler.join();
-    }
-
-    std::mem::forget(handlers);
-    std::mem::forget(threads);
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        self.shared.set_stop(true);
+        self.set(0);
+    }
 }
 
-fn run_thread(idx: usize, tx: Sender<Arc<ThreadCtrl>>) {
+fn run_thread(idx: usize, shared: Arc<SharedState>, tx: Sender<Arc<ThreadCtrl>>) {
     let mut pos = Box::new(Position::new());
-    pos.pawns_table.reserve_exact(16384);
-    for _ in 0..16384 {
-        pos.pawns_table
-            .push(std::cell::UnsafeCell::new(pawns::Entry::new()));
-    }
-    pos.material_table.reserve_exact(8192);
-    for _ in 0..8192 {
-        pos.material_table
-            .push(std::cell::UnsafeCell::new(material::Entry::new()));
-    }
+    pos.init_tables();
     pos.is_main = idx == 0;
     pos.thread_idx = idx as i32;
-    let th = Arc::new(ThreadCtrl::new(idx));
+    let th = Arc::new(ThreadCtrl::new(idx, shared));
     tx.send(th.clone()).unwrap();
     pos.thread_ctrl = Some(th.clone());
     pos.previous_time_reduction = 1.;
@@ -204,7 +544,7 @@ fn run_thread(idx: usize, tx: Sender<Arc<ThreadCtrl>>) {
             pos.counter_moves = unsafe { std::mem::zeroed() };
             pos.main_history = unsafe { std::mem::zeroed() };
             pos.capture_history = unsafe { std::mem::zeroed() };
-            pos.cont_history = unsafe { std::mem::zeroed() };
+            pos.cont_history.clear();
             pos.cont_history.init();
             state.clear = false;
             continue;
@@ -212,19 +552,27 @@ fn run_thread(idx: usize, tx: Sender<Arc<ThreadCtrl>>) {
         {
             let common = th.common.lock().unwrap();
             let pos_data = common.pos_data.read().unwrap();
+            let chess960 = th.shared.options.read().unwrap().get_bool("UCI_Chess960");
             pos.init_states();
-            pos.set(&pos_data.fen, ucioption::get_bool("UCI_Chess960"));
+            pos.set(&pos_data.fen, chess960).unwrap();
             for &m in pos_data.moves.iter() {
                 let gives_check = pos.gives_check(m);
                 pos.do_move(m, gives_check);
             }
             let fen = pos.fen();
-            pos.set(&fen, ucioption::get_bool("UCI_Chess960"));
+            pos.set(&fen, chess960).unwrap();
             pos.root_moves = (*common.root_moves).clone();
+            pos.limits = common.limits.clone();
+            pos.tb_config = common.tb_config.clone();
+            pos.network = th.shared.network.read().unwrap().clone();
         } // Locks are dropped here
         pos.nodes = 0;
         pos.tb_hits = 0;
         if th.idx == 0 {
+            let _guard = OutcomeGuard {
+                slot: th.common.lock().unwrap().outcome.clone(),
+                shared: th.shared.clone(),
+            };
             mainthread_search(&mut pos, &th);
         } else {
             thread_search(&mut pos, &th);
@@ -242,153 +590,54 @@ fn run_thread(idx: usize, tx: Sender<Arc<ThreadCtrl>>) {
     }
 }
 
+// wake_up() and wait_for() find the state of a thread poisoned if the
+// thread panicked while searching. There is nothing left to wake up or wait
+// for then.
+
 fn wake_up(th: &ThreadCtrl, exit: bool, clear: bool) {
-    let mut state = th.state.lock().unwrap();
+    let mut state = match th.state.lock() {
+        Ok(state) => state,
+        Err(_) => return,
+    };
     state.searching = true;
     state.exit = exit;
     state.clear = clear;
     th.cv.notify_one();
 }
 
-pub fn wake_up_slaves() {
-    let threads: Box<Threads> = unsafe { Box::from_raw(THREADS) };
-
-    for th in threads.iter() {
-        if th.idx != 0 {
-            wake_up(th, false, false);
-        }
-    }
-
-    std::mem::forget(threads);
-}
-
-pub fn clear_search() {
-    let threads: Box<Threads> = unsafe { Box::from_raw(THREADS) };
-
-    for th in threads.iter() {
-        wake_up(th, false, true);
-    }
-
-    std::mem::forget(threads);
-}
-
-pub fn wait_for_main() {
-    let threads: Box<Threads> = unsafe { Box::from_raw(THREADS) };
-
-    for th in threads.iter() {
-        if th.idx == 0 {
-            let mut state = th.state.lock().unwrap();
-            while state.searching {
-                state = th.cv.wait(state).unwrap();
-            }
-        }
-    }
-
-    std::mem::forget(threads);
-}
-
-pub fn wait_for_slaves() {
-    let threads: Box<Threads> = unsafe { Box::from_raw(THREADS) };
-
-    for th in threads.iter() {
-        if th.idx != 0 {
-            let mut state = th.state.lock().unwrap();
-            while state.searching {
-                state = th.cv.wait(state).unwrap();
-            }
-        }
-    }
-
-    std::mem::forget(threads);
-}
-
-pub fn wait_for_all() {
-    let threads: Box<Threads> = unsafe { Box::from_raw(THREADS) };
-
-    for th in threads.iter() {
-        let mut state = th.state.lock().unwrap();
-        while state.searching {
-            state = th.cv.wait(state).unwrap();
-        }
+fn wait_for(th: &ThreadCtrl) {
+    let mut state = match th.state.lock() {
+        Ok(state) => state,
+        Err(_) => return,
+    };
+    while state.searching {
+        state = th.cv.wait(state).unwrap();
     }
-
-    std::mem::forget(threads);
 }
 
-pub fn start_thinking(
-    pos: &mut Position,
-    pos_data: &Arc<RwLock<PosData>>,
-    limits: &LimitsType,
-    searchmoves: Vec<Move>,
-    ponder_mode: bool,
-) {
-    let threads: Box<Threads> = unsafe { Box::from_raw(THREADS) };
-
-    wait_for_main();
-
-    set_stop_on_ponderhit(false);
-    set_stop(false);
-    set_ponder(ponder_mode);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use engine::Engine;
+    use listener::{PvLine, SearchStats};
+    use std::panic;
 
-    unsafe {
-        LIMITS = (*limits).clone();
-    }
+    struct Panicking;
 
-    let mut root_moves = RootMoves::new();
-    for m in MoveList::new::<Legal>(pos) {
-        if searchmoves.is_empty() || searchmoves.iter().any(|&x| x == m) {
-            root_moves.push(RootMove::new(m));
+    impl SearchListener for Panicking {
+        fn on_iteration(&self, _: &Position, _: i32, _: &[PvLine], _: &SearchStats) {
+            panic!("listener failure");
         }
     }
 
-    tb::read_options();
-    tb::rank_root_moves(pos, &mut root_moves);
-
-    let root_moves = Arc::new(root_moves);
-    let result = Arc::new(Mutex::new(SearchResult {
-        depth: Depth::ZERO,
-        score: -Value::INFINITE,
-        pv: Vec::new(),
-    }));
-
-    for th in threads.iter() {
-        th.nodes.set(0);
-        th.tb_hits.set(0);
-        let mut common = th.common.lock().unwrap();
-        common.root_moves = root_moves.clone();
-        common.pos_data = pos_data.clone();
-        common.result = result.clone();
-    }
-
-    wake_up(&threads[0], false, false);
-
-    std::mem::forget(threads);
-}
-
-pub fn nodes_searched() -> u64 {
-    let threads: Box<Threads> = unsafe { Box::from_raw(THREADS) };
-
-    let mut nodes = 0;
-
-    for th in threads.iter() {
-        nodes += th.nodes.get();
-    }
-
-    std::mem::forget(threads);
-
-    nodes
-}
-
-pub fn tb_hits() -> u64 {
-    let threads: Box<Threads> = unsafe { Box::from_raw(THREADS) };
-
-    let mut tb_hits = 0;
-
-    for th in threads.iter() {
-        tb_hits += th.tb_hits.get();
+    #[test]
+    fn panicking_search() {
+        let mut engine = Engine::new();
+        engine.set_listener(Arc::new(Panicking));
+        let mut limits = LimitsType::new();
+        limits.depth = 1;
+        let handle = engine.go(&limits, Vec::new(), false);
+        assert!(panic::catch_unwind(panic::AssertUnwindSafe(|| handle.wait())).is_err());
+        assert!(panic::catch_unwind(panic::AssertUnwindSafe(|| handle.try_result())).is_err());
     }
-
-    std::mem::forget(threads);
-
-    tb_hits
 }
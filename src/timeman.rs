@@ -2,25 +2,20 @@
 
 use search;
 use types::*;
-use ucioption;
+use ucioption::OptionsMap;
 
 use std;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::RwLock;
+use std::time::Instant;
 
-static mut START_TIME: Option<std::time::Instant> = None;
-static mut OPTIMUM_TIME: i64 = 0;
-static mut MAXIMUM_TIME: i64 = 0;
+// TimeManagement holds the time allocated for the current search. It is
+// set up by the main thread and read by all search threads of an engine.
 
-pub fn optimum() -> i64 {
-    unsafe { OPTIMUM_TIME }
-}
-
-pub fn maximum() -> i64 {
-    unsafe { MAXIMUM_TIME }
-}
-
-pub fn elapsed() -> i64 {
-    let duration = unsafe { START_TIME.unwrap().elapsed() };
-    (duration.as_secs() * 1000 + (duration.subsec_nanos() / 1000000) as u64) as i64
+pub struct TimeManagement {
+    start_time: RwLock<Option<Instant>>,
+    optimum_time: AtomicI64,
+    maximum_time: AtomicI64,
 }
 
 #[derive(PartialEq, Eq)]
@@ -78,56 +73,77 @@ fn remaining(my_time: i64, movestogo: i32, ply: i32, slow_mover: i64, time_type:
     (my_time as f64 * ratio1.min(ratio2)) as i64
 }
 
-// init() is called at the beginning of the search and calculates the allowed
-// thinking time out of the time control and current game ply. We support
-// four different kinds of time controls, passed in 'limits':
-//
-//  inc == 0 && movestogo == 0 means: x basetime  [sudden death!]
-//  inc == 0 && movestogo != 0 means: x moves in y minutes
-//  inc >  0 && movestogo == 0 means: x basetime + z increment
-//  inc >  0 && movestogo != 0 means: x moves in y minutes + z increment
-
-pub fn init(limits: &mut search::LimitsType, us: Color, ply: i32) {
-    let min_think_time = ucioption::get_i32("Minimum Thinking Time") as i64;
-    let move_overhead = ucioption::get_i32("Move Overhead") as i64;
-    let slow_mover = ucioption::get_i32("Slow Mover") as i64;
-
-    unsafe {
-        START_TIME = limits.start_time;
-        let time = std::cmp::max(limits.time[us.0 as usize], min_think_time);
-        OPTIMUM_TIME = time;
-        MAXIMUM_TIME = time;
+impl TimeManagement {
+    pub fn new() -> TimeManagement {
+        TimeManagement {
+            start_time: RwLock::new(None),
+            optimum_time: AtomicI64::new(0),
+            maximum_time: AtomicI64::new(0),
+        }
     }
 
-    let max_mtg = if limits.movestogo != 0 {
-        std::cmp::min(limits.movestogo, MOVE_HORIZON)
-    } else {
-        MOVE_HORIZON
-    };
-
-    // We calculate optimum time usage for different hypothetical "moves to go"
-    // values and choose the minimum of calculated search time values. Usually
-    // the greates hyp_mtg givse the minimum values.
-    for hyp_mtg in 1..(max_mtg + 1) {
-        // Calculate thinking time for hypothetical "moves to go" value
-        let mut hyp_my_time = limits.time[us.0 as usize]
-            + limits.inc[us.0 as usize] * (hyp_mtg - 1) as i64
-            - move_overhead * (2 + std::cmp::min(hyp_mtg, 40) as i64);
+    pub fn optimum(&self) -> i64 {
+        self.optimum_time.load(Ordering::Relaxed)
+    }
 
-        hyp_my_time = std::cmp::max(hyp_my_time, 0);
+    pub fn maximum(&self) -> i64 {
+        self.maximum_time.load(Ordering::Relaxed)
+    }
 
-        let t1 = min_think_time + remaining(hyp_my_time, hyp_mtg, ply, slow_mover, OptimumTime);
-        let t2 = min_think_time + remaining(hyp_my_time, hyp_mtg, ply, slow_mover, MaxTime);
+    pub fn elapsed(&self) -> i64 {
+        let duration = self.start_time.read().unwrap().unwrap().elapsed();
+        (duration.as_secs() * 1000 + (duration.subsec_nanos() / 1000000) as u64) as i64
+    }
 
-        unsafe {
-            OPTIMUM_TIME = std::cmp::min(t1, OPTIMUM_TIME);
-            MAXIMUM_TIME = std::cmp::min(t2, MAXIMUM_TIME);
+    // init() is called at the beginning of the search and calculates the
+    // allowed thinking time out of the time control and current game ply.
+    // We support four different kinds of time controls, passed in 'limits':
+    //
+    //  inc == 0 && movestogo == 0 means: x basetime  [sudden death!]
+    //  inc == 0 && movestogo != 0 means: x moves in y minutes
+    //  inc >  0 && movestogo == 0 means: x basetime + z increment
+    //  inc >  0 && movestogo != 0 means: x moves in y minutes + z increment
+
+    pub fn init(&self, options: &OptionsMap, limits: &search::LimitsType, us: Color, ply: i32) {
+        let min_think_time = options.get_i32("Minimum Thinking Time") as i64;
+        let move_overhead = options.get_i32("Move Overhead") as i64;
+        let slow_mover = options.get_i32("Slow Mover") as i64;
+
+        *self.start_time.write().unwrap() = limits.start_time;
+        let time = std::cmp::max(limits.time[us.0 as usize], min_think_time);
+        let mut optimum_time = time;
+        let mut maximum_time = time;
+
+        let max_mtg = if limits.movestogo != 0 {
+            std::cmp::min(limits.movestogo, MOVE_HORIZON)
+        } else {
+            MOVE_HORIZON
+        };
+
+        // We calculate optimum time usage for different hypothetical "moves
+        // to go" values and choose the minimum of calculated search time
+        // values. Usually the greates hyp_mtg givse the minimum values.
+        for hyp_mtg in 1..(max_mtg + 1) {
+            // Calculate thinking time for hypothetical "moves to go" value
+            let mut hyp_my_time = limits.time[us.0 as usize]
+                + limits.inc[us.0 as usize] * (hyp_mtg - 1) as i64
+                - move_overhead * (2 + std::cmp::min(hyp_mtg, 40) as i64);
+
+            hyp_my_time = std::cmp::max(hyp_my_time, 0);
+
+            let t1 =
+                min_think_time + remaining(hyp_my_time, hyp_mtg, ply, slow_mover, OptimumTime);
+            let t2 = min_think_time + remaining(hyp_my_time, hyp_mtg, ply, slow_mover, MaxTime);
+
+            optimum_time = std::cmp::min(t1, optimum_time);
+            maximum_time = std::cmp::min(t2, maximum_time);
         }
-    }
 
-    if ucioption::get_bool("Ponder") {
-        unsafe {
-            OPTIMUM_TIME += OPTIMUM_TIME / 4;
+        if options.get_bool("Ponder") {
+            optimum_time += optimum_time / 4;
         }
+
+        self.optimum_time.store(optimum_time, Ordering::Relaxed);
+        self.maximum_time.store(maximum_time, Ordering::Relaxed);
     }
 }
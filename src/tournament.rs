@@ -0,0 +1,1085 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+// Engine matches. Two players, each either an external UCI engine or an
+// instance of this engine with its own option values, play a series of
+// games from a list of openings. Every opening is played twice, with the
+// colors reversed. The games are played with a clock, and can be
+// adjudicated as a draw or a resignation based on the scores reported by
+// the engines. They are optionally saved as PGN, and the result is given as
+// the wins, draws and losses of the first player with an Elo estimate. A
+// match can also be run as an SPRT, which ends it once it is decided.
+//
+// Instances of this engine are run in-process with a SearchListener that
+// discards their output, external engines are run as child processes.
+
+use bitboard::*;
+use engine::Engine;
+use listener::SearchListener;
+use misc;
+use movegen::*;
+use pgn::Game;
+use position::Position;
+use search::LimitsType;
+use stats;
+use stats::{Sprt, SprtStatus};
+use types::*;
+use uci;
+
+use std;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Openings used when no file is given
+const OPENINGS: [&str; 27] = [
+    "rnbqkb1r/pppppp1p/5np1/8/2PP4/8/PP2PPPP/RNBQKBNR w KQkq - 0 3",
+    "rnbqkb1r/pppp1ppp/4pn2/8/2PP4/8/PP2PPPP/RNBQKBNR w KQkq - 0 3",
+    "rnbqkbnr/ppp2ppp/4p3/3p4/2PP4/8/PP2PPPP/RNBQKBNR w KQkq - 0 3",
+    "rnbqkbnr/pp2pppp/2p5/3p4/2PP4/8/PP2PPPP/RNBQKBNR w KQkq - 0 3",
+    "rnbqkb1r/ppp1pppp/5n2/8/2pP4/5N2/PP2PPPP/RNBQKB1R w KQkq - 2 4",
+    "rnbqkbnr/pp2pppp/3p4/2p5/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 0 3",
+    "r1bqkbnr/pp1ppppp/2n5/2p5/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3",
+    "rnbqkbnr/ppp2ppp/4p3/3p4/3PP3/8/PPP2PPP/RNBQKBNR w KQkq - 0 3",
+    "rnbqkbnr/pp1p1ppp/4p3/2p5/3PP3/8/PPP2PPP/RNBQKBNR w KQkq - 0 3",
+    "rnbqkb1r/ppp1pppp/3p1n2/8/3PP3/8/PPP2PPP/RNBQKBNR w KQkq - 1 3",
+    "rnbqkbnr/pp2pppp/2p5/3p4/3PP3/8/PPP2PPP/RNBQKBNR w KQkq - 0 3",
+    "r1bqk1nr/pppp1ppp/2n5/2b1p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4",
+    "r1bqkbnr/1ppp1ppp/p1n5/1B2p3/4P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 0 4",
+    "r1bqkbnr/pppp1ppp/2n5/8/3pP3/5N2/PPP2PPP/RNBQKB1R w KQkq - 0 4",
+    "rnbqkb1r/ppp2ppp/3p4/8/4n3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 0 5",
+    "rnbqkbnr/ppp2ppp/3p4/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 0 3",
+    "r1bqkb1r/pppp1ppp/2n2n2/4p3/4P3/2N2N2/PPPP1PPP/R1BQKB1R w KQkq - 4 4",
+    "rnbqkbnr/pp2pppp/8/2pp4/8/5NP1/PPPPPP1P/RNBQKB1R w KQkq - 0 3",
+    "rnbqkb1r/ppp1pppp/5n2/3p4/3P4/5N2/PPP1PPPP/RNBQKB1R w KQkq - 1 3",
+    "rnbqkbnr/ppp2ppp/4p3/3p4/2P5/5N2/PP1PPPPP/RNBQKB1R w KQkq - 0 3",
+    "rnbqkbnr/ppp1pppp/8/8/2Pp4/5N2/PP1PPPPP/RNBQKB1R w KQkq - 0 3",
+    "rnbqkb1r/pppppp1p/5np1/8/2P5/5N2/PP1PPPPP/RNBQKB1R w KQkq - 0 3",
+    "rnbqkb1r/pppppp1p/5np1/8/2P5/2N5/PP1PPPPP/R1BQKBNR w KQkq - 0 3",
+    "rnbqkb1r/pppp1ppp/5n2/4p3/2P5/2N5/PP1PPPPP/R1BQKBNR w KQkq - 2 3",
+    "rnbq1rk1/ppppppbp/5np1/8/8/5NP1/PPPPPPBP/RNBQ1RK1 w - - 4 5",
+    "rnbqkb1r/pp2pppp/2p2n2/3p4/8/5NP1/PPPPPPBP/RNBQK2R w KQkq - 0 4",
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+];
+
+// Scores are exchanged in centipawns. A mate in n moves is MATE_CP - n.
+pub const MATE_CP: i32 = 100_000;
+
+// Time allowed for starting an engine and for answering 'isready'
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Time an engine may exceed its clock by before it is considered hung
+const GRACE_TIME: i64 = 1000;
+
+// Time allowed for answering 'stop' after a timeout, before the engine is
+// restarted
+const STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// PlayerConfig describes a player of a match. Without a command, the
+/// player is an instance of this engine.
+#[derive(Clone, Debug)]
+pub struct PlayerConfig {
+    pub command: Option<String>,
+    pub name: Option<String>,
+    pub options: Vec<(String, String)>,
+}
+
+impl PlayerConfig {
+    pub fn new(command: Option<String>) -> PlayerConfig {
+        PlayerConfig {
+            command,
+            name: None,
+            options: Vec::new(),
+        }
+    }
+}
+
+/// MatchConfig holds the settings of a match, with times in milliseconds.
+/// A game is adjudicated as a draw when, from move draw_move_number on,
+/// the scores of both engines have stayed within draw_score centipawns of
+/// zero for draw_move_count moves each. A player resigns when its score
+/// has been below -resign_score for resign_move_count moves in a row. A
+/// move count of 0 disables the adjudication. With an SPRT, the match
+/// stops as soon as one of the hypotheses is accepted.
+#[derive(Clone, Debug)]
+pub struct MatchConfig {
+    pub players: [PlayerConfig; 2],
+    pub games: usize,
+    pub time: i64,
+    pub inc: i64,
+    pub openings: Option<String>,
+    pub pgn: Option<String>,
+    pub concurrency: usize,
+    pub draw_move_number: i32,
+    pub draw_move_count: i32,
+    pub draw_score: i32,
+    pub resign_move_count: i32,
+    pub resign_score: i32,
+    pub sprt: Option<Sprt>,
+}
+
+impl Default for MatchConfig {
+    fn default() -> MatchConfig {
+        MatchConfig {
+            players: [PlayerConfig::new(None), PlayerConfig::new(None)],
+            games: 40,
+            time: 5000,
+            inc: 0,
+            openings: None,
+            pgn: None,
+            concurrency: 1,
+            draw_move_number: 40,
+            draw_move_count: 10,
+            draw_score: 5,
+            resign_move_count: 3,
+            resign_score: 1000,
+            sprt: None,
+        }
+    }
+}
+
+/// MatchResult counts the games won, drawn and lost by the first player,
+/// and the results of the completed game pairs in pentanomial form, see
+/// the stats module
+#[derive(Clone, Copy, Debug)]
+pub struct MatchResult {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+    pub pairs: [u32; 5],
+}
+
+impl MatchResult {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    pub fn score(&self) -> f64 {
+        stats::score(&self.trinomial())
+    }
+
+    pub fn trinomial(&self) -> [u32; 3] {
+        [self.losses, self.draws, self.wins]
+    }
+
+    // counts() returns the results the statistics are based on: the pairs
+    // once one is complete, the single games before
+
+    pub fn counts(&self) -> Vec<u32> {
+        if self.pairs.iter().any(|&c| c > 0) {
+            self.pairs.to_vec()
+        } else {
+            self.trinomial().to_vec()
+        }
+    }
+
+    // elo() returns the Elo difference and the half width of its 95%
+    // confidence interval
+
+    pub fn elo(&self) -> (f64, f64) {
+        stats::elo_estimate(&self.counts())
+    }
+}
+
+// Reply is the move chosen by a player, in coordinate notation, together
+// with its score from the player's point of view, if known, and the depth
+
+struct Reply {
+    best: String,
+    score: Option<i32>,
+    depth: i32,
+}
+
+trait Player {
+    fn name(&self) -> String;
+
+    fn new_game(&mut self) -> io::Result<()>;
+
+    // go() returns the move to play after the given moves from the FEN
+    // position. Clocks and increments are passed in limits. An error of
+    // kind TimedOut is returned if there is no answer within timeout.
+    fn go(
+        &mut self,
+        fen: &str,
+        moves: &[String],
+        limits: &LimitsType,
+        timeout: Duration,
+    ) -> io::Result<Reply>;
+}
+
+// Quiet discards the output of the in-process engines
+
+struct Quiet;
+
+impl SearchListener for Quiet {}
+
+struct EnginePlayer {
+    engine: Engine,
+    name: String,
+}
+
+impl EnginePlayer {
+    fn new(config: &PlayerConfig) -> io::Result<EnginePlayer> {
+        let mut engine = Engine::new();
+        engine.set_listener(Arc::new(Quiet));
+
+        for (name, value) in config.options.iter() {
+            engine
+                .set_option(name, value)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        }
+
+        let name = match config.name {
+            Some(ref name) => name.clone(),
+            None => String::from(misc::engine_info(true).lines().next().unwrap()),
+        };
+
+        Ok(EnginePlayer { engine, name })
+    }
+}
+
+// centipawns() converts a Value to centipawns, with mates as MATE_CP - n
+
+pub fn centipawns(v: Value) -> i32 {
+    if v.abs() < Value::MATE - Value(MAX_PLY) {
+        v.0 * 100 / PawnValueEg.0
+    } else if v > Value::ZERO {
+        MATE_CP - ((Value::MATE - v).0 + 1) / 2
+    } else {
+        -MATE_CP + (Value::MATE + v).0 / 2
+    }
+}
+
+impl Player for EnginePlayer {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn new_game(&mut self) -> io::Result<()> {
+        self.engine.new_game();
+        Ok(())
+    }
+
+    fn go(
+        &mut self,
+        fen: &str,
+        moves: &[String],
+        limits: &LimitsType,
+        _timeout: Duration,
+    ) -> io::Result<Reply> {
+        let moves: Vec<&str> = moves.iter().map(|m| m.as_str()).collect();
+        self.engine
+            .set_position(fen, &moves)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+
+        let outcome = self.engine.go(limits, Vec::new(), false).wait();
+
+        Ok(Reply {
+            best: uci::move_str(outcome.best, false),
+            score: if outcome.score == Value::NONE {
+                None
+            } else {
+                Some(centipawns(outcome.score))
+            },
+            depth: outcome.depth,
+        })
+    }
+}
+
+// UciProcess is an external engine. Its output is read by a separate
+// thread, so that waiting for it can time out.
+
+struct UciProcess {
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+    name: String,
+    command: String,
+    config: PlayerConfig,
+}
+
+impl UciProcess {
+    fn new(command: &str, config: &PlayerConfig) -> io::Result<UciProcess> {
+        let mut child = Command::new(command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+
+        let (tx, rx) = channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(_) => break,
+                };
+                if tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut process = UciProcess {
+            child,
+            stdin,
+            lines: rx,
+            name: String::from(command),
+            command: String::from(command),
+            config: config.clone(),
+        };
+
+        let mut id_name = None;
+        process.send("uci")?;
+        process.read_until("uciok", HANDSHAKE_TIMEOUT, |line| {
+            if let Some(name) = line.strip_prefix("id name ") {
+                id_name = Some(String::from(name.trim()));
+            }
+        })?;
+        if let Some(name) = config.name.clone().or(id_name) {
+            process.name = name;
+        }
+
+        for (name, value) in config.options.iter() {
+            process.send(&format!("setoption name {} value {}", name, value))?;
+        }
+        process.ready()?;
+
+        Ok(process)
+    }
+
+    fn send(&mut self, cmd: &str) -> io::Result<()> {
+        writeln!(self.stdin, "{}", cmd)?;
+        self.stdin.flush()
+    }
+
+    // read_until() reads lines until one starting with the given token,
+    // which is returned. The lines before are passed to f.
+
+    fn read_until<F: FnMut(&str)>(
+        &mut self,
+        token: &str,
+        timeout: Duration,
+        mut f: F,
+    ) -> io::Result<String> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            match self.lines.recv_timeout(left) {
+                Ok(line) => {
+                    if line.split_whitespace().next() == Some(token) {
+                        return Ok(line);
+                    }
+                    f(&line);
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(io::Error::new(io::ErrorKind::TimedOut, "no answer in time"))
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "engine exited"))
+                }
+            }
+        }
+    }
+
+    // ready() waits until the engine has processed all commands
+
+    fn ready(&mut self) -> io::Result<()> {
+        self.send("isready")?;
+        self.read_until("readyok", HANDSHAKE_TIMEOUT, |_| {})?;
+        Ok(())
+    }
+
+    // stop() ends a search that timed out, so that its late 'bestmove' is
+    // not taken for the answer to the next 'go'. An engine that does not
+    // stop in time is restarted, or killed if that fails, so that the next
+    // command fails and the player is restarted after the game.
+
+    fn stop(&mut self) {
+        if self.send("stop").is_ok()
+            && self.read_until("bestmove", STOP_TIMEOUT, |_| {}).is_ok()
+        {
+            return;
+        }
+
+        let config = self.config.clone();
+        match UciProcess::new(&self.command, &config) {
+            Ok(process) => *self = process,
+            Err(_) => {
+                let _ = self.child.kill();
+            }
+        }
+    }
+}
+
+// parse_info() takes the depth and score of the best line from an 'info'
+// line. Bounds are taken as scores, and 'info string' lines are ignored.
+
+fn parse_info(line: &str, score: &mut Option<i32>, depth: &mut i32) {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    if tokens.first() != Some(&"info") {
+        return;
+    }
+
+    let mut i = 1;
+    while i + 1 < tokens.len() {
+        match tokens[i] {
+            "multipv" if tokens[i + 1] != "1" => return,
+            "depth" => *depth = tokens[i + 1].parse().unwrap_or(*depth),
+            "score" if i + 2 < tokens.len() => {
+                let n: Option<i32> = tokens[i + 2].parse().ok();
+                *score = match (tokens[i + 1], n) {
+                    ("cp", Some(n)) => Some(n),
+                    ("mate", Some(n)) if n > 0 => Some(MATE_CP - n),
+                    ("mate", Some(n)) => Some(-MATE_CP - n),
+                    _ => *score,
+                };
+            }
+            "pv" | "string" => return,
+            _ => {}
+        }
+        i += 1;
+    }
+}
+
+impl Player for UciProcess {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn new_game(&mut self) -> io::Result<()> {
+        self.send("ucinewgame")?;
+        self.ready()
+    }
+
+    fn go(
+        &mut self,
+        fen: &str,
+        moves: &[String],
+        limits: &LimitsType,
+        timeout: Duration,
+    ) -> io::Result<Reply> {
+        let mut cmd = format!("position fen {}", fen);
+        if !moves.is_empty() {
+            cmd.push_str(" moves ");
+            cmd.push_str(&moves.join(" "));
+        }
+        self.send(&cmd)?;
+        self.send(&format!(
+            "go wtime {} btime {} winc {} binc {}",
+            limits.time[WHITE.0 as usize],
+            limits.time[BLACK.0 as usize],
+            limits.inc[WHITE.0 as usize],
+            limits.inc[BLACK.0 as usize]
+        ))?;
+
+        let mut score = None;
+        let mut depth = 0;
+        let line = match self.read_until("bestmove", timeout, |line| {
+            parse_info(line, &mut score, &mut depth)
+        }) {
+            Ok(line) => line,
+            Err(e) => {
+                if e.kind() == io::ErrorKind::TimedOut {
+                    self.stop();
+                }
+                return Err(e);
+            }
+        };
+
+        Ok(Reply {
+            best: String::from(line.split_whitespace().nth(1).unwrap_or("")),
+            score,
+            depth,
+        })
+    }
+}
+
+impl Drop for UciProcess {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        for _ in 0..100 {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn create_player(config: &PlayerConfig) -> io::Result<Box<dyn Player>> {
+    match config.command {
+        Some(ref command) => Ok(Box::new(UciProcess::new(command, config)?)),
+        None => Ok(Box::new(EnginePlayer::new(config)?)),
+    }
+}
+
+// load_openings() reads one position per line, in FEN or EPD format.
+// Positions without move counters start at move 1.
+
+fn load_openings(file_name: &str) -> io::Result<Vec<String>> {
+    let mut openings = Vec::new();
+
+    for line in BufReader::new(File::open(file_name)?).lines() {
+        let line = line?;
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if tokens.len() < 4 || tokens[0].starts_with('#') {
+            continue;
+        }
+
+        let mut fen = tokens[..4].join(" ");
+        if tokens.len() >= 6
+            && tokens[4].parse::<u32>().is_ok()
+            && tokens[5].parse::<u32>().is_ok()
+        {
+            fen = tokens[..6].join(" ");
+        } else {
+            fen.push_str(" 0 1");
+        }
+
+        if Position::from_fen(&fen, false).is_ok() {
+            openings.push(fen);
+        }
+    }
+
+    Ok(openings)
+}
+
+fn color_name(c: Color) -> &'static str {
+    if c == WHITE {
+        "White"
+    } else {
+        "Black"
+    }
+}
+
+// loss() returns the result of a game lost by side c and its reason
+
+fn loss(c: Color, reason: &str) -> (&'static str, String) {
+    let result = if c == WHITE { "0-1" } else { "1-0" };
+    (result, format!("{} {}", color_name(c), reason))
+}
+
+// game_end() returns the result and reason if the game is over by the rules
+
+pub fn game_end(pos: &Position) -> Option<(&'static str, String)> {
+    let us = pos.side_to_move();
+
+    if MoveList::new::<Legal>(pos).len() == 0 {
+        return Some(if pos.checkers() != 0 {
+            loss(us, "is mated")
+        } else {
+            ("1/2-1/2", String::from("Draw by stalemate"))
+        });
+    }
+
+    let reason = if pos.rule50_count() > 99 {
+        "Draw by fifty moves rule"
+    } else if pos.is_draw(0) {
+        "Draw by 3-fold repetition"
+    } else if pos.pieces_pp(PAWN, ROOK) == 0
+        && pos.pieces_p(QUEEN) == 0
+        && !more_than_one(pos.pieces_pp(KNIGHT, BISHOP))
+    {
+        "Draw by insufficient mating material"
+    } else {
+        return None;
+    };
+
+    Some(("1/2-1/2", String::from(reason)))
+}
+
+// GameRecord is a finished game together with the reason it ended and
+// the player that failed to respond, if any
+
+struct GameRecord {
+    game: Game,
+    reason: String,
+    failed: Option<usize>,
+}
+
+// play_game() plays one game, with players[white] as white, from the
+// given opening
+
+fn play_game(
+    players: &mut [Box<dyn Player>],
+    white: usize,
+    opening: &str,
+    round: usize,
+    config: &MatchConfig,
+) -> GameRecord {
+    let mut pos = Box::new(Position::from_fen(opening, false).unwrap());
+    let mut game = Game::new();
+    game.set_tag("Event", "Rustfish match");
+    game.set_tag("Round", &round.to_string());
+    game.set_tag("White", &players[white].name());
+    game.set_tag("Black", &players[1 - white].name());
+    if opening != uci::START_FEN {
+        game.set_tag("SetUp", "1");
+        game.set_tag("FEN", opening);
+    }
+    game.set_tag(
+        "TimeControl",
+        &format!("{}+{}", config.time as f64 / 1000.0, config.inc as f64 / 1000.0),
+    );
+
+    let player_of = |c: Color| if c == WHITE { white } else { 1 - white };
+    let mut failed = None;
+    let mut moves: Vec<String> = Vec::new();
+    let mut clocks = [config.time; 2];
+    let mut draw_plies = 0;
+    let mut resign_moves = [0; 2];
+
+    for &c in [WHITE, BLACK].iter() {
+        if players[player_of(c)].new_game().is_err() {
+            failed = Some(player_of(c));
+        }
+    }
+
+    let (result, reason) = loop {
+        let us = pos.side_to_move();
+        if let Some(p) = failed {
+            break loss(if p == white { WHITE } else { BLACK }, "disconnects");
+        }
+        if let Some(end) = game_end(&pos) {
+            break end;
+        }
+
+        let mut limits = LimitsType::new();
+        limits.time = clocks;
+        limits.inc = [config.inc; 2];
+        let timeout = Duration::from_millis((clocks[us.0 as usize] + GRACE_TIME) as u64);
+
+        let start = Instant::now();
+        let reply = players[player_of(us)].go(opening, &moves, &limits, timeout);
+        let elapsed = start.elapsed();
+
+        let reply = match reply {
+            Ok(reply) => reply,
+            Err(ref e) if e.kind() == io::ErrorKind::TimedOut => break loss(us, "loses on time"),
+            Err(_) => {
+                failed = Some(player_of(us));
+                continue;
+            }
+        };
+
+        let clock = &mut clocks[us.0 as usize];
+        *clock -= elapsed.as_millis() as i64;
+        if *clock < 0 {
+            break loss(us, "loses on time");
+        }
+        *clock += config.inc;
+
+        let m = uci::to_move(&pos, &reply.best);
+        if m == Move::NONE {
+            break loss(us, &format!("makes an illegal move: {}", reply.best));
+        }
+
+        let move_number = pos.game_ply() / 2 + 1;
+        game.push(&mut pos, m);
+        game.moves.last_mut().unwrap().comment = Some(format!(
+            "{}/{} {:.3}s",
+            match reply.score {
+                Some(s) if s.abs() > MATE_CP / 2 => {
+                    format!("{}M{}", if s > 0 { "+" } else { "-" }, MATE_CP - s.abs())
+                }
+                Some(s) => format!("{:+.2}", s as f64 / 100.0),
+                None => String::from("book"),
+            },
+            reply.depth,
+            elapsed.as_secs_f64()
+        ));
+        moves.push(uci::move_str(m, false));
+        let gives_check = pos.gives_check(m);
+        pos.do_move(m, gives_check);
+
+        // Adjudication
+        let score = reply.score.unwrap_or(0);
+        if reply.score.is_some()
+            && move_number >= config.draw_move_number
+            && score.abs() <= config.draw_score
+        {
+            draw_plies += 1;
+        } else {
+            draw_plies = 0;
+        }
+        if config.draw_move_count > 0 && draw_plies >= 2 * config.draw_move_count {
+            break ("1/2-1/2", String::from("Draw by adjudication"));
+        }
+
+        let resign = &mut resign_moves[us.0 as usize];
+        *resign = if reply.score.is_some() && score <= -config.resign_score {
+            *resign + 1
+        } else {
+            0
+        };
+        if config.resign_move_count > 0 && *resign >= config.resign_move_count {
+            break loss(us, "resigns");
+        }
+    };
+
+    game.set_result(result);
+    GameRecord {
+        game,
+        reason,
+        failed,
+    }
+}
+
+// Progress is the state of a match shared by the threads playing it
+
+struct Progress {
+    names: [String; 2],
+    result: MatchResult,
+    // Points of the first player, out of 2, in the first finished game of
+    // each unfinished pair
+    pending: HashMap<usize, usize>,
+    sprt_status: SprtStatus,
+    // Set when no more games are to be started
+    stopped: bool,
+    pgn: Option<File>,
+}
+
+// worker() plays games until all have been started or the match is
+// stopped. Each worker has its own pair of players.
+
+fn worker(config: &MatchConfig, openings: &[String], next: &AtomicUsize, progress: &Mutex<Progress>) {
+    let mut players = Vec::new();
+    for p in config.players.iter() {
+        match create_player(p) {
+            Ok(player) => players.push(player),
+            Err(e) => {
+                println!("info string Unable to start player: {}", e);
+                progress.lock().unwrap().stopped = true;
+                return;
+            }
+        }
+    }
+
+    loop {
+        let i = next.fetch_add(1, Ordering::SeqCst);
+        if i >= config.games || progress.lock().unwrap().stopped {
+            break;
+        }
+
+        let white = i % 2;
+        let opening = &openings[(i / 2) % openings.len()];
+        let record = play_game(&mut players, white, opening, i + 1, config);
+
+        // A player that stopped responding is restarted for the next game
+        if let Some(p) = record.failed {
+            match create_player(&config.players[p]) {
+                Ok(player) => players[p] = player,
+                Err(e) => {
+                    println!("info string Unable to restart player: {}", e);
+                    progress.lock().unwrap().stopped = true;
+                }
+            }
+        }
+
+        let mut progress = progress.lock().unwrap();
+        let game = &record.game;
+        let points = match (game.result.as_str(), white) {
+            ("1-0", 0) | ("0-1", 1) => 2,
+            ("1/2-1/2", _) => 1,
+            _ => 0,
+        };
+        match points {
+            2 => progress.result.wins += 1,
+            1 => progress.result.draws += 1,
+            _ => progress.result.losses += 1,
+        }
+        match progress.pending.remove(&(i / 2)) {
+            Some(other) => progress.result.pairs[points + other] += 1,
+            None => {
+                progress.pending.insert(i / 2, points);
+            }
+        }
+
+        if let Some(ref mut file) = progress.pgn {
+            if game.write(file).and_then(|_| writeln!(file)).is_err() {
+                println!("info string Unable to write the PGN file");
+            }
+        }
+
+        let r = progress.result;
+        println!(
+            "Finished game {} ({} vs {}): {} {{{}}}",
+            i + 1,
+            game.tag("White").unwrap(),
+            game.tag("Black").unwrap(),
+            game.result,
+            record.reason
+        );
+        println!(
+            "Score of {} vs {}: {} - {} - {}  [{:.3}] {}",
+            progress.names[0],
+            progress.names[1],
+            r.wins,
+            r.losses,
+            r.draws,
+            r.score(),
+            r.games()
+        );
+
+        // No more games are started once the test is decided
+        if let Some(ref sprt) = config.sprt {
+            let counts = r.counts();
+            let (lower, upper) = sprt.bounds();
+            println!(
+                "SPRT: llr {:.3}, lbound {:.3}, ubound {:.3}",
+                sprt.llr(&counts),
+                lower,
+                upper
+            );
+            if progress.sprt_status == SprtStatus::Continue {
+                progress.sprt_status = sprt.status(&counts);
+                if progress.sprt_status != SprtStatus::Continue {
+                    progress.stopped = true;
+                }
+            }
+        }
+    }
+}
+
+// run() plays a match and returns its result, or None if it could not be
+// started
+
+pub fn run(config: &MatchConfig) -> Option<MatchResult> {
+    let openings = match config.openings {
+        Some(ref file_name) => match load_openings(file_name) {
+            Ok(ref openings) if openings.is_empty() => {
+                println!("info string No positions found in {}", file_name);
+                return None;
+            }
+            Ok(openings) => openings,
+            Err(e) => {
+                println!("info string Unable to read {}: {}", file_name, e);
+                return None;
+            }
+        },
+        None => OPENINGS.iter().map(|&fen| String::from(fen)).collect(),
+    };
+
+    let pgn = match config.pgn {
+        Some(ref file_name) => {
+            match OpenOptions::new().create(true).append(true).open(file_name) {
+                Ok(file) => Some(file),
+                Err(e) => {
+                    println!("info string Unable to open {}: {}", file_name, e);
+                    return None;
+                }
+            }
+        }
+        None => None,
+    };
+
+    // Names of identical players are told apart by their number
+    let mut names = [String::new(), String::new()];
+    for (i, p) in config.players.iter().enumerate() {
+        names[i] = match (&p.name, &p.command) {
+            (Some(name), _) => name.clone(),
+            (_, Some(command)) => command.clone(),
+            _ => String::from(misc::engine_info(true).lines().next().unwrap()),
+        };
+    }
+    if names[0] == names[1] {
+        names[0].push_str(" #1");
+        names[1].push_str(" #2");
+    }
+
+    let mut config = config.clone();
+    for (i, p) in config.players.iter_mut().enumerate() {
+        p.name = Some(names[i].clone());
+    }
+
+    let progress = Arc::new(Mutex::new(Progress {
+        names,
+        result: MatchResult {
+            wins: 0,
+            draws: 0,
+            losses: 0,
+            pairs: [0; 5],
+        },
+        pending: HashMap::new(),
+        sprt_status: SprtStatus::Continue,
+        stopped: false,
+        pgn,
+    }));
+    let next = Arc::new(AtomicUsize::new(0));
+    let config = Arc::new(config);
+    let openings = Arc::new(openings);
+
+    let mut handles = Vec::new();
+    for _ in 0..std::cmp::max(config.concurrency, 1) {
+        let (config, openings, next, progress) =
+            (config.clone(), openings.clone(), next.clone(), progress.clone());
+        // The engines need a large stack, as in main()
+        let handle = thread::Builder::new()
+            .stack_size(16 * 1024 * 1024)
+            .spawn(move || worker(&config, &openings, &next, &progress))
+            .unwrap();
+        handles.push(handle);
+    }
+    for handle in handles {
+        let _ = handle.join();
+    }
+
+    let progress = progress.lock().unwrap();
+    let result = progress.result;
+    if result.games() > 0 {
+        let (elo, margin) = result.elo();
+        let p = result.pairs;
+        println!(
+            "Elo difference: {:.1} +/- {:.1}, LOS: {:.1} %",
+            elo,
+            margin,
+            100.0 * stats::los(&result.counts())
+        );
+        println!("Ptnml(0-2): {}, {}, {}, {}, {}", p[0], p[1], p[2], p[3], p[4]);
+    }
+    match progress.sprt_status {
+        SprtStatus::AcceptH0 => println!("SPRT: H0 was accepted"),
+        SprtStatus::AcceptH1 => println!("SPRT: H1 was accepted"),
+        SprtStatus::Continue => {}
+    }
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(lines: &[&str]) -> (Option<i32>, i32) {
+        let (mut score, mut depth) = (None, 0);
+        for line in lines {
+            parse_info(line, &mut score, &mut depth);
+        }
+        (score, depth)
+    }
+
+    #[test]
+    fn info_lines() {
+        assert_eq!(
+            info(&["info depth 12 seldepth 18 multipv 1 score cp 35 nodes 1000 pv e2e4 e7e5"]),
+            (Some(35), 12)
+        );
+        assert_eq!(info(&["info depth 20 score mate 3 pv h5f7"]), (Some(MATE_CP - 3), 20));
+        assert_eq!(info(&["info depth 20 score mate -2 pv g1h1"]), (Some(-MATE_CP + 2), 20));
+        assert_eq!(
+            info(&["info depth 9 score cp 50 lowerbound nodes 10"]),
+            (Some(50), 9)
+        );
+        assert_eq!(
+            info(&["info depth 9 score cp -20 upperbound nodes 10"]),
+            (Some(-20), 9)
+        );
+
+        // Other lines than the best one, and lines without a score, keep
+        // the last score
+        assert_eq!(
+            info(&[
+                "info depth 10 multipv 1 score cp 15 pv d2d4",
+                "info depth 10 multipv 2 score cp -40 pv a2a3",
+                "info depth 11 currmove e2e4 currmovenumber 1",
+                "info string depth 40 score cp 999",
+                "bestmove d2d4",
+            ]),
+            (Some(15), 11)
+        );
+    }
+
+    #[test]
+    fn centipawn_scores() {
+        assert_eq!(centipawns(PawnValueEg), 100);
+        assert_eq!(centipawns(-PawnValueEg * 2), -200);
+        assert_eq!(centipawns(Value::MATE - 1), MATE_CP - 1);
+        assert_eq!(centipawns(Value::MATE - 5), MATE_CP - 3);
+        assert_eq!(centipawns(-Value::MATE + 2), -MATE_CP + 1);
+    }
+
+    fn end(fen: &str, moves: &[&str]) -> Option<(&'static str, String)> {
+        let mut pos = Position::from_fen(fen, false).unwrap();
+        for &s in moves {
+            let m = uci::to_move(&pos, s);
+            assert!(m != Move::NONE, "{}", s);
+            let gives_check = pos.gives_check(m);
+            pos.do_move(m, gives_check);
+        }
+        game_end(&pos)
+    }
+
+    #[test]
+    fn game_ends() {
+        Engine::new();
+
+        let draw = |reason: &str| Some(("1/2-1/2", String::from(reason)));
+        assert_eq!(
+            end("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3", &[]),
+            Some(("0-1", String::from("White is mated")))
+        );
+        assert_eq!(
+            end("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", &["a1a8"]),
+            Some(("1-0", String::from("Black is mated")))
+        );
+        assert_eq!(end("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1", &[]), draw("Draw by stalemate"));
+        assert_eq!(end("8/8/4k3/8/8/4K3/4R3/8 w - - 99 80", &[]), None);
+        assert_eq!(
+            end("8/8/4k3/8/8/4K3/4R3/8 w - - 99 80", &["e2a2"]),
+            draw("Draw by fifty moves rule")
+        );
+
+        let knights = ["g1f3", "g8f6", "f3g1", "f6g8"];
+        assert_eq!(end(uci::START_FEN, &knights), None);
+        assert_eq!(
+            end(uci::START_FEN, &[knights, knights].concat()),
+            draw("Draw by 3-fold repetition")
+        );
+
+        let insufficient = draw("Draw by insufficient mating material");
+        assert_eq!(end("8/8/4k3/8/8/4K3/8/8 w - - 0 1", &[]), insufficient);
+        assert_eq!(end("8/8/4k3/8/8/4K3/4B3/8 w - - 0 1", &[]), insufficient);
+        assert_eq!(end("8/8/4k3/8/8/4K3/4N3/8 w - - 0 1", &[]), insufficient);
+        assert_eq!(end("8/8/4k3/8/8/4K3/4NB2/8 w - - 0 1", &[]), None);
+        assert_eq!(end("8/8/4k3/8/8/4K3/4P3/8 w - - 0 1", &[]), None);
+    }
+
+    #[test]
+    fn openings() {
+        Engine::new();
+
+        let file_name = std::env::temp_dir().join(format!("openings-{}.epd", std::process::id()));
+        std::fs::write(
+            &file_name,
+            "# Comments and invalid positions are skipped\n\
+             rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1\n\
+             rnbqkbnr/pppppppp/8/8/3P4/8/PPP1PPPP/RNBQKBNR b KQkq - bm d5; id \"d4\";\n\
+             r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3\n\
+             \n\
+             not a position\n\
+             8/8/8/8/8/8/8/8 w - - 0 1\n",
+        )
+        .unwrap();
+        let openings = load_openings(file_name.to_str().unwrap());
+        std::fs::remove_file(&file_name).unwrap();
+
+        assert_eq!(
+            openings.unwrap(),
+            [
+                "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1",
+                "rnbqkbnr/pppppppp/8/8/3P4/8/PPP1PPPP/RNBQKBNR b KQkq - 0 1",
+                "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3",
+            ]
+        );
+        assert!(load_openings("/nonexistent/openings.epd").is_err());
+    }
+}
@@ -3,6 +3,7 @@
 use types::*;
 
 use std;
+use std::cell::{Cell, UnsafeCell};
 
 // TTEntry struct is the 10 bytes transposition-table entry, defined as below:
 //
@@ -79,142 +80,151 @@ struct Cluster {
     _padding: [u8; 2], // Align to a divisor of the cache line size
 }
 
-static mut CLUSTER_COUNT: usize = 0;
-static mut TABLE: *mut Cluster = 0 as *mut Cluster;
-static mut TABLE_CAP: usize = 0;
-static mut GENERATION8: u8 = 0;
-
-pub fn new_search() {
-    unsafe {
-        GENERATION8 += 4; // Lower two bits are used by bound
+impl Cluster {
+    fn new() -> Cluster {
+        unsafe { std::mem::zeroed() }
     }
 }
 
-pub fn generation() -> u8 {
-    unsafe { GENERATION8 }
-}
+// TranspositionTable is shared by all search threads of an engine. Entries
+// are read and written without any locking, exactly as in Stockfish: a
+// torn entry is detected by the move validation in the search.
 
-// The lowest order bits of the key are used to get the index of the cluster
-fn cluster(key: Key) -> &'static mut Cluster {
-    unsafe {
-        let p: *mut Cluster =
-            TABLE.offset((((key.0 as u32 as u64) * (CLUSTER_COUNT as u64)) >> 32) as isize);
-        let c: &'static mut Cluster = &mut *p;
-        c
-    }
+pub struct TranspositionTable {
+    table: UnsafeCell<Vec<Cluster>>,
+    generation8: Cell<u8>,
 }
 
-// tt::resize() sets the size of the transposition table, measured in
-// megabytes. The transposition table consists of a power of 2 number of
-// clusters and each cluster consists of CLUSTER_SIZE number of TTEntry.
+// Entries are shared between the search threads without locking
+unsafe impl Sync for TranspositionTable {}
 
-pub fn resize(mb_size: usize) {
-    let new_cluster_count = mb_size * 1024 * 1024 / std::mem::size_of::<Cluster>();
-
-    unsafe {
-        if new_cluster_count == CLUSTER_COUNT {
-            return;
+impl TranspositionTable {
+    pub fn new() -> TranspositionTable {
+        TranspositionTable {
+            table: UnsafeCell::new(Vec::new()),
+            generation8: Cell::new(0),
         }
+    }
 
-        free();
+    pub fn new_search(&self) {
+        // Lower two bits are used by bound
+        self.generation8.set(self.generation8.get().wrapping_add(4));
+    }
 
-        CLUSTER_COUNT = new_cluster_count;
+    pub fn generation(&self) -> u8 {
+        self.generation8.get()
+    }
 
-        let mut v: Vec<Cluster> = Vec::with_capacity(new_cluster_count);
-        TABLE = v.as_mut_ptr();
-        TABLE_CAP = v.capacity();
-        // forget in order to prevent deallocation by dropping
-        std::mem::forget(v);
+    // The lowest order bits of the key are used to get the index of the cluster
+    fn cluster(&self, key: Key) -> &'static mut Cluster {
+        unsafe {
+            let clusters = &mut *self.table.get();
+            let idx = (((key.0 as u32 as u64) * (clusters.len() as u64)) >> 32) as usize;
+            let p: *mut Cluster = clusters.as_mut_ptr().add(idx);
+            let c: &'static mut Cluster = &mut *p;
+            c
+        }
     }
-}
 
-// tt::free() deallocates the transposition table.
+    // resize() sets the size of the transposition table, measured in
+    // megabytes. The transposition table consists of a power of 2 number of
+    // clusters and each cluster consists of CLUSTER_SIZE number of TTEntry.
+    // It must not be called while the search threads are running.
+
+    pub fn resize(&self, mb_size: usize) {
+        let new_cluster_count = mb_size * 1024 * 1024 / std::mem::size_of::<Cluster>();
+
+        let clusters = unsafe { &mut *self.table.get() };
 
-pub fn free() {
-    unsafe {
-        if !TABLE.is_null() {
-            let _ = Vec::from_raw_parts(TABLE, 0, TABLE_CAP);
-            // deallocate by dropping
+        if new_cluster_count == clusters.len() {
+            return;
         }
-    }
-}
 
-// tt::clear() clears the entire transposition table. It is called whenever
-// the table is resized or when the user asks the program to clear the table
-// (via the UCI interface).
-
-pub fn clear() {
-    let tt_slice = unsafe { std::slice::from_raw_parts_mut(TABLE, CLUSTER_COUNT) };
-
-    for cluster in tt_slice.iter_mut() {
-        for tte in cluster.entry.iter_mut() {
-            tte.key16 = 0;
-            tte.move16 = 0;
-            tte.value16 = 0;
-            tte.eval16 = 0;
-            tte.gen_bound8 = 0;
-            tte.depth8 = 0;
-            tte.key16 = 0;
+        *clusters = Vec::new();
+        clusters.reserve_exact(new_cluster_count);
+        for _ in 0..new_cluster_count {
+            clusters.push(Cluster::new());
         }
     }
-}
 
-// tt::probe() looks up the current position in the transposition table. It
-// returns true and a pointer to the TTentry if the position is found.
-// Otherwise, it returns false and a pointer to an empty or least valuable
-// TTEntry to be replaced later. The replace value of an entry is calculated
-// as its depth minus 8 times its relative age. TTEntry t1 is considered more
-// valuable than TTEntry t2 if its replace value is greater than that of t2.
-
-pub fn probe(key: Key) -> (&'static mut TTEntry, bool) {
-    let cl = cluster(key);
-    // Use the high 16 bits of the hash key as key inside the cluster
-    let key16 = (key.0 >> 48) as u16;
-
-    for i in 0..CLUSTER_SIZE {
-        if cl.entry[i].key16 == 0 || cl.entry[i].key16 == key16 {
-            if cl.entry[i].gen_bound8 & 0xfc != generation() && cl.entry[i].key16 != 0 {
-                cl.entry[i].gen_bound8 = generation() | (cl.entry[i].bound().0 as u8);
+    // clear() clears the entire transposition table. It is called whenever
+    // the table is resized or when the user asks the program to clear the
+    // table (via the UCI interface).
+
+    pub fn clear(&self) {
+        let clusters = unsafe { &mut *self.table.get() };
+
+        for cluster in clusters.iter_mut() {
+            for tte in cluster.entry.iter_mut() {
+                tte.key16 = 0;
+                tte.move16 = 0;
+                tte.value16 = 0;
+                tte.eval16 = 0;
+                tte.gen_bound8 = 0;
+                tte.depth8 = 0;
             }
-            let found = cl.entry[i].key16 != 0;
-            return (&mut (cl.entry[i]), found);
         }
     }
 
-    // Find an entry to be replaced according to the replacement strategy
-    let mut r = 0;
-    for i in 1..CLUSTER_SIZE {
-        // Due to our packed storage format for generation and its cyclic
-        // nature we add 259 (256 is the modulus plus 3 to keep the lowest
-        // two bound bits from affecting the result) to calculate the entry
-        // age correctly even after generation8 overflows into the next cycle.
-        if (cl.entry[r].depth8 as i32)
-            - ((259 + (generation() as i32) - (cl.entry[r].gen_bound8 as i32)) & 0xfc) * 2
-            > (cl.entry[i].depth8 as i32)
-                - ((259 + (generation() as i32) - (cl.entry[i].gen_bound8 as i32)) & 0xfc) * 2
-        {
-            r = i;
+    // probe() looks up the current position in the transposition table. It
+    // returns true and a pointer to the TTentry if the position is found.
+    // Otherwise, it returns false and a pointer to an empty or least valuable
+    // TTEntry to be replaced later. The replace value of an entry is
+    // calculated as its depth minus 8 times its relative age. TTEntry t1 is
+    // considered more valuable than TTEntry t2 if its replace value is
+    // greater than that of t2.
+
+    pub fn probe(&self, key: Key) -> (&'static mut TTEntry, bool) {
+        let cl = self.cluster(key);
+        let generation = self.generation();
+        // Use the high 16 bits of the hash key as key inside the cluster
+        let key16 = (key.0 >> 48) as u16;
+
+        for i in 0..CLUSTER_SIZE {
+            if cl.entry[i].key16 == 0 || cl.entry[i].key16 == key16 {
+                if cl.entry[i].gen_bound8 & 0xfc != generation && cl.entry[i].key16 != 0 {
+                    cl.entry[i].gen_bound8 = generation | (cl.entry[i].bound().0 as u8);
+                }
+                let found = cl.entry[i].key16 != 0;
+                return (&mut (cl.entry[i]), found);
+            }
         }
-    }
 
-    (&mut (cl.entry[r]), false)
-}
+        // Find an entry to be replaced according to the replacement strategy
+        let mut r = 0;
+        for i in 1..CLUSTER_SIZE {
+            // Due to our packed storage format for generation and its cyclic
+            // nature we add 259 (256 is the modulus plus 3 to keep the lowest
+            // two bound bits from affecting the result) to calculate the entry
+            // age correctly even after generation8 overflows into the next
+            // cycle.
+            if (cl.entry[r].depth8 as i32)
+                - ((259 + (generation as i32) - (cl.entry[r].gen_bound8 as i32)) & 0xfc) * 2
+                > (cl.entry[i].depth8 as i32)
+                    - ((259 + (generation as i32) - (cl.entry[i].gen_bound8 as i32)) & 0xfc) * 2
+            {
+                r = i;
+            }
+        }
 
-// tt::hashfull() returns an approximation of the hashtable occupation during
-// a search. The hash is x permill full, as per UCI protocol.
+        (&mut (cl.entry[r]), false)
+    }
 
-pub fn hashfull() -> i32 {
-    let tt_slice = unsafe { std::slice::from_raw_parts(TABLE, 1000 / CLUSTER_SIZE) };
+    // hashfull() returns an approximation of the hashtable occupation during
+    // a search. The hash is x permill full, as per UCI protocol.
 
-    let mut cnt = 0;
+    pub fn hashfull(&self) -> i32 {
+        let clusters = unsafe { &*self.table.get() };
+        let mut cnt = 0;
 
-    for cluster in tt_slice.iter() {
-        for tte in cluster.entry.iter() {
-            if tte.gen_bound8 & 0xfc == generation() {
-                cnt += 1;
+        for cluster in clusters.iter().take(1000 / CLUSTER_SIZE) {
+            for tte in cluster.entry.iter() {
+                if tte.gen_bound8 & 0xfc == self.generation() {
+                    cnt += 1;
+                }
             }
         }
-    }
 
-    cnt
+        cnt
+    }
 }
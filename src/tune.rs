@@ -0,0 +1,332 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+// Runtime-tunable evaluation and search parameters, for tuning with SPSA.
+//
+// tunable! defines constants that can be changed at runtime when the engine
+// is built with the "tune" feature. Each non-zero integer component is then
+// exposed as a UCI spin option named after the constant, for instance
+// "TEMPO", "RAZOR_MARGIN[1]" or "MOBILITY_BONUS[0][4].mg". The range of an
+// option is [0, 2 * v] for a positive default value v and [2 * v, 0] for a
+// negative one.
+//
+// param!(NAME) reads a parameter and param!(NAME[i][j]) an element of an
+// array parameter. In the default build tunable! defines a plain const and
+// param! is that const, so tuning costs nothing.
+//
+// The parameters are process-wide, so setting one affects all engines of
+// the process. They are kept as 32-bit words that are only read and
+// written atomically, so they can be changed while a search is running.
+
+#[cfg(not(feature = "tune"))]
+macro_rules! tunable {
+    ($($(#[$attr:meta])* pub $name:ident: $t:ty = $v:expr;)*) => {
+        $($(#[$attr])* pub const $name: $t = $v;)*
+    };
+}
+
+#[cfg(feature = "tune")]
+macro_rules! tunable {
+    ($($(#[$attr:meta])* pub $name:ident: $t:ty = $v:expr;)*) => {
+        $($(#[$attr])* pub static $name: $crate::tune::Tunable<$t> = $crate::tune::Tunable::new($v);)*
+    };
+}
+
+#[cfg(not(feature = "tune"))]
+macro_rules! param {
+    ($name:path $([$idx:expr])*) => {
+        $name$([$idx])*
+    };
+}
+
+#[cfg(feature = "tune")]
+macro_rules! param {
+    ($name:path $([$idx:expr])*) => {
+        {
+            // Some callers are inside unsafe blocks already
+            #[allow(unused_unsafe)]
+            let v = $name.load(|p| unsafe { ::std::ptr::addr_of!((*p)$([$idx])*) });
+            v
+        }
+    };
+}
+
+#[cfg(feature = "tune")]
+pub use self::registry::*;
+
+#[cfg(feature = "tune")]
+mod registry {
+    use evaluate;
+    use material;
+    use pawns;
+    use psqt;
+    use search;
+    use types::*;
+
+    use std;
+    use std::cell::UnsafeCell;
+    use std::mem::{size_of, MaybeUninit};
+    use std::sync::atomic::{AtomicI32, Ordering};
+    use std::sync::Mutex;
+
+    /// Tunable holds the value of a parameter. The value consists of i32
+    /// components (see Components), which are only accessed as AtomicI32.
+    pub struct Tunable<T>(UnsafeCell<T>);
+
+    unsafe impl<T> Sync for Tunable<T> {}
+
+    // read() copies the value at p, which points into a parameter, word by
+    // word
+
+    unsafe fn read<E: Copy>(p: *const E) -> E {
+        let mut v = MaybeUninit::<E>::uninit();
+        let src = p as *const AtomicI32;
+        let dst = v.as_mut_ptr() as *mut i32;
+        for i in 0..size_of::<E>() / 4 {
+            *dst.add(i) = (*src.add(i)).load(Ordering::Relaxed);
+        }
+        v.assume_init()
+    }
+
+    impl<T: Copy> Tunable<T> {
+        pub const fn new(v: T) -> Tunable<T> {
+            Tunable(UnsafeCell::new(v))
+        }
+
+        // load() reads the value or the element of it that f returns a
+        // pointer to. Only the element is copied, see param!.
+
+        pub fn load<E: Copy, F: FnOnce(*const T) -> *const E>(&self, f: F) -> E {
+            unsafe { read(f(self.0.get())) }
+        }
+
+        pub fn get(&self) -> T {
+            self.load(|p| p)
+        }
+
+        fn store(&self, v: T) {
+            let src = &v as *const T as *const i32;
+            let dst = self.0.get() as *const AtomicI32;
+            for i in 0..size_of::<T>() / 4 {
+                unsafe { (*dst.add(i)).store(*src.add(i), Ordering::Relaxed) }
+            }
+        }
+    }
+
+    // Serializes the changes of parameters, which write a whole value
+    static WRITE: Mutex<()> = Mutex::new(());
+
+    // Components gives access to the integer components of a parameter
+    // value, together with their names. source() writes the value as Rust
+    // source code, with nested arrays indented by the given level.
+
+    pub trait Components: Copy {
+        const SCALAR: bool = true;
+
+        fn visit(&mut self, name: &str, f: &mut dyn FnMut(&str, &mut i32));
+        fn source(&self, indent: usize) -> String;
+    }
+
+    impl Components for i32 {
+        fn visit(&mut self, name: &str, f: &mut dyn FnMut(&str, &mut i32)) {
+            f(name, self);
+        }
+
+        fn source(&self, _: usize) -> String {
+            format!("{}", self)
+        }
+    }
+
+    impl Components for Value {
+        fn visit(&mut self, name: &str, f: &mut dyn FnMut(&str, &mut i32)) {
+            f(name, &mut self.0);
+        }
+
+        fn source(&self, _: usize) -> String {
+            format!("Value({})", self.0)
+        }
+    }
+
+    impl Components for Score {
+        fn visit(&mut self, name: &str, f: &mut dyn FnMut(&str, &mut i32)) {
+            let mut mg = self.mg().0;
+            let mut eg = self.eg().0;
+            f(&format!("{}.mg", name), &mut mg);
+            f(&format!("{}.eg", name), &mut eg);
+            *self = Score::make(mg, eg);
+        }
+
+        fn source(&self, _: usize) -> String {
+            format!("s!({}, {})", self.mg().0, self.eg().0)
+        }
+    }
+
+    impl<T: Components, const N: usize> Components for [T; N] {
+        const SCALAR: bool = false;
+
+        fn visit(&mut self, name: &str, f: &mut dyn FnMut(&str, &mut i32)) {
+            for (i, v) in self.iter_mut().enumerate() {
+                v.visit(&format!("{}[{}]", name, i), f);
+            }
+        }
+
+        // Arrays of numbers are written on one line, other arrays with one
+        // element per line
+        fn source(&self, indent: usize) -> String {
+            let elems: Vec<String> = self.iter().map(|v| v.source(indent + 1)).collect();
+            if T::SCALAR {
+                format!("[{}]", elems.join(", "))
+            } else {
+                let pad = "    ".repeat(indent + 1);
+                let mut s = String::from("[\n");
+                for e in elems {
+                    s.push_str(&format!("{}{},\n", pad, e));
+                }
+                s.push_str(&"    ".repeat(indent));
+                s.push(']');
+                s
+            }
+        }
+    }
+
+    // Param gives access to the components of a parameter. Changes made by
+    // visit() are written back at once.
+
+    pub trait Param: Sync {
+        fn visit(&self, name: &str, f: &mut dyn FnMut(&str, &mut i32));
+        fn source(&self, name: &str) -> String;
+    }
+
+    impl<T: Components> Param for Tunable<T> {
+        fn visit(&self, name: &str, f: &mut dyn FnMut(&str, &mut i32)) {
+            let _lock = WRITE.lock().unwrap();
+            let mut v = self.get();
+            v.visit(name, f);
+            self.store(v);
+        }
+
+        // source() returns the declaration of the parameter, as written in
+        // a tunable! block
+        fn source(&self, name: &str) -> String {
+            let type_name = std::any::type_name::<T>().replace("rustfish::types::", "");
+            format!("    pub {}: {} = {};\n", name, type_name, self.get().source(1))
+        }
+    }
+
+    // params() lists the tunable parameters with their option names
+
+    fn params() -> Vec<(&'static str, &'static dyn Param)> {
+        vec![
+            ("TEMPO", &evaluate::TEMPO),
+            ("LAZY_THRESHOLD", &evaluate::LAZY_THRESHOLD),
+            ("MOBILITY_BONUS", &evaluate::MOBILITY_BONUS),
+            ("KING_ATTACK_WEIGHTS", &evaluate::KING_ATTACK_WEIGHTS),
+            ("RAZOR_MARGIN", &search::RAZOR_MARGIN),
+            ("CAPTURE_PRUNE_MARGIN", &search::CAPTURE_PRUNE_MARGIN),
+            ("FUTILITY_MARGIN", &search::FUTILITY_MARGIN),
+        ]
+    }
+
+    // eval_params() lists the evaluation weights optimized by the Texel
+    // tuner, see texel.rs. They are not exposed as UCI options.
+
+    pub fn eval_params() -> Vec<(&'static str, &'static dyn Param)> {
+        vec![
+            ("MOBILITY_BONUS", &evaluate::MOBILITY_BONUS),
+            ("OUTPOST", &evaluate::OUTPOST),
+            ("ROOK_ON_FILE", &evaluate::ROOK_ON_FILE),
+            ("THREAT_BY_MINOR", &evaluate::THREAT_BY_MINOR),
+            ("THREAT_BY_ROOK", &evaluate::THREAT_BY_ROOK),
+            ("THREAT_BY_KING", &evaluate::THREAT_BY_KING),
+            ("PASSED", &evaluate::PASSED),
+            ("PASSED_FILE", &evaluate::PASSED_FILE),
+            ("KING_PROTECTOR", &evaluate::KING_PROTECTOR),
+            ("BISHOP_PAWNS", &evaluate::BISHOP_PAWNS),
+            ("CLOSE_ENEMIES", &evaluate::CLOSE_ENEMIES),
+            ("CONNECTIVITY", &evaluate::CONNECTIVITY),
+            ("CORNERED_BISHOP", &evaluate::CORNERED_BISHOP),
+            ("HANGING", &evaluate::HANGING),
+            ("HINDER_PASSED_PAWN", &evaluate::HINDER_PASSED_PAWN),
+            ("KNIGHT_ON_QUEEN", &evaluate::KNIGHT_ON_QUEEN),
+            ("LONG_DIAGONAL_BISHOP", &evaluate::LONG_DIAGONAL_BISHOP),
+            ("MINOR_BEHIND_PAWN", &evaluate::MINOR_BEHIND_PAWN),
+            ("OVERLOAD", &evaluate::OVERLOAD),
+            ("PAWNLESS_FLANK", &evaluate::PAWNLESS_FLANK),
+            ("ROOK_ON_PAWN", &evaluate::ROOK_ON_PAWN),
+            ("THREAT_BY_PAWN_PUSH", &evaluate::THREAT_BY_PAWN_PUSH),
+            ("THREAT_BY_RANK", &evaluate::THREAT_BY_RANK),
+            ("THREAT_BY_SAFE_PAWN", &evaluate::THREAT_BY_SAFE_PAWN),
+            ("THREAT_BY_SLIDER_ON_QUEEN", &evaluate::THREAT_BY_SLIDER_ON_QUEEN),
+            ("TRAPPED_ROOK", &evaluate::TRAPPED_ROOK),
+            ("WEAK_QUEEN", &evaluate::WEAK_QUEEN),
+            ("WEAK_UNOPPOSED_PAWN", &evaluate::WEAK_UNOPPOSED_PAWN),
+            ("ISOLATED", &pawns::ISOLATED),
+            ("BACKWARD", &pawns::BACKWARD),
+            ("DOUBLED", &pawns::DOUBLED),
+            ("BONUS", &psqt::BONUS),
+            ("QUADRATIC_OURS", &material::QUADRATIC_OURS),
+            ("QUADRATIC_THEIRS", &material::QUADRATIC_THEIRS),
+        ]
+    }
+
+    static mut OPTIONS: Vec<(&'static str, i32)> = Vec::new();
+
+    // tune::init() collects the names and default values of the options at
+    // startup, before any parameter can be changed.
+
+    pub fn init() {
+        let mut options = Vec::new();
+        for (name, p) in params() {
+            p.visit(name, &mut |n, v| {
+                if *v != 0 {
+                    options.push((&*Box::leak(String::from(n).into_boxed_str()), *v));
+                }
+            });
+        }
+        unsafe {
+            OPTIONS = options;
+        }
+    }
+
+    // options() returns the name, default value and range of every option
+
+    pub fn options() -> Vec<(&'static str, i32, i32, i32)> {
+        let options = unsafe { &*std::ptr::addr_of!(OPTIONS) };
+        options
+            .iter()
+            .map(|&(name, v)| {
+                if v > 0 {
+                    (name, v, 0, 2 * v)
+                } else {
+                    (name, v, 2 * v, 0)
+                }
+            })
+            .collect()
+    }
+
+    // is_option() tells whether the name is the name of an option
+    // registered by init()
+
+    pub fn is_option(name: &str) -> bool {
+        let options = unsafe { &*std::ptr::addr_of!(OPTIONS) };
+        options.iter().any(|&(n, _)| n == name)
+    }
+
+    // set() changes the parameter component with the given option name.
+    // Names that are not options are ignored.
+
+    pub fn set(name: &str, value: i32) {
+        if !is_option(name) {
+            return;
+        }
+
+        for (param_name, p) in params() {
+            if name.starts_with(param_name) {
+                p.visit(param_name, &mut |n, v| {
+                    if n == name {
+                        *v = value;
+                    }
+                });
+            }
+        }
+    }
+}
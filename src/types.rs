@@ -30,7 +30,7 @@ pub const MAX_MOVES: usize = 256;
 pub const MAX_PLY: i32 = 128;
 pub const MAX_MATE_PLY: i32 = 128;
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct Color(pub u32);
 
 pub const WHITE: Color = Color(0);
@@ -677,7 +677,7 @@ pub const PROMOTION: MoveType = MoveType(1 << 14);
 pub const ENPASSANT: MoveType = MoveType(2 << 14);
 pub const CASTLING: MoveType = MoveType(3 << 14);
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct Move(pub u32);
 
 impl Move {
@@ -1,125 +1,620 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 use benchmark::*;
+use book::BookBuilder;
+use engine::Engine;
+use evaluate;
+use gensfen;
+use gensfen::{GensfenConfig, SfenFormat};
+use listener::*;
 use misc;
+use pgn::PgnReader;
 use movegen::*;
+use notation;
+use notation::Notation;
 use position::*;
 use search;
-use threads;
-use threads::PosData;
+use stats::Sprt;
+use testsuite;
+#[cfg(feature = "tune")]
+use texel;
+use tournament;
+use tournament::{MatchConfig, PlayerConfig};
 use types::*;
-use ucioption;
+use xboard;
 
 use std;
 use std::env;
-use std::sync::{Arc, RwLock};
+use std::fs::File;
+use std::io::stdout;
+use std::io::BufReader;
+use std::io::Write;
+use std::str::{FromStr, SplitWhitespace};
 use std::time::Instant;
 
 // FEN string of the initial position, normal chess
-const START_FEN: &'static str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+pub const START_FEN: &'static str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
 
-// position() is called when engine receives the "position" UCI command.
-// The function sets up the position described in the given FEN string ("fen")
-// or the starting position ("startpos") and then makes the moves given in the
-// following move list ("moves").
+// UciCommand is a parsed UCI command or one of the additional debug
+// commands. Moves are kept in coordinate notation, as they can only be
+// checked against a position.
 
-fn position(pos: &mut Position, pos_data: &mut PosData, args: &str) {
-    let fen: &str;
+#[derive(Debug)]
+pub enum UciCommand {
+    Uci,
+    IsReady,
+    SetOption {
+        name: String,
+        value: String,
+    },
+    UciNewGame,
+    Position {
+        fen: String,
+        moves: Vec<String>,
+    },
+    PgnPosition(String),
+    Go {
+        limits: search::LimitsType,
+        searchmoves: Vec<String>,
+        ponder: bool,
+    },
+    Stop,
+    PonderHit,
+    Quit,
+    Bench(String),
+    Display,
+    Eval,
+    MakeBook {
+        pgn: String,
+        book: String,
+        min_games: u32,
+        max_ply: usize,
+    },
+    Tune {
+        file: String,
+        iterations: usize,
+        rate: f64,
+    },
+    Match(MatchConfig),
+    Gensfen(GensfenConfig),
+    TestSuite {
+        files: Vec<String>,
+        limits: search::LimitsType,
+        csv: Option<String>,
+    },
+}
+
+// parse() converts a line of input into a UciCommand. Empty lines give
+// None. Malformed commands give an error message, which is meant to be
+// sent to the GUI as an "info string".
+
+pub fn parse(line: &str) -> Result<Option<UciCommand>, String> {
+    let mut tokens = line.split_whitespace();
 
-    let moves = match args.find("moves") {
-        Some(idx) => idx,
-        None => args.len(),
+    let token = match tokens.next() {
+        Some(token) => token,
+        None => return Ok(None),
     };
 
-    if &args[0..8] == "startpos" {
-        fen = START_FEN;
-    } else if &args[0..3] == "fen" {
-        fen = (&args[3..moves]).trim();
-    } else {
-        return;
+    let cmd = match token {
+        "uci" => UciCommand::Uci,
+        "isready" => UciCommand::IsReady,
+        "setoption" => parse_setoption(tokens)?,
+        "ucinewgame" => UciCommand::UciNewGame,
+        "position" => parse_position(tokens)?,
+        "go" => parse_go(tokens)?,
+        "stop" => UciCommand::Stop,
+        "ponderhit" => UciCommand::PonderHit,
+        "quit" => UciCommand::Quit,
+
+        // Additional custom non-UCI commands
+        "bench" => UciCommand::Bench(tokens.collect::<Vec<_>>().join(" ")),
+        "d" => UciCommand::Display,
+        "eval" => UciCommand::Eval,
+        "makebook" => parse_makebook(tokens)?,
+        "tune" => parse_tune(tokens)?,
+        "match" => parse_match(tokens)?,
+        "gensfen" => parse_gensfen(tokens)?,
+        "testsuite" => parse_testsuite(tokens)?,
+        _ => return Err(format!("Unknown command: {}", line.trim())),
+    };
+
+    Ok(Some(cmd))
+}
+
+// parse_setoption() parses "setoption name <id> [value <x>]". Both the
+// name and the value may consist of several words.
+
+fn parse_setoption(tokens: SplitWhitespace) -> Result<UciCommand, String> {
+    let args: Vec<&str> = tokens.collect();
+
+    if args.first() != Some(&"name") {
+        return Err(String::from("Expected: setoption name <id> [value <x>]"));
     }
 
-    pos.init_states();
-    pos.set(fen, ucioption::get_bool("UCI_Chess960"));
-    pos_data.fen = String::from(fen);
-    pos_data.moves = Vec::new();
+    let (name, value) = match args.iter().position(|&t| t == "value") {
+        Some(idx) => (args[1..idx].join(" "), args[idx + 1..].join(" ")),
+        None => (args[1..].join(" "), String::new()),
+    };
 
-    if moves == args.len() {
-        return;
+    if name.is_empty() {
+        return Err(String::from("Missing option name"));
     }
 
-    // Parse move list
-    let moves = &args[moves + 5..].trim();
-    let iter = moves.split_whitespace();
-    for token in iter {
-        let m = to_move(pos, token);
-        if m == Move::NONE {
-            break;
+    Ok(UciCommand::SetOption { name, value })
+}
+
+// parse_position() parses "position [fen <fenstring> | startpos] moves
+// <move1> .... <movei>". The FEN string itself is validated when the
+// position is set up. As an extension, "position pgn <file>" sets up the
+// final position of the first game in a PGN file.
+
+fn parse_position(tokens: SplitWhitespace) -> Result<UciCommand, String> {
+    let args: Vec<&str> = tokens.collect();
+
+    let (setup, moves) = match args.iter().position(|&t| t == "moves") {
+        Some(idx) => (&args[..idx], &args[idx + 1..]),
+        None => (&args[..], &args[args.len()..]),
+    };
+
+    if let Some((&"pgn", path)) = setup.split_first() {
+        if path.is_empty() || !moves.is_empty() {
+            return Err(String::from("Expected: position pgn <file>"));
         }
-        let gives_check = pos.gives_check(m);
-        pos.do_move(m, gives_check);
-        pos_data.moves.push(m);
+        return Ok(UciCommand::PgnPosition(path.join(" ")));
     }
+
+    let fen = match setup.split_first() {
+        Some((&"startpos", [])) => String::from(START_FEN),
+        Some((&"fen", rest)) if !rest.is_empty() => rest.join(" "),
+        Some((&"fen", _)) => return Err(String::from("Missing FEN string")),
+        _ => return Err(String::from("Expected: position [fen <fenstring> | startpos] moves ...")),
+    };
+
+    Ok(UciCommand::Position {
+        fen,
+        moves: moves.iter().map(|&m| String::from(m)).collect(),
+    })
 }
 
-// setoption() is called when engine received the "setoption" UCI command.
-// The function updates the UCI option ("name") to the given value ("value").
+// parse_go() parses the search parameters of the "go" command. Unknown
+// tokens are ignored, as required by the UCI protocol.
 
-fn setoption(args: &str) {
-    let idx = args.find("name").unwrap();
-    let args = &args[idx + 4..];
-    if let Some(idx) = args.find("value") {
-        let name = &args[..idx].trim();
-        let value = &args[idx + 5..].trim();
-        ucioption::set(name, value);
-    } else {
-        let name = args.trim();
-        ucioption::set(name, &"");
+fn parse_go(mut tokens: SplitWhitespace) -> Result<UciCommand, String> {
+    let mut limits = search::LimitsType::new();
+    let mut searchmoves = Vec::new();
+    let mut ponder = false;
+
+    while let Some(token) = tokens.next() {
+        match token {
+            "searchmoves" => searchmoves.extend(tokens.by_ref().map(String::from)),
+            "wtime" => limits.time[WHITE.0 as usize] = number(token, tokens.next())?,
+            "btime" => limits.time[BLACK.0 as usize] = number(token, tokens.next())?,
+            "winc" => limits.inc[WHITE.0 as usize] = number(token, tokens.next())?,
+            "binc" => limits.inc[BLACK.0 as usize] = number(token, tokens.next())?,
+            "movestogo" => limits.movestogo = number(token, tokens.next())?,
+            "depth" => limits.depth = number(token, tokens.next())?,
+            "nodes" => limits.nodes = number(token, tokens.next())?,
+            "movetime" => limits.movetime = number(token, tokens.next())?,
+            "mate" => limits.mate = number(token, tokens.next())?,
+            "perft" => limits.perft = number(token, tokens.next())?,
+            "divide" => limits.divide = true,
+            "hash" => limits.perft_hash = number(token, tokens.next())?,
+            "infinite" => limits.infinite = true,
+            "ponder" => ponder = true,
+            _ => {}
+        }
     }
+
+    Ok(UciCommand::Go {
+        limits,
+        searchmoves,
+        ponder,
+    })
 }
 
-// go() is called when engine receives the "go" UCI command. The function
-// sets the thinking time and other parameters from the input string, then
-// starts the search.
+// parse_makebook() parses "makebook <pgn file> <book file> [mingames <n>]
+// [maxply <n>]". By default moves played in fewer than 3 games are left
+// out and the whole game is used.
 
-fn go(pos: &mut Position, pos_data: &Arc<RwLock<PosData>>, args: &str) {
-    let mut limits = search::LimitsType::new();
-    let mut searchmoves: Vec<Move> = Vec::new();
-    let mut ponder_mode = false;
+fn parse_makebook(mut tokens: SplitWhitespace) -> Result<UciCommand, String> {
+    let usage = "Expected: makebook <pgn file> <book file> [mingames <n>] [maxply <n>]";
+
+    let (pgn, book) = match (tokens.next(), tokens.next()) {
+        (Some(pgn), Some(book)) => (String::from(pgn), String::from(book)),
+        _ => return Err(String::from(usage)),
+    };
+
+    let mut min_games = 3;
+    let mut max_ply = 1024;
 
-    let mut iter = args.split_whitespace();
-    while let Some(token) = iter.next() {
+    while let Some(token) = tokens.next() {
         match token {
-            "searchmoves" => {
-                while let Some(token) = iter.next() {
-                    searchmoves.push(to_move(pos, token));
+            "mingames" => min_games = number(token, tokens.next())?,
+            "maxply" => max_ply = number(token, tokens.next())?,
+            _ => return Err(String::from(usage)),
+        }
+    }
+
+    Ok(UciCommand::MakeBook {
+        pgn,
+        book,
+        min_games,
+        max_ply,
+    })
+}
+
+// parse_tune() parses "tune <file> [iterations <n>] [rate <x>]"
+
+fn parse_tune(mut tokens: SplitWhitespace) -> Result<UciCommand, String> {
+    let usage = "Expected: tune <file> [iterations <n>] [rate <x>]";
+
+    let file = match tokens.next() {
+        Some(file) => String::from(file),
+        None => return Err(String::from(usage)),
+    };
+
+    let mut iterations = 100;
+    let mut rate = 1.0;
+
+    while let Some(token) = tokens.next() {
+        match token {
+            "iterations" => iterations = number(token, tokens.next())?,
+            "rate" => rate = number(token, tokens.next())?,
+            _ => return Err(String::from(usage)),
+        }
+    }
+
+    Ok(UciCommand::Tune {
+        file,
+        iterations,
+        rate,
+    })
+}
+
+// parse_match() parses "match [engine <command|self> [name <name>]
+// [option <name>=<value>]...]... [games <n>] [tc <base>[+<inc>]]
+// [openings <file>] [pgn <file>] [concurrency <n>] [draw <movenumber>
+// <movecount> <score>] [resign <movecount> <score>] [sprt <elo0> <elo1>
+// [alpha <x>] [beta <x>]]". Times are in seconds and scores in
+// centipawns. Players not given are this engine. Without a number of
+// games, an SPRT runs until one of the hypotheses is accepted.
+
+fn parse_match(mut tokens: SplitWhitespace) -> Result<UciCommand, String> {
+    let usage = "Expected: match [engine <command|self> [name <name>] \
+                 [option <name>=<value>]...]... [games <n>] [tc <base>[+<inc>]] \
+                 [openings <file>] [pgn <file>] [concurrency <n>] \
+                 [draw <movenumber> <movecount> <score>] [resign <movecount> <score>] \
+                 [sprt <elo0> <elo1> [alpha <x>] [beta <x>]]";
+
+    let mut config = MatchConfig::default();
+    let mut players: Vec<PlayerConfig> = Vec::new();
+    let mut games = None;
+
+    while let Some(token) = tokens.next() {
+        match token {
+            "engine" => match tokens.next() {
+                Some("self") => players.push(PlayerConfig::new(None)),
+                Some(command) => players.push(PlayerConfig::new(Some(String::from(command)))),
+                None => return Err(String::from(usage)),
+            },
+            "name" | "option" if players.is_empty() => return Err(String::from(usage)),
+            "name" => {
+                let name = tokens.next().ok_or_else(|| String::from(usage))?;
+                players.last_mut().unwrap().name = Some(String::from(name));
+            }
+            "option" => {
+                // The name of the option may consist of several words
+                let mut words = Vec::new();
+                let value = loop {
+                    let word = tokens.next().ok_or_else(|| String::from(usage))?;
+                    match word.find('=') {
+                        Some(i) => {
+                            words.push(&word[..i]);
+                            break String::from(&word[i + 1..]);
+                        }
+                        None => words.push(word),
+                    }
+                };
+                let option = (words.join(" "), value);
+                players.last_mut().unwrap().options.push(option);
+            }
+            "games" => games = Some(number(token, tokens.next())?),
+            "tc" => {
+                let tc = tokens.next().unwrap_or("");
+                let (base, inc) = match tc.find('+') {
+                    Some(i) => (&tc[..i], Some(&tc[i + 1..])),
+                    None => (tc, None),
+                };
+                config.time = (number::<f64>(token, Some(base))? * 1000.0) as i64;
+                config.inc = match inc {
+                    Some(inc) => (number::<f64>(token, Some(inc))? * 1000.0) as i64,
+                    None => 0,
+                };
+            }
+            "openings" => config.openings = tokens.next().map(String::from),
+            "pgn" => config.pgn = tokens.next().map(String::from),
+            "concurrency" => config.concurrency = number(token, tokens.next())?,
+            "draw" => {
+                config.draw_move_number = number(token, tokens.next())?;
+                config.draw_move_count = number(token, tokens.next())?;
+                config.draw_score = number(token, tokens.next())?;
+            }
+            "resign" => {
+                config.resign_move_count = number(token, tokens.next())?;
+                config.resign_score = number(token, tokens.next())?;
+            }
+            "sprt" => {
+                let elo0 = number(token, tokens.next())?;
+                let elo1 = number(token, tokens.next())?;
+                config.sprt = Some(Sprt::new(elo0, elo1));
+            }
+            "alpha" | "beta" if config.sprt.is_none() => return Err(String::from(usage)),
+            "alpha" => config.sprt.as_mut().unwrap().alpha = number(token, tokens.next())?,
+            "beta" => config.sprt.as_mut().unwrap().beta = number(token, tokens.next())?,
+            _ => return Err(String::from(usage)),
+        }
+    }
+
+    if players.len() > 2 {
+        return Err(String::from(usage));
+    }
+    for (i, p) in players.into_iter().enumerate() {
+        config.players[i] = p;
+    }
+    config.games = match (games, config.sprt) {
+        (Some(games), _) => games,
+        (None, Some(_)) => usize::MAX,
+        (None, None) => config.games,
+    };
+
+    Ok(UciCommand::Match(config))
+}
+
+// parse_gensfen() parses "gensfen <file> [positions <n>] [depth <n>]
+// [nodes <n>] [threads <n>] [hash <mb>] [randomplies <n>] [evallimit <cp>]
+// [maxply <n>] [draw <ply> <count> <score>] [format bin|text] [seed <n>]"
+
+fn parse_gensfen(mut tokens: SplitWhitespace) -> Result<UciCommand, String> {
+    let usage = "Expected: gensfen <file> [positions <n>] [depth <n>] [nodes <n>] \
+                 [threads <n>] [hash <mb>] [randomplies <n>] [evallimit <cp>] [maxply <n>] \
+                 [draw <ply> <count> <score>] [format bin|text] [seed <n>]";
+
+    let mut config = GensfenConfig {
+        output: match tokens.next() {
+            Some(file) => String::from(file),
+            None => return Err(String::from(usage)),
+        },
+        ..GensfenConfig::default()
+    };
+
+    while let Some(token) = tokens.next() {
+        match token {
+            "positions" => config.positions = number(token, tokens.next())?,
+            "depth" => config.depth = number(token, tokens.next())?,
+            "nodes" => config.nodes = number(token, tokens.next())?,
+            "threads" => config.threads = number(token, tokens.next())?,
+            "hash" => config.hash = number(token, tokens.next())?,
+            "randomplies" => config.random_plies = number(token, tokens.next())?,
+            "evallimit" => config.eval_limit = number(token, tokens.next())?,
+            "maxply" => config.max_ply = number(token, tokens.next())?,
+            "draw" => {
+                config.draw_ply = number(token, tokens.next())?;
+                config.draw_count = number(token, tokens.next())?;
+                config.draw_score = number(token, tokens.next())?;
+            }
+            "format" => {
+                config.format = match tokens.next() {
+                    Some("bin") => SfenFormat::Binary,
+                    Some("text") => SfenFormat::Text,
+                    _ => return Err(String::from(usage)),
                 }
             }
-            "wtime" => limits.time[WHITE.0 as usize] = iter.next().unwrap().parse().unwrap(),
-            "btime" => limits.time[BLACK.0 as usize] = iter.next().unwrap().parse().unwrap(),
-            "winc" => limits.inc[WHITE.0 as usize] = iter.next().unwrap().parse().unwrap(),
-            "binc" => limits.inc[BLACK.0 as usize] = iter.next().unwrap().parse().unwrap(),
-            "movestogo" => limits.movestogo = iter.next().unwrap().parse().unwrap(),
-            "depth" => limits.depth = iter.next().unwrap().parse().unwrap(),
-            "nodes" => limits.nodes = iter.next().unwrap().parse().unwrap(),
-            "movetime" => limits.movetime = iter.next().unwrap().parse().unwrap(),
-            "mate" => limits.mate = iter.next().unwrap().parse().unwrap(),
-            "perft" => limits.perft = iter.next().unwrap().parse().unwrap(),
-            "infinite" => limits.infinite = true,
-            "ponder" => ponder_mode = true,
-            _ => {}
+            "seed" => config.seed = Some(number(token, tokens.next())?),
+            _ => return Err(String::from(usage)),
+        }
+    }
+
+    Ok(UciCommand::Gensfen(config))
+}
+
+// parse_testsuite() parses "testsuite <file>... movetime|depth|nodes <n>
+// [csv <file>]"
+
+fn parse_testsuite(mut tokens: SplitWhitespace) -> Result<UciCommand, String> {
+    let usage = "Expected: testsuite <file>... movetime|depth|nodes <n> [csv <file>]";
+
+    let mut files = Vec::new();
+    let mut limits = search::LimitsType::new();
+    loop {
+        match tokens.next() {
+            Some(token @ "movetime") => limits.movetime = number(token, tokens.next())?,
+            Some(token @ "depth") => limits.depth = number(token, tokens.next())?,
+            Some(token @ "nodes") => limits.nodes = number(token, tokens.next())?,
+            Some(file) => {
+                files.push(String::from(file));
+                continue;
+            }
+            None => return Err(String::from(usage)),
+        }
+        break;
+    }
+
+    let csv = match (tokens.next(), tokens.next()) {
+        (None, _) => None,
+        (Some("csv"), Some(file)) => Some(String::from(file)),
+        _ => return Err(String::from(usage)),
+    };
+
+    if files.is_empty() {
+        return Err(String::from(usage));
+    }
+
+    Ok(UciCommand::TestSuite { files, limits, csv })
+}
+
+// number() parses the value that follows a keyword of a command, such as
+// "depth 12". The keyword is only used in the error message.
+
+fn number<T: FromStr>(name: &str, token: Option<&str>) -> Result<T, String> {
+    match token {
+        Some(t) => t
+            .parse()
+            .map_err(|_| format!("Invalid value for {}: {}", name, t)),
+        None => Err(format!("Missing value for {}", name)),
+    }
+}
+
+// execute() runs a parsed command on the given engine. Errors are reported
+// to the GUI as "info string" and the engine keeps going.
+
+pub fn execute(engine: &mut Engine, cmd: UciCommand) {
+    match cmd {
+        UciCommand::Uci => {
+            println!("id name {}", misc::engine_info(true));
+            engine.print_options();
+            println!("uciok");
+        }
+        UciCommand::IsReady => println!("readyok"),
+        UciCommand::SetOption { name, value } => {
+            if let Err(e) = engine.set_option(&name, &value) {
+                println!("info string {}", e);
+            }
+        }
+        UciCommand::UciNewGame => engine.new_game(),
+        UciCommand::Position { fen, moves } => {
+            let moves: Vec<&str> = moves.iter().map(|m| m.as_str()).collect();
+            if let Err(e) = engine.set_position(&fen, &moves) {
+                println!("info string Invalid FEN: {}", e);
+            }
+        }
+        UciCommand::PgnPosition(path) => pgn_position(engine, &path),
+        UciCommand::Go {
+            limits,
+            searchmoves,
+            ponder,
+        } => go(engine, &limits, &searchmoves, ponder),
+        UciCommand::Stop | UciCommand::Quit => engine.stop(),
+        // The GUI sends 'ponderhit' to tell us the user has played the
+        // expected move. So 'ponderhit' will be sent if we were told to
+        // ponder on the same move the user has played.
+        UciCommand::PonderHit => engine.ponderhit(),
+        UciCommand::Bench(args) => bench(engine, &args),
+        UciCommand::Display => engine.print_position(),
+        UciCommand::Eval => print!("{}", evaluate::trace(engine.position(), engine.network())),
+        UciCommand::MakeBook {
+            pgn,
+            book,
+            min_games,
+            max_ply,
+        } => make_book(&pgn, &book, min_games, max_ply),
+        UciCommand::Tune {
+            file,
+            iterations,
+            rate,
+        } => tune(&file, iterations, rate),
+        UciCommand::Match(config) => {
+            tournament::run(&config);
+        }
+        UciCommand::Gensfen(config) => {
+            gensfen::run(&config);
+        }
+        UciCommand::TestSuite { files, limits, csv } => {
+            testsuite::run(engine, &files, &limits, csv.as_deref());
+        }
+    }
+}
+
+// tune() runs the Texel tuner, which needs the weights to be changeable at
+// runtime
+
+#[cfg(feature = "tune")]
+fn tune(file: &str, iterations: usize, rate: f64) {
+    texel::run(file, iterations, rate);
+}
+
+#[cfg(not(feature = "tune"))]
+fn tune(_: &str, _: usize, _: f64) {
+    println!("info string The tuner requires a build with --features tune");
+}
+
+// pgn_position() sets up the final position of the first game in the
+// given PGN file
+
+fn pgn_position(engine: &mut Engine, path: &str) {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) => {
+            println!("info string Unable to open {}: {}", path, e);
+            return;
+        }
+    };
+
+    match PgnReader::new(BufReader::new(file)).next() {
+        Some(Ok(game)) => {
+            if let Err(e) = engine.set_game(&game) {
+                println!("info string Invalid FEN: {}", e);
+            }
+        }
+        Some(Err(e)) => println!("info string Invalid PGN: {}", e),
+        None => println!("info string No game found in {}", path),
+    }
+}
+
+// make_book() is called when engine receives the "makebook" command. It
+// reads all games of a PGN file and writes the moves played in them as a
+// Polyglot book.
+
+fn make_book(pgn: &str, book: &str, min_games: u32, max_ply: usize) {
+    let file = match File::open(pgn) {
+        Ok(file) => file,
+        Err(e) => {
+            println!("info string Unable to open {}: {}", pgn, e);
+            return;
+        }
+    };
+
+    let mut builder = BookBuilder::new(max_ply);
+    for game in PgnReader::new(BufReader::new(file)) {
+        match game {
+            Ok(game) => builder.add_game(&game),
+            Err(_) => builder.skipped += 1,
         }
     }
 
-    threads::start_thinking(pos, pos_data, &limits, searchmoves, ponder_mode);
+    match builder.write(book, min_games) {
+        Ok(n) => println!(
+            "info string {} games read, {} skipped, {} entries written to {}",
+            builder.games, builder.skipped, n, book
+        ),
+        Err(e) => println!("info string Unable to write {}: {}", book, e),
+    }
+}
+
+// go() is called when engine receives the "go" UCI command. It converts
+// the search moves, if any, and then starts the search.
+
+fn go(engine: &mut Engine, limits: &search::LimitsType, searchmoves: &[String], ponder: bool) {
+    let mut moves: Vec<Move> = Vec::new();
+
+    for token in searchmoves.iter() {
+        let m = to_move(engine.position(), token);
+        if m == Move::NONE {
+            println!("info string Illegal search move: {}", token);
+        } else {
+            moves.push(m);
+        }
+    }
+
+    engine.go(limits, moves, ponder);
 }
 
 // bench() is called when engine receives the "bench" command. First a list
 // of UCI commands is setup according to bench parameters. Then the commands
 // are run one by one. At the end, a summary is printed.
 
-fn bench(pos: &mut Position, pos_data: &Arc<RwLock<PosData>>, args: &str) {
-    let list = setup_bench(pos, args);
+fn bench(engine: &mut Engine, args: &str) {
+    let list = setup_bench(engine.position(), args);
     let num = list.iter().filter(|&s| s.find("go ") != None).count();
 
     let now = Instant::now();
@@ -127,25 +622,17 @@ fn bench(pos: &mut Position, pos_data: &Arc<RwLock<PosData>>, args: &str) {
     let mut cnt = 1;
     let mut nodes = 0;
     for cmd in list.iter() {
-        let cmd_slice: &str = &cmd;
-        let (token, args) = if let Some(idx) = cmd_slice.find(char::is_whitespace) {
-            cmd_slice.split_at(idx)
-        } else {
-            (cmd_slice, "")
-        };
-        let args = args.trim();
-        if token == "go" {
-            eprintln!("\nPosition: {}/{}", cnt, num);
-            cnt += 1;
-            go(pos, pos_data, args);
-            threads::wait_for_main();
-            nodes += threads::nodes_searched();
-        } else if token == "setoption" {
-            setoption(args);
-        } else if token == "position" {
-            position(pos, &mut pos_data.write().unwrap(), args);
-        } else if token == "ucinewgame" {
-            search::clear();
+        match parse(cmd) {
+            Ok(Some(cmd @ UciCommand::Go { .. })) => {
+                eprintln!("\nPosition: {}/{}", cnt, num);
+                cnt += 1;
+                execute(engine, cmd);
+                engine.wait();
+                nodes += engine.nodes_searched();
+            }
+            Ok(Some(cmd)) => execute(engine, cmd),
+            Ok(None) => {}
+            Err(e) => println!("info string {}", e),
         }
     }
 
@@ -169,80 +656,154 @@ fn bench(pos: &mut Position, pos_data: &Arc<RwLock<PosData>>, args: &str) {
 // graceful exit if the GUI dies unexpectedly. When called with some comand
 // line arguments, e.g. to run 'bench', once the command is executed the
 // function returns immediately. In addition to the UCI ones, some additional
-// debug commands are supported.
-
-pub fn cmd_loop() {
-    let mut pos = Box::new(Position::new());
-
-    pos.init_states();
-    pos.set(START_FEN, false);
-
-    let pos_data = Arc::new(RwLock::new(PosData {
-        fen: String::from(START_FEN),
-        moves: Vec::new(),
-    }));
+// debug commands are supported. If the first command is 'xboard', the XBoard
+// protocol is used instead.
 
+pub fn cmd_loop(engine: &mut Engine) {
     let mut cmd = String::new();
     for arg in env::args().skip(1) {
         cmd.push_str(&arg);
         cmd.push(' ');
     }
 
+    let mut first = true;
     loop {
         if env::args().len() == 1 {
             cmd = String::new();
             // Block here waiting for input or EOF
-            if let Err(_) = std::io::stdin().read_line(&mut cmd) {
-                cmd = String::from("quit");
+            match std::io::stdin().read_line(&mut cmd) {
+                Ok(0) | Err(_) => cmd = String::from("quit"),
+                Ok(_) => {}
             }
         }
-        let cmd_slice = cmd.trim();
-        let (token, args) = if let Some(idx) = cmd_slice.find(char::is_whitespace) {
-            cmd_slice.split_at(idx)
-        } else {
-            (cmd_slice, "")
-        };
-        let args = args.trim();
 
-        // The GUI sends 'ponderhit' to tell us the user has played the
-        // expected move. So 'ponderhit' will be sent if we were told to
-        // ponder on the same move the user has played. We should continue
-        // searching but switch from pondering to normal search. In case
-        // threads::stop_on_ponderhit() is true, we are waiting for
-        // 'ponderhit' to stop the search, for instance if max search depth
-        // has been reached.
-        match token {
-            "quit" | "stop" => threads::set_stop(true),
-            "ponderhit" => {
-                if threads::stop_on_ponderhit() {
-                    threads::set_stop(true);
-                } else {
-                    threads::set_ponder(false); // Switch to normal search
-                }
+        // A GUI speaking the XBoard protocol introduces itself first
+        if first && cmd.trim() == "xboard" {
+            xboard::cmd_loop(engine);
+            break;
+        }
+        first = first && cmd.trim().is_empty();
+
+        let quit = match parse(&cmd) {
+            Ok(Some(cmd)) => {
+                let quit = matches!(cmd, UciCommand::Quit);
+                execute(engine, cmd);
+                quit
             }
-            "uci" => {
-                println!("id name {}", misc::engine_info(true));
-                ucioption::print();
-                println!("uciok");
+            Ok(None) => false,
+            Err(e) => {
+                println!("info string {}", e);
+                false
             }
-            "setoption" => setoption(args),
-            "go" => go(&mut pos, &pos_data, args),
-            "position" => position(&mut pos, &mut pos_data.write().unwrap(), args),
-            "ucinewgame" => search::clear(),
-            "isready" => println!("readyok"),
+        };
 
-            // Additional custom non-UCI commands
-            "bench" => bench(&mut pos, &pos_data, args),
-            "d" => pos.print(),
-            _ => println!("Unknown command: {} {}", cmd, args),
-        }
-        if env::args().len() > 1 || token == "quit" {
+        if env::args().len() > 1 || quit {
             // Command-line args are one-shot
             break;
         }
     }
 }
 
+/// UciListener reports the search to the GUI as UCI 'info' and 'bestmove'
+/// lines on stdout. It is the listener of a new engine.
+pub struct UciListener;
+
+impl SearchListener for UciListener {
+    fn on_iteration(&self, pos: &Position, depth: i32, lines: &[PvLine], stats: &SearchStats) {
+        if depth == 0 {
+            println!("info depth 0 score {}", value(lines[0].score));
+            stdout().flush().unwrap();
+            return;
+        }
+
+        let options = pos.shared().options.read().unwrap();
+        let notation = notation::notation(&options.get_string("PV Notation"));
+        drop(options);
+
+        for line in lines.iter() {
+            print!(
+                "info depth {} seldepth {} multipv {} score {} ",
+                line.depth,
+                line.sel_depth,
+                line.multipv,
+                value(line.score)
+            );
+
+            if let Some((w, d, l)) = line.wdl {
+                print!("wdl {} {} {} ", w, d, l);
+            }
+
+            match line.bound {
+                ScoreBound::Lower => print!("lowerbound "),
+                ScoreBound::Upper => print!("upperbound "),
+                ScoreBound::Exact => {}
+            }
+
+            print!("nodes {} nps {}", stats.nodes, stats.nps);
+
+            if stats.time > 1000 {
+                print!(" hashfull {}", stats.hashfull);
+            }
+
+            print!(" tbhits {} time {} pv", stats.tb_hits, stats.time);
+
+            if notation == Notation::Uci {
+                for &m in line.pv.iter() {
+                    print!(" {}", move_str(m, pos.is_chess960()));
+                }
+            } else {
+                // Other notations need a position to play the moves on
+                let mut root = Position::from_fen(&pos.fen(), pos.is_chess960()).unwrap();
+                print!(" {}", notation::line_str(&mut root, &line.pv, notation));
+            }
+            println!();
+        }
+        stdout().flush().unwrap();
+    }
+
+    fn on_currmove(&self, pos: &Position, depth: i32, m: Move, number: usize) {
+        println!(
+            "info depth {} currmove {} currmovenumber {}",
+            depth,
+            move_str(m, pos.is_chess960()),
+            number
+        );
+        stdout().flush().unwrap();
+    }
+
+    fn on_progress(&self, stats: &SearchStats) {
+        println!(
+            "info nodes {} nps {} hashfull {} tbhits {} time {}",
+            stats.nodes, stats.nps, stats.hashfull, stats.tb_hits, stats.time
+        );
+        stdout().flush().unwrap();
+    }
+
+    fn on_bestmove(&self, pos: &Position, best: Move, ponder: Option<Move>) {
+        print!("bestmove {}", move_str(best, pos.is_chess960()));
+
+        if let Some(m) = ponder {
+            print!(" ponder {}", move_str(m, pos.is_chess960()));
+        }
+
+        println!();
+        stdout().flush().unwrap();
+    }
+
+    fn on_perft(&self, pos: &Position, m: Move, nodes: u64) {
+        println!("{}: {}", move_str(m, pos.is_chess960()), nodes);
+        stdout().flush().unwrap();
+    }
+
+    fn on_perft_done(&self, nodes: u64) {
+        println!("\nNodes searched: {}", nodes);
+    }
+
+    fn on_message(&self, msg: &str) {
+        println!("info string {}", msg);
+    }
+}
+
 // value() converts a Value to a string suitable for use with the UCI
 // protocol specification:
 //
@@ -271,6 +832,42 @@ pub fn value(v: Value) -> String {
     return s;
 }
 
+// win_rate_model() returns the expected win rate in per mille of a position
+// with score v at the given game ply. The model is a logistic function of
+// the score in centipawns, as reported by value(), whose center a and width
+// b depend on the game ply:
+//
+//   win rate = 1000 / (1 + exp((a - x) / b))
+//
+// The coefficients of a and b, third order polynomials in ply / 64, are
+// fitted to this engine's self-play games by fit_wdl.py, which gives the
+// gensfen command for the data. Those games are adjudicated, so the model is
+// less reliable in long games, where positions are few.
+
+fn win_rate_model(v: Value, ply: i32) -> i32 {
+    // The model captures only up to 240 plies, so limit the input
+    let m = std::cmp::min(240, ply) as f64 / 64.0;
+
+    const AS: [f64; 4] = [-3.05168945, 48.62282490, -66.04258560, 96.23975516];
+    const BS: [f64; 4] = [-8.68880368, 88.07686646, -250.46622274, 291.71215673];
+    let a = ((AS[0] * m + AS[1]) * m + AS[2]) * m + AS[3];
+    let b = ((BS[0] * m + BS[1]) * m + BS[2]) * m + BS[3];
+
+    // Transform the score to centipawns with limited range
+    let x = (100.0 * v.0 as f64 / PawnValueEg.0 as f64).clamp(-1000.0, 1000.0);
+
+    (0.5 + 1000.0 / (1.0 + ((a - x) / b).exp())) as i32
+}
+
+// wdl() returns the win, draw and loss probabilities in per mille of a
+// position with score v at the given game ply, for the UCI 'wdl' output
+
+pub fn wdl(v: Value, ply: i32) -> (i32, i32, i32) {
+    let w = win_rate_model(v, ply);
+    let l = win_rate_model(-v, ply);
+    (w, 1000 - w - l, l)
+}
+
 // square() converts a Square to a string in algebraic notation (g1, a7, etc.)
 
 pub fn square(s: Square) -> String {
@@ -331,3 +928,186 @@ pub fn to_move(pos: &Position, s: &str) -> Move {
 
     Move::NONE
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_ok(line: &str) -> UciCommand {
+        match parse(line) {
+            Ok(Some(cmd)) => cmd,
+            other => panic!("{}: {:?}", line, other),
+        }
+    }
+
+    #[test]
+    fn empty_and_unknown() {
+        assert!(parse("").unwrap().is_none());
+        assert!(parse("   ").unwrap().is_none());
+        assert!(parse("foo bar").is_err());
+    }
+
+    #[test]
+    fn go() {
+        match parse_ok("go wtime 60000 btime 50000 winc 1000 binc 500 movestogo 20") {
+            UciCommand::Go {
+                limits,
+                searchmoves,
+                ponder,
+            } => {
+                assert_eq!(limits.time, [60000, 50000]);
+                assert_eq!(limits.inc, [1000, 500]);
+                assert_eq!(limits.movestogo, 20);
+                assert!(searchmoves.is_empty());
+                assert!(!ponder);
+            }
+            cmd => panic!("{:?}", cmd),
+        }
+
+        match parse_ok("go ponder depth 12 nodes 100000 foo infinite searchmoves e2e4 d2d4") {
+            UciCommand::Go {
+                limits,
+                searchmoves,
+                ponder,
+            } => {
+                assert_eq!(limits.depth, 12);
+                assert_eq!(limits.nodes, 100000);
+                assert!(limits.infinite);
+                assert_eq!(searchmoves, ["e2e4", "d2d4"]);
+                assert!(ponder);
+            }
+            cmd => panic!("{:?}", cmd),
+        }
+
+        match parse_ok("go perft 4 divide hash 16") {
+            UciCommand::Go { limits, .. } => {
+                assert_eq!(limits.perft, 4);
+                assert!(limits.divide);
+                assert_eq!(limits.perft_hash, 16);
+            }
+            cmd => panic!("{:?}", cmd),
+        }
+
+        assert!(parse("go depth x").is_err());
+        assert!(parse("go depth -1").is_err());
+        assert!(parse("go movetime").is_err());
+    }
+
+    #[test]
+    fn position() {
+        match parse_ok("position startpos") {
+            UciCommand::Position { fen, moves } => {
+                assert_eq!(fen, START_FEN);
+                assert!(moves.is_empty());
+            }
+            cmd => panic!("{:?}", cmd),
+        }
+
+        match parse_ok("position startpos moves e2e4 e7e5 g1f3") {
+            UciCommand::Position { fen, moves } => {
+                assert_eq!(fen, START_FEN);
+                assert_eq!(moves, ["e2e4", "e7e5", "g1f3"]);
+            }
+            cmd => panic!("{:?}", cmd),
+        }
+
+        let kiwipete = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        match parse_ok(&format!("position fen {} moves e1g1", kiwipete)) {
+            UciCommand::Position { fen, moves } => {
+                assert_eq!(fen, kiwipete);
+                assert_eq!(moves, ["e1g1"]);
+            }
+            cmd => panic!("{:?}", cmd),
+        }
+
+        match parse_ok("position pgn games/my game.pgn") {
+            UciCommand::PgnPosition(path) => assert_eq!(path, "games/my game.pgn"),
+            cmd => panic!("{:?}", cmd),
+        }
+
+        assert!(parse("position").is_err());
+        assert!(parse("position fen").is_err());
+        assert!(parse("position fen moves e2e4").is_err());
+        assert!(parse("position pgn").is_err());
+        assert!(parse("position pgn game.pgn moves e2e4").is_err());
+        assert!(parse("position middlegame").is_err());
+    }
+
+    // The FEN string is only checked when the position is set up
+
+    #[test]
+    fn position_bad_fen() {
+        let mut engine = Engine::new();
+        for fen in [
+            "8/8/8 w - - 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR x KQkq - 0 1",
+        ]
+        .iter()
+        {
+            match parse_ok(&format!("position fen {}", fen)) {
+                UciCommand::Position { fen, moves } => {
+                    assert!(engine.set_position(&fen, &[]).is_err(), "{}", fen);
+                    assert!(moves.is_empty());
+                }
+                cmd => panic!("{:?}", cmd),
+            }
+        }
+    }
+
+    #[test]
+    fn setoption() {
+        match parse_ok("setoption name Hash value 128") {
+            UciCommand::SetOption { name, value } => {
+                assert_eq!(name, "Hash");
+                assert_eq!(value, "128");
+            }
+            cmd => panic!("{:?}", cmd),
+        }
+
+        match parse_ok("setoption name Skill Level value 10") {
+            UciCommand::SetOption { name, value } => {
+                assert_eq!(name, "Skill Level");
+                assert_eq!(value, "10");
+            }
+            cmd => panic!("{:?}", cmd),
+        }
+
+        match parse_ok("setoption name SyzygyPath value /tb/wdl dir:/tb/dtz") {
+            UciCommand::SetOption { name, value } => {
+                assert_eq!(name, "SyzygyPath");
+                assert_eq!(value, "/tb/wdl dir:/tb/dtz");
+            }
+            cmd => panic!("{:?}", cmd),
+        }
+
+        // Buttons have no value
+        match parse_ok("setoption name Clear Hash") {
+            UciCommand::SetOption { name, value } => {
+                assert_eq!(name, "Clear Hash");
+                assert_eq!(value, "");
+            }
+            cmd => panic!("{:?}", cmd),
+        }
+
+        assert!(parse("setoption").is_err());
+        assert!(parse("setoption Hash value 128").is_err());
+        assert!(parse("setoption name").is_err());
+        assert!(parse("setoption name value 128").is_err());
+    }
+    #[test]
+    fn wdl_probabilities() {
+        for ply in (0..300).step_by(7) {
+            for cp in (-1200..=1200).step_by(25) {
+                let v = Value(cp * PawnValueEg.0 / 100);
+                let (w, d, l) = wdl(v, ply);
+                assert!(w >= 0 && d >= 0 && l >= 0, "{} {}: {} {} {}", cp, ply, w, d, l);
+                assert_eq!(w + d + l, 1000);
+                assert_eq!(wdl(-v, ply), (l, d, w));
+            }
+        }
+
+        // Winning chances grow with the score
+        assert!(wdl(Value(300), 40).0 > wdl(Value(100), 40).0);
+        assert!(wdl(Value(100), 40).0 > wdl(Value::ZERO, 40).0);
+    }
+}
@@ -1,12 +1,14 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+use nnue::Network;
 use tb;
-use threads;
-use tt;
+use threads::ThreadPool;
+#[cfg(feature = "tune")]
+use tune;
 
-use std;
+use std::sync::Arc;
 
-type OnChange = Option<fn(&OptVal)>;
+type OnChange = Option<fn(&mut ThreadPool, &OptVal)>;
 
 struct Opt {
     key: &'static str,
@@ -24,6 +26,7 @@ impl Opt {
     }
 }
 
+#[derive(Clone)]
 enum OptVal {
     StringOpt {
         def: &'static str,
@@ -75,152 +78,224 @@ impl OptVal {
     }
 }
 
-fn on_clear_hash(_: &OptVal) {
-    tt::clear();
+fn on_clear_hash(pool: &mut ThreadPool, _: &OptVal) {
+    pool.wait_for_all();
+    pool.shared.tt.clear();
 }
 
-fn on_hash_size(opt_val: &OptVal) {
+fn on_hash_size(pool: &mut ThreadPool, opt_val: &OptVal) {
     if let &OptVal::Spin { cur, .. } = opt_val {
-        tt::resize(cur as usize);
+        pool.wait_for_all();
+        pool.shared.tt.resize(cur as usize);
     }
 }
 
-fn on_threads(opt_val: &OptVal) {
+fn on_threads(pool: &mut ThreadPool, opt_val: &OptVal) {
     if let &OptVal::Spin { cur, .. } = opt_val {
-        threads::set(cur as usize);
+        pool.set(cur as usize);
     }
 }
 
-fn on_tb_path(opt_val: &OptVal) {
+// on_tb_path() loads the tablebases of the new path. A search already
+// running keeps probing the tablebases it started with.
+
+fn on_tb_path(pool: &mut ThreadPool, opt_val: &OptVal) {
     if let &OptVal::StringOpt { ref cur, .. } = opt_val {
-        tb::init(String::from(cur.as_str()));
+        *pool.shared.tablebases.write().unwrap() = tb::open(cur);
     }
 }
 
-static mut OPTIONS: *mut Vec<Opt> = 0 as *mut Vec<Opt>;
-
-pub fn init() {
-    let mut opts = Box::new(Vec::new());
-    opts.push(Opt::new("Contempt", OptVal::spin(12, -100, 100), None));
-    opts.push(Opt::new(
-        "Analysis Contempt",
-        OptVal::combo("Both var Off var White var Black var Both"),
-        None,
-    ));
-    opts.push(Opt::new(
-        "Threads",
-        OptVal::spin(1, 1, 512),
-        Some(on_threads),
-    ));
-    opts.push(Opt::new(
-        "Hash",
-        OptVal::spin(16, 1, 128 * 1024),
-        Some(on_hash_size),
-    ));
-    opts.push(Opt::new("Clear Hash", OptVal::Button, Some(on_clear_hash)));
-    opts.push(Opt::new("Ponder", OptVal::check(false), None));
-    opts.push(Opt::new("MultiPV", OptVal::spin(1, 1, 500), None));
-    opts.push(Opt::new("Move Overhead", OptVal::spin(30, 0, 5000), None));
-    opts.push(Opt::new(
-        "Minimum Thinking Time",
-        OptVal::spin(20, 0, 5000),
-        None,
-    ));
-    opts.push(Opt::new("Slow Mover", OptVal::spin(84, 10, 1000), None));
-    opts.push(Opt::new("UCI_AnalyseMode", OptVal::check(false), None));
-    opts.push(Opt::new("UCI_Chess960", OptVal::check(false), None));
-    opts.push(Opt::new(
-        "SyzygyPath",
-        OptVal::string("<empty>"),
-        Some(on_tb_path),
-    ));
-    opts.push(Opt::new("SyzygyProbeDepth", OptVal::spin(1, 1, 100), None));
-    opts.push(Opt::new("Syzygy50MoveRule", OptVal::check(true), None));
-    opts.push(Opt::new("SyzygyProbeLimit", OptVal::spin(6, 0, 6), None));
-    opts.push(Opt::new("SyzygyUseDTM", OptVal::check(true), None));
-    unsafe {
-        OPTIONS = Box::into_raw(opts);
+// on_eval_file() loads the network named by EvalFile if "Use NNUE" is set,
+// and unloads it otherwise. If the network cannot be loaded, the classical
+// evaluation is used.
+
+fn on_eval_file(pool: &mut ThreadPool, _: &OptVal) {
+    pool.wait_for_all();
+
+    let (use_nnue, file_name) = {
+        let options = pool.shared.options.read().unwrap();
+        (options.get_bool("Use NNUE"), options.get_string("EvalFile"))
+    };
+
+    let mut network = pool.shared.network.write().unwrap();
+    if !use_nnue {
+        *network = None;
+        return;
     }
+    if network.as_ref().map_or(false, |net| net.file_name == file_name) {
+        return;
+    }
+
+    *network = match Network::load(&file_name) {
+        Ok(net) => {
+            println!(
+                "info string NNUE evaluation using {} enabled ({})",
+                file_name,
+                net.simd.name()
+            );
+            Some(Arc::new(net))
+        }
+        Err(e) => {
+            println!("info string Could not load NNUE network {}: {}", file_name, e);
+            None
+        }
+    };
 }
 
-pub fn free() {
-    let _opts = unsafe { Box::from_raw(OPTIONS) };
+/// OptionsMap holds the UCI options of one engine instance. Each engine
+/// owns its own map, so changing an option only affects that engine.
+pub struct OptionsMap {
+    opts: Vec<Opt>,
 }
 
-pub fn print() {
-    let opts = unsafe { Box::from_raw(OPTIONS) };
-    for opt in opts.iter() {
-        print!(
-            "\noption name {} type {}",
-            opt.key,
-            match opt.val {
-                OptVal::StringOpt { def, .. } => format!("string default {}", def),
-                OptVal::Spin { def, min, max, .. } =>
-                    format!("spin default {} min {} max {}", def, min, max),
-                OptVal::Check { def, .. } =>
-                    format!("check default {}", if def { true } else { false }),
-                OptVal::Button => format!("button"),
-                OptVal::Combo { def, .. } => format!("combo default {}", def),
-            }
-        );
+impl OptionsMap {
+    pub fn new() -> OptionsMap {
+        let mut opts = Vec::new();
+        opts.push(Opt::new("Contempt", OptVal::spin(12, -100, 100), None));
+        opts.push(Opt::new(
+            "Analysis Contempt",
+            OptVal::combo("Both var Off var White var Black var Both"),
+            None,
+        ));
+        opts.push(Opt::new(
+            "Threads",
+            OptVal::spin(1, 1, 512),
+            Some(on_threads),
+        ));
+        opts.push(Opt::new(
+            "Hash",
+            OptVal::spin(16, 1, 128 * 1024),
+            Some(on_hash_size),
+        ));
+        opts.push(Opt::new("Clear Hash", OptVal::Button, Some(on_clear_hash)));
+        opts.push(Opt::new("Ponder", OptVal::check(false), None));
+        opts.push(Opt::new("MultiPV", OptVal::spin(1, 1, 500), None));
+        opts.push(Opt::new("Skill Level", OptVal::spin(20, 0, 20), None));
+        opts.push(Opt::new("Move Overhead", OptVal::spin(30, 0, 5000), None));
+        opts.push(Opt::new(
+            "Minimum Thinking Time",
+            OptVal::spin(20, 0, 5000),
+            None,
+        ));
+        opts.push(Opt::new("Slow Mover", OptVal::spin(84, 10, 1000), None));
+        opts.push(Opt::new("UCI_AnalyseMode", OptVal::check(false), None));
+        opts.push(Opt::new("UCI_Chess960", OptVal::check(false), None));
+        opts.push(Opt::new("UCI_LimitStrength", OptVal::check(false), None));
+        opts.push(Opt::new("UCI_Elo", OptVal::spin(1350, 1350, 2850), None));
+        opts.push(Opt::new("UCI_ShowWDL", OptVal::check(false), None));
+        opts.push(Opt::new(
+            "PV Notation",
+            OptVal::combo("UCI var UCI var SAN var LAN"),
+            None,
+        ));
+        opts.push(Opt::new(
+            "SyzygyPath",
+            OptVal::string("<empty>"),
+            Some(on_tb_path),
+        ));
+        opts.push(Opt::new("SyzygyProbeDepth", OptVal::spin(1, 1, 100), None));
+        opts.push(Opt::new("Syzygy50MoveRule", OptVal::check(true), None));
+        opts.push(Opt::new("SyzygyProbeLimit", OptVal::spin(6, 0, 6), None));
+        opts.push(Opt::new("SyzygyUseDTM", OptVal::check(true), None));
+        opts.push(Opt::new("OwnBook", OptVal::check(false), None));
+        opts.push(Opt::new("BookFile", OptVal::string("book.bin"), None));
+        opts.push(Opt::new("BookDepth", OptVal::spin(255, 1, 1000), None));
+        opts.push(Opt::new("BestBookMove", OptVal::check(false), None));
+        opts.push(Opt::new(
+            "Use NNUE",
+            OptVal::check(false),
+            Some(on_eval_file),
+        ));
+        opts.push(Opt::new(
+            "EvalFile",
+            OptVal::string("nn-62ef826d1a6d.nnue"),
+            Some(on_eval_file),
+        ));
+        #[cfg(feature = "tune")]
+        for (name, def, min, max) in tune::options() {
+            opts.push(Opt::new(name, OptVal::spin(def, min, max), None));
+        }
+        OptionsMap { opts }
     }
-    print!("\n");
-    std::mem::forget(opts);
-}
 
-pub fn set(key: &str, val: &str) {
-    let mut opts = unsafe { Box::from_raw(OPTIONS) };
-    if let Some(opt) = opts.iter_mut().find(|ref o| o.key == key) {
+    pub fn print(&self) {
+        for opt in self.opts.iter() {
+            print!(
+                "\noption name {} type {}",
+                opt.key,
+                match opt.val {
+                    OptVal::StringOpt { def, .. } => format!("string default {}", def),
+                    OptVal::Spin { def, min, max, .. } =>
+                        format!("spin default {} min {} max {}", def, min, max),
+                    OptVal::Check { def, .. } =>
+                        format!("check default {}", if def { true } else { false }),
+                    OptVal::Button => format!("button"),
+                    OptVal::Combo { def, .. } => format!("combo default {}", def),
+                }
+            );
+        }
+        print!("\n");
+    }
+
+    // update() checks and stores the new value of an option and returns its
+    // on_change handler together with a copy of the new value. The handler
+    // is not called here, as it may need exclusive access to the thread
+    // pool.
+
+    fn update(&mut self, key: &str, val: &str) -> Result<(OnChange, OptVal), String> {
+        let opt = match self.opts.iter_mut().find(|ref o| o.key == key) {
+            Some(opt) => opt,
+            None => return Err(format!("No such option: {}", key)),
+        };
+        let invalid = || format!("Invalid value for option {}: {}", key, val);
         match opt.val {
             OptVal::StringOpt { ref mut cur, .. } => *cur = String::from(val),
-            OptVal::Spin { ref mut cur, .. } => *cur = val.parse().unwrap(),
-            OptVal::Check { ref mut cur, .. } => *cur = val == "true",
+            OptVal::Spin {
+                ref mut cur,
+                min,
+                max,
+                ..
+            } => match val.parse() {
+                Ok(v) if v >= min && v <= max => *cur = v,
+                _ => return Err(invalid()),
+            },
+            OptVal::Check { ref mut cur, .. } => match val {
+                "true" => *cur = true,
+                "false" => *cur = false,
+                _ => return Err(invalid()),
+            },
             OptVal::Button => {}
-            OptVal::Combo { ref mut cur, .. } => *cur = String::from(val).to_lowercase(),
-        }
-        if let Some(on_change) = opt.on_change {
-            on_change(&opt.val);
+            OptVal::Combo { def, ref mut cur } => {
+                let val = val.to_lowercase();
+                if !def.to_lowercase().split(" var ").skip(1).any(|v| v == val) {
+                    return Err(invalid());
+                }
+                *cur = val;
+            }
         }
-    } else {
-        println!("No such option: {}", key);
+        Ok((opt.on_change, opt.val.clone()))
     }
-    unsafe {
-        OPTIONS = Box::into_raw(opts);
-    }
-}
 
-pub fn get_i32(key: &str) -> i32 {
-    let opts = unsafe { Box::from_raw(OPTIONS) };
-    let val = {
-        let opt = opts.iter().find(|ref o| o.key == key).unwrap();
+    pub fn get_i32(&self, key: &str) -> i32 {
+        let opt = self.opts.iter().find(|ref o| o.key == key).unwrap();
         if let OptVal::Spin { cur, .. } = opt.val {
             cur
         } else {
             0
         }
-    };
-    std::mem::forget(opts);
-    val
-}
+    }
 
-pub fn get_bool(key: &str) -> bool {
-    let opts = unsafe { Box::from_raw(OPTIONS) };
-    let val = {
-        let opt = opts.iter().find(|ref o| o.key == key).unwrap();
+    pub fn get_bool(&self, key: &str) -> bool {
+        let opt = self.opts.iter().find(|ref o| o.key == key).unwrap();
         if let OptVal::Check { cur, .. } = opt.val {
             cur
         } else {
             false
         }
-    };
-    std::mem::forget(opts);
-    val
-}
+    }
 
-pub fn get_string(key: &str) -> String {
-    let opts = unsafe { Box::from_raw(OPTIONS) };
-    let val = {
-        let opt = opts.iter().find(|ref o| o.key == key).unwrap();
+    pub fn get_string(&self, key: &str) -> String {
+        let opt = self.opts.iter().find(|ref o| o.key == key).unwrap();
         if let OptVal::StringOpt { ref cur, .. } = opt.val {
             String::from(cur.as_str())
         } else if let OptVal::Combo { ref cur, .. } = opt.val {
@@ -228,7 +303,29 @@ pub fn get_string(key: &str) -> String {
         } else {
             String::new()
         }
-    };
-    std::mem::forget(opts);
-    val
+    }
+}
+
+// set() updates the option of the given thread pool and then calls its
+// on_change handler, if any. Unknown options and invalid values are
+// rejected and leave the option unchanged.
+
+pub fn set(pool: &mut ThreadPool, key: &str, val: &str) -> Result<(), String> {
+    let (on_change, opt_val) = pool.shared.options.write().unwrap().update(key, val)?;
+    if let Some(on_change) = on_change {
+        on_change(pool, &opt_val);
+    }
+
+    // The values of tunable parameters are kept in the parameters
+    // themselves, see tune.rs
+    #[cfg(feature = "tune")]
+    {
+        if let OptVal::Spin { cur, .. } = opt_val {
+            if tune::is_option(key) {
+                tune::set(key, cur);
+            }
+        }
+    }
+
+    Ok(())
 }
@@ -0,0 +1,603 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+// XBoard front end. When the first command received is 'xboard', the
+// engine speaks version 2 of the Chess Engine Communication Protocol (CECP)
+// of WinBoard and XBoard instead of UCI.
+//
+// The game is kept as its start position and the list of moves played,
+// which is set up on the engine after every change. This makes 'undo' and
+// 'remove' trivial. A search runs in the background, as with UCI, while
+// stdin is read by a separate thread, so that commands like 'ping' and '?'
+// are answered during the search. The move it plays is added to the game as
+// soon as the search ends, and the result is printed if the game is over.
+
+use engine::Engine;
+use listener::*;
+use misc;
+use position::Position;
+use search::LimitsType;
+use threads::SearchHandle;
+use tournament;
+use types::*;
+use uci;
+
+use std;
+use std::io::stdout;
+use std::io::{BufRead, Write};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+// How often the end of a search for a move is checked while waiting for a
+// command
+const POLL: Duration = Duration::from_millis(10);
+
+// XBoardListener prints the thinking output, if enabled with 'post', and
+// the moves of the engine
+
+struct XBoardListener {
+    post: AtomicBool,
+    analyzing: AtomicBool,
+}
+
+impl SearchListener for XBoardListener {
+    fn on_iteration(&self, _pos: &Position, depth: i32, lines: &[PvLine], stats: &SearchStats) {
+        if depth == 0
+            || !(self.post.load(Ordering::Relaxed) || self.analyzing.load(Ordering::Relaxed))
+        {
+            return;
+        }
+
+        // Only the best line is shown, with the time in centiseconds
+        let line = &lines[0];
+        let pv: Vec<String> = line.pv.iter().map(|&m| uci::move_str(m, false)).collect();
+        println!(
+            "{} {} {} {} {}",
+            line.depth,
+            score(line.score),
+            stats.time / 10,
+            stats.nodes,
+            pv.join(" ")
+        );
+        stdout().flush().unwrap();
+    }
+
+    fn on_bestmove(&self, _pos: &Position, best: Move, _ponder: Option<Move>) {
+        if self.analyzing.load(Ordering::Relaxed) || best == Move::NONE {
+            return;
+        }
+
+        println!("move {}", uci::move_str(best, false));
+        stdout().flush().unwrap();
+    }
+
+    fn on_message(&self, msg: &str) {
+        println!("# {}", msg);
+    }
+}
+
+// score() converts a Value to centipawns. Mate scores are given as
+// 100000 + moves to mate, as understood by XBoard.
+
+fn score(v: Value) -> i32 {
+    if v.abs() < Value::MATE - Value(MAX_PLY) {
+        return v.0 * 100 / PawnValueEg.0;
+    }
+
+    if v > Value::ZERO {
+        100000 + ((Value::MATE - v).0 + 1) / 2
+    } else {
+        -100000 + (-Value::MATE - v).0 / 2
+    }
+}
+
+// XBoard is the state of the game and of the clocks. Times are in
+// milliseconds.
+
+struct XBoard {
+    listener: Arc<XBoardListener>,
+    fen: String,
+    // Game ply of the start position
+    start_ply: i32,
+    moves: Vec<String>,
+    // In force mode the engine only checks the moves it receives
+    force: bool,
+    analyzing: bool,
+    // Running search and whether it is looking for a move to play
+    search: Option<(SearchHandle, bool)>,
+    moves_per_session: i32,
+    inc: i64,
+    time: i64,
+    otim: i64,
+    movetime: i64,
+    depth: u32,
+}
+
+impl XBoard {
+    fn new() -> XBoard {
+        XBoard {
+            listener: Arc::new(XBoardListener {
+                post: AtomicBool::new(false),
+                analyzing: AtomicBool::new(false),
+            }),
+            fen: String::from(uci::START_FEN),
+            start_ply: 0,
+            moves: Vec::new(),
+            force: false,
+            analyzing: false,
+            search: None,
+            // Same default as XBoard: 40 moves in 5 minutes
+            moves_per_session: 40,
+            inc: 0,
+            time: 300_000,
+            otim: 300_000,
+            movetime: 0,
+            depth: 0,
+        }
+    }
+
+    // set_position() sets up the game on the engine. Moves that cannot be
+    // played, which would be a bug, are reported and dropped from the game,
+    // so that the game and the engine agree again.
+
+    fn set_position(&mut self, engine: &mut Engine) {
+        let moves: Vec<&str> = self.moves.iter().map(|m| m.as_str()).collect();
+        if let Err(e) = engine.set_position(&self.fen, &moves) {
+            println!("Error (invalid position): {}", e);
+            return;
+        }
+
+        let played = (engine.position().game_ply() - self.start_ply) as usize;
+        if played < self.moves.len() {
+            println!("Error (illegal move): {}", self.moves[played]);
+            self.moves.truncate(played);
+        }
+    }
+
+    // finish() waits for the running search, if any, and adds the move it
+    // played to the game. An analysis is always stopped, a search for a
+    // move only if stop is set, which makes the engine move at once.
+
+    fn finish(&mut self, engine: &mut Engine, stop: bool) {
+        if let Some((handle, play)) = self.search.take() {
+            if stop || !play {
+                handle.stop();
+            }
+            let outcome = handle.wait();
+            if play && outcome.best != Move::NONE {
+                self.moves.push(uci::move_str(outcome.best, false));
+                self.set_position(engine);
+                self.check_result(engine);
+            }
+        }
+    }
+
+    // thinking() returns true while a search for a move is running
+
+    fn thinking(&self) -> bool {
+        matches!(self.search, Some((_, true)))
+    }
+
+    // check_result() prints the result if the game is over by the rules and
+    // returns true in that case
+
+    fn check_result(&self, engine: &Engine) -> bool {
+        match tournament::game_end(engine.position()) {
+            Some((result, reason)) => {
+                println!("{} {{{}}}", result, reason);
+                stdout().flush().unwrap();
+                true
+            }
+            None => false,
+        }
+    }
+
+    // limits() returns the search limits for a move of the side to move.
+    // Sessions of a conventional clock start with the game, at 'new' or
+    // 'setboard', whatever the move number of the position.
+
+    fn limits(&self, engine: &Engine) -> LimitsType {
+        let mut limits = LimitsType::new();
+
+        if self.movetime != 0 {
+            limits.movetime = self.movetime;
+        } else {
+            let us = engine.position().side_to_move();
+            limits.time[us.0 as usize] = self.time;
+            limits.time[(!us).0 as usize] = self.otim;
+            limits.inc = [self.inc; 2];
+            if self.moves_per_session != 0 {
+                // Whoever moved first, the side to move has played half of
+                // the moves, rounded down
+                let moves_played = self.moves.len() as i32 / 2;
+                limits.movestogo = self.moves_per_session - moves_played % self.moves_per_session;
+            }
+        }
+        limits.depth = self.depth;
+
+        limits
+    }
+
+    // think() starts searching for a move for the side to move
+
+    fn think(&mut self, engine: &mut Engine) {
+        let limits = self.limits(engine);
+        self.search = Some((engine.go(&limits, Vec::new(), false), true));
+    }
+
+    fn analyze(&mut self, engine: &mut Engine) {
+        let mut limits = LimitsType::new();
+        limits.infinite = true;
+        self.search = Some((engine.go(&limits, Vec::new(), false), false));
+    }
+
+    fn set_analyzing(&mut self, b: bool) {
+        self.analyzing = b;
+        self.listener.analyzing.store(b, Ordering::Relaxed);
+    }
+
+    // user_move() plays a move of the opponent and, unless in force mode,
+    // starts thinking about the reply
+
+    fn user_move(&mut self, engine: &mut Engine, token: &str) {
+        let m = uci::to_move(engine.position(), token);
+        if m == Move::NONE {
+            println!("Illegal move: {}", token);
+            return;
+        }
+
+        self.moves.push(uci::move_str(m, false));
+        self.set_position(engine);
+
+        if !self.check_result(engine) && !self.force && !self.analyzing {
+            self.think(engine);
+        }
+    }
+
+    // level() sets up a conventional or incremental clock from "level
+    // <moves> <base> <inc>", where base is in minutes or minutes:seconds
+    // and inc in seconds
+
+    fn level(&mut self, args: &[&str]) -> Result<(), String> {
+        if args.len() != 3 {
+            return Err(String::from("level"));
+        }
+
+        let base = match args[1].find(':') {
+            Some(i) => number::<i64>(&args[1][..i])? * 60 + number::<i64>(&args[1][i + 1..])?,
+            None => number::<i64>(args[1])? * 60,
+        };
+
+        self.moves_per_session = number(args[0])?;
+        self.inc = (number::<f64>(args[2])? * 1000.0) as i64;
+        self.time = base * 1000;
+        self.otim = base * 1000;
+        self.movetime = 0;
+        Ok(())
+    }
+
+    // execute() runs one command. It returns false on 'quit'.
+
+    fn execute(&mut self, engine: &mut Engine, line: &str) -> bool {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let (cmd, args) = match tokens.split_first() {
+            Some((cmd, args)) => (*cmd, args),
+            None => return true,
+        };
+
+        let result = match cmd {
+            "protover" => {
+                let name = misc::engine_info(true);
+                println!("feature done=0");
+                println!(
+                    "feature myname=\"{}\" setboard=1 usermove=1 ping=1 analyze=1 colors=0 \
+                     sigint=0 sigterm=0 reuse=1 variants=\"normal\"",
+                    name.lines().next().unwrap()
+                );
+                println!("feature done=1");
+                Ok(())
+            }
+            "new" => {
+                self.finish(engine, true);
+                self.fen = String::from(uci::START_FEN);
+                self.start_ply = 0;
+                self.moves.clear();
+                self.force = false;
+                self.movetime = 0;
+                self.depth = 0;
+                engine.new_game();
+                self.set_position(engine);
+                Ok(())
+            }
+            "force" | "result" => {
+                self.finish(engine, true);
+                self.force = true;
+                Ok(())
+            }
+            "go" => {
+                self.finish(engine, false);
+                self.force = false;
+                self.think(engine);
+                Ok(())
+            }
+            "?" => {
+                self.finish(engine, true);
+                Ok(())
+            }
+            "usermove" => match args.first() {
+                Some(token) => {
+                    self.finish(engine, false);
+                    self.user_move(engine, token);
+                    Ok(())
+                }
+                None => Err(String::from(cmd)),
+            },
+            "level" => self.level(args),
+            "st" => args
+                .first()
+                .ok_or_else(|| String::from(cmd))
+                .and_then(|&t| number::<i64>(t))
+                .map(|st| self.movetime = st * 1000),
+            "sd" => args
+                .first()
+                .ok_or_else(|| String::from(cmd))
+                .and_then(|&t| number(t))
+                .map(|sd| self.depth = sd),
+            "time" => args
+                .first()
+                .ok_or_else(|| String::from(cmd))
+                .and_then(|&t| number::<i64>(t))
+                .map(|t| self.time = t * 10),
+            "otim" => args
+                .first()
+                .ok_or_else(|| String::from(cmd))
+                .and_then(|&t| number::<i64>(t))
+                .map(|t| self.otim = t * 10),
+            "post" | "nopost" => {
+                self.listener.post.store(cmd == "post", Ordering::Relaxed);
+                Ok(())
+            }
+            "analyze" => {
+                self.finish(engine, true);
+                self.set_analyzing(true);
+                Ok(())
+            }
+            "exit" => {
+                self.finish(engine, true);
+                self.set_analyzing(false);
+                Ok(())
+            }
+            "undo" | "remove" => {
+                self.finish(engine, true);
+                let n = if cmd == "undo" { 1 } else { 2 };
+                if self.moves.len() >= n {
+                    let len = self.moves.len() - n;
+                    self.moves.truncate(len);
+                    self.set_position(engine);
+                }
+                Ok(())
+            }
+            "setboard" => {
+                self.finish(engine, true);
+                let fen = args.join(" ");
+                match engine.set_position(&fen, &[]) {
+                    Ok(()) => {
+                        self.fen = fen;
+                        self.start_ply = engine.position().game_ply();
+                        self.moves.clear();
+                    }
+                    Err(e) => println!("tellusererror Illegal position: {}", e),
+                }
+                Ok(())
+            }
+            "ping" => {
+                // All earlier commands are done, so the reply is immediate.
+                // A move still being searched follows the pong, as the
+                // protocol allows.
+                println!("pong {}", args.join(" "));
+                Ok(())
+            }
+            "quit" => {
+                self.finish(engine, true);
+                return false;
+            }
+            // Commands we accept but ignore
+            "xboard" | "accepted" | "rejected" | "random" | "hard" | "easy" | "computer"
+            | "name" | "rating" | "ics" | "." => Ok(()),
+            _ => {
+                println!("Error (unknown command): {}", line.trim());
+                return true;
+            }
+        };
+
+        if let Err(cmd) = result {
+            println!("Error (bad arguments): {}", cmd);
+        }
+
+        // An analysis restarts after every change of the position
+        if self.analyzing && self.search.is_none() {
+            self.analyze(engine);
+        }
+
+        true
+    }
+}
+
+fn number<T: FromStr>(token: &str) -> Result<T, String> {
+    token.parse().map_err(|_| String::from(token))
+}
+
+// cmd_loop() runs the XBoard protocol on stdin and stdout until 'quit' or
+// the end of the input. While thinking, it also checks every POLL whether
+// the search has ended, to play its move.
+
+pub fn cmd_loop(engine: &mut Engine) {
+    let mut xboard = XBoard::new();
+    engine.set_listener(xboard.listener.clone());
+    xboard.set_position(engine);
+
+    let (tx, rx) = channel();
+    thread::spawn(move || {
+        let stdin = std::io::stdin();
+        for line in stdin.lock().lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => break,
+            };
+            if tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    loop {
+        let received = if xboard.thinking() {
+            rx.recv_timeout(POLL)
+        } else {
+            rx.recv().map_err(|_| RecvTimeoutError::Disconnected)
+        };
+
+        let line = match received {
+            Ok(line) => line,
+            Err(RecvTimeoutError::Timeout) => {
+                let done = match xboard.search {
+                    Some((ref handle, _)) => handle.try_result().is_some(),
+                    None => false,
+                };
+                if done {
+                    xboard.finish(engine, false);
+                }
+                continue;
+            }
+            Err(RecvTimeoutError::Disconnected) => String::from("quit"),
+        };
+
+        if !xboard.execute(engine, &line) {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(xboard: &mut XBoard, engine: &mut Engine, lines: &[&str]) {
+        for line in lines {
+            assert!(xboard.execute(engine, line));
+        }
+    }
+
+    #[test]
+    fn clock_commands() {
+        let mut engine = Engine::new();
+        let mut xboard = XBoard::new();
+
+        run(&mut xboard, &mut engine, &["level 40 5 0", "time 12345", "otim 500"]);
+        assert_eq!(xboard.moves_per_session, 40);
+        assert_eq!((xboard.time, xboard.otim, xboard.inc), (123_450, 5000, 0));
+
+        let limits = xboard.limits(&engine);
+        assert_eq!(limits.time, [123_450, 5000]);
+        assert_eq!(limits.movestogo, 40);
+
+        run(&mut xboard, &mut engine, &["level 0 2:30 1.5"]);
+        assert_eq!(xboard.moves_per_session, 0);
+        assert_eq!((xboard.time, xboard.otim, xboard.inc), (150_000, 150_000, 1500));
+        assert_eq!(xboard.limits(&engine).inc, [1500, 1500]);
+
+        // Bad arguments leave the clock as it was
+        run(&mut xboard, &mut engine, &["level 40 x 0", "level 40 5", "time", "st x"]);
+        assert_eq!(xboard.moves_per_session, 0);
+        assert_eq!((xboard.time, xboard.inc, xboard.movetime), (150_000, 1500, 0));
+
+        run(&mut xboard, &mut engine, &["st 10", "sd 7"]);
+        let limits = xboard.limits(&engine);
+        assert_eq!((limits.movetime, limits.depth), (10_000, 7));
+        assert_eq!(limits.time, [0, 0]);
+    }
+
+    #[test]
+    fn moves_to_go() {
+        let mut engine = Engine::new();
+        let mut xboard = XBoard::new();
+
+        // The session starts at setboard, not at move 1
+        run(
+            &mut xboard,
+            &mut engine,
+            &[
+                "force",
+                "level 40 5 0",
+                "setboard r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 3 30",
+            ],
+        );
+        assert_eq!(xboard.limits(&engine).movestogo, 40);
+        run(&mut xboard, &mut engine, &["usermove g8f6"]);
+        assert_eq!(xboard.limits(&engine).movestogo, 40);
+        run(&mut xboard, &mut engine, &["usermove f1c4"]);
+        assert_eq!(xboard.limits(&engine).movestogo, 39);
+    }
+
+    #[test]
+    fn user_moves() {
+        let mut engine = Engine::new();
+        let mut xboard = XBoard::new();
+
+        run(
+            &mut xboard,
+            &mut engine,
+            &["new", "force", "usermove e2e4", "usermove e7e5", "usermove e1e3", "usermove g1f3"],
+        );
+        assert_eq!(xboard.moves, ["e2e4", "e7e5", "g1f3"]);
+        assert_eq!(engine.position().game_ply(), 3);
+        assert!(!xboard.thinking());
+
+        run(&mut xboard, &mut engine, &["undo"]);
+        assert_eq!(xboard.moves, ["e2e4", "e7e5"]);
+        run(&mut xboard, &mut engine, &["remove"]);
+        assert!(xboard.moves.is_empty());
+        assert_eq!(engine.position().fen(), uci::START_FEN);
+
+        // Nothing to take back
+        run(&mut xboard, &mut engine, &["usermove d2d4", "remove"]);
+        assert_eq!(xboard.moves, ["d2d4"]);
+    }
+
+    #[test]
+    fn bad_setboard() {
+        let mut engine = Engine::new();
+        let mut xboard = XBoard::new();
+
+        run(
+            &mut xboard,
+            &mut engine,
+            &[
+                "force",
+                "setboard 4k3/8/8/8/8/8/4P3/4K3 w - - 0 60",
+                "usermove e2e4",
+                "setboard 8/8/8/8/8/8/8/8 w - - 0 1",
+            ],
+        );
+        assert_eq!(xboard.fen, "4k3/8/8/8/8/8/4P3/4K3 w - - 0 60");
+        assert_eq!(xboard.moves, ["e2e4"]);
+        assert_eq!(engine.position().fen(), "4k3/8/8/8/4P3/8/8/4K3 b - - 0 60");
+    }
+
+    #[test]
+    fn move_now() {
+        let mut engine = Engine::new();
+        let mut xboard = XBoard::new();
+        engine.set_listener(xboard.listener.clone());
+
+        run(&mut xboard, &mut engine, &["new", "st 60", "usermove e2e4"]);
+        assert!(xboard.thinking());
+        run(&mut xboard, &mut engine, &["ping 1", "?"]);
+        assert!(!xboard.thinking());
+        assert_eq!(xboard.moves.len(), 2);
+        assert_eq!(engine.position().game_ply(), 2);
+    }
+}
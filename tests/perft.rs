@@ -0,0 +1,202 @@
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+// Perft results of reference positions, which verify the legal move
+// generator. The default tier only runs the depths with a small number of
+// leaf nodes, so that it stays fast in debug builds. The deep tier runs all
+// of them:
+//
+//   cargo test --release --test perft -- --ignored
+
+extern crate rustfish;
+
+use rustfish::perft;
+use rustfish::perft::PerftTable;
+use rustfish::position::Position;
+use rustfish::Engine;
+
+// Depths with more leaf nodes than this only run in the deep tier
+const FAST_NODES: u64 = 500_000;
+
+// The perft results of a position from depth 1 on
+struct PerftCase {
+    fen: &'static str,
+    counts: &'static [u64],
+}
+
+// Standard test positions: the initial position, "Kiwipete" and positions
+// 3 to 6 of the Chess Programming Wiki, with position 4 also mirrored
+const STANDARD: &[PerftCase] = &[
+    PerftCase {
+        fen: "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        counts: &[20, 400, 8902, 197281, 4865609, 119060324],
+    },
+    PerftCase {
+        fen: "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        counts: &[48, 2039, 97862, 4085603, 193690690],
+    },
+    PerftCase {
+        fen: "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        counts: &[14, 191, 2812, 43238, 674624, 11030083, 178633661],
+    },
+    PerftCase {
+        fen: "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        counts: &[6, 264, 9467, 422333, 15833292],
+    },
+    PerftCase {
+        fen: "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+        counts: &[6, 264, 9467, 422333, 15833292],
+    },
+    PerftCase {
+        fen: "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        counts: &[44, 1486, 62379, 2103487, 89941194],
+    },
+    PerftCase {
+        fen: "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+        counts: &[46, 2079, 89890, 3894594, 164075551],
+    },
+];
+
+// Positions of the Chess960 perft suite, with Shredder-FEN castling rights
+const CHESS960: &[PerftCase] = &[
+    PerftCase {
+        fen: "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+        counts: &[21, 528, 12189, 326672, 8146062],
+    },
+    PerftCase {
+        fen: "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9",
+        counts: &[21, 807, 18002, 667366, 16253601],
+    },
+    PerftCase {
+        fen: "b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9",
+        counts: &[20, 479, 10471, 273318, 6417013],
+    },
+    PerftCase {
+        fen: "qbbnnrkr/2pp2pp/p7/1p2pp2/8/P3PP2/1PPP1KPP/QBBNNR1R w hf - 0 9",
+        counts: &[22, 593, 13440, 382958, 9183776],
+    },
+    PerftCase {
+        fen: "1nbbnrkr/p1p1ppp1/3p4/1p3P1p/3Pq2P/8/PPP1P1P1/QNBBNRKR w HFhf - 0 9",
+        counts: &[28, 1120, 31058, 1171749, 34030312],
+    },
+];
+
+// Positions testing a single rule, with the result at one depth
+const TRICKY: &[(&str, u32, u64)] = &[
+    // En passant captures that are illegal or give check
+    ("3k4/3p4/8/K1P4r/8/8/8/8 b - - 0 1", 6, 1134888),
+    ("8/8/4k3/8/2p5/8/B2P2K1/8 w - - 0 1", 6, 1015133),
+    ("8/8/1k6/2b5/2pP4/8/5K2/8 b - d3 0 1", 6, 1440467),
+    // Castling that gives check, loses or is prevented
+    ("5k2/8/8/8/8/8/8/4K2R w K - 0 1", 6, 661072),
+    ("3k4/8/8/8/8/8/8/R3K3 w Q - 0 1", 6, 803711),
+    ("r3k2r/1b4bq/8/8/8/8/7B/R3K2R w KQkq - 0 1", 4, 1274206),
+    ("r3k2r/8/3Q4/8/8/5q2/8/R3K2R b KQkq - 0 1", 4, 1720476),
+    // Promotions out of check and giving check
+    ("2K2r2/4P3/8/8/8/8/8/3k4 w - - 0 1", 6, 3821001),
+    ("4k3/1P6/8/8/8/8/K7/8 w - - 0 1", 6, 217342),
+    ("8/P1k5/K7/8/8/8/8/8 w - - 0 1", 6, 92683),
+    // Discovered and double checks
+    ("8/8/1P2K3/8/2n5/1q6/8/5k2 b - - 0 1", 5, 1004658),
+    ("8/8/2k5/5q2/5n2/8/5K2/8 b - - 0 1", 4, 23527),
+    // Stalemate and checkmate
+    ("K1k5/8/P7/8/8/8/8/8 w - - 0 1", 6, 2217),
+    ("8/k1P5/8/1K6/8/8/8/8 w - - 0 1", 7, 567584),
+];
+
+// position() sets up the given position. Creating an engine initializes
+// the lookup tables the move generator relies on.
+
+fn position(fen: &str, chess960: bool) -> Box<Position> {
+    let _ = Engine::new();
+    Box::new(Position::from_fen(fen, chess960).unwrap())
+}
+
+// check() compares the perft results of the cases up to max_nodes leaf
+// nodes per depth
+
+fn check(cases: &[PerftCase], chess960: bool, max_nodes: u64) {
+    for case in cases {
+        let mut pos = position(case.fen, chess960);
+        for (i, &expected) in case.counts.iter().enumerate() {
+            if expected > max_nodes {
+                break;
+            }
+            let depth = i as i32 + 1;
+            assert_eq!(
+                perft::perft(&mut pos, depth),
+                expected,
+                "perft {} of {}",
+                depth,
+                case.fen
+            );
+        }
+    }
+}
+
+fn check_tricky(max_nodes: u64) {
+    for &(fen, depth, expected) in TRICKY {
+        if expected > max_nodes {
+            continue;
+        }
+        let mut pos = position(fen, false);
+        assert_eq!(
+            perft::perft(&mut pos, depth as i32),
+            expected,
+            "perft {} of {}",
+            depth,
+            fen
+        );
+    }
+}
+
+#[test]
+fn standard() {
+    check(STANDARD, false, FAST_NODES);
+}
+
+#[test]
+fn chess960() {
+    check(CHESS960, true, FAST_NODES);
+}
+
+#[test]
+fn tricky() {
+    check_tricky(FAST_NODES);
+}
+
+// The divided counts and the counts with a hash table must add up to the
+// plain perft result
+
+#[test]
+fn divide_and_hash() {
+    let case = &STANDARD[1];
+    let mut pos = position(case.fen, false);
+    let mut table = PerftTable::new(1);
+
+    let mut moves = 0;
+    let total = perft::divide(&mut pos, 3, Some(&mut table), |_, _, _| moves += 1);
+    assert_eq!(moves, case.counts[0]);
+    assert_eq!(total, case.counts[2]);
+
+    // The second run finds its subtrees in the table
+    assert_eq!(perft::perft_hashed(&mut pos, 3, &mut table), case.counts[2]);
+    assert_eq!(perft::perft_hashed(&mut pos, 4, &mut table), case.counts[3]);
+}
+
+#[test]
+#[ignore]
+fn standard_deep() {
+    check(STANDARD, false, u64::MAX);
+}
+
+#[test]
+#[ignore]
+fn chess960_deep() {
+    check(CHESS960, true, u64::MAX);
+}
+
+#[test]
+#[ignore]
+fn tricky_deep() {
+    check_tricky(u64::MAX);
+}